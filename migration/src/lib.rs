@@ -1,12 +1,20 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20241104_023919_create_cities_table;
+mod m20261017_090000_create_gazetteer_table;
+mod m20261017_120000_create_geocode_cache_table;
+mod m20261017_120100_create_forecast_cache_table;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20241104_023919_create_cities_table::Migration)]
+        vec![
+            Box::new(m20241104_023919_create_cities_table::Migration),
+            Box::new(m20261017_090000_create_gazetteer_table::Migration),
+            Box::new(m20261017_120000_create_geocode_cache_table::Migration),
+            Box::new(m20261017_120100_create_forecast_cache_table::Migration),
+        ]
     }
 }
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Gazetteer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Gazetteer::Id)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Gazetteer::Name).string().not_null())
+                    .col(ColumnDef::new(Gazetteer::CountryCode).string())
+                    .col(ColumnDef::new(Gazetteer::Admin1).string())
+                    .col(ColumnDef::new(Gazetteer::Lat).float().not_null())
+                    .col(ColumnDef::new(Gazetteer::Long).float().not_null())
+                    .col(ColumnDef::new(Gazetteer::Population).big_integer())
+                    .col(ColumnDef::new(Gazetteer::Timezone).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_gazetteer_lat_long")
+                    .table(Gazetteer::Table)
+                    .col(Gazetteer::Lat)
+                    .col(Gazetteer::Long)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Gazetteer::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Gazetteer {
+    Table,
+    Id,
+    Name,
+    CountryCode,
+    Admin1,
+    Lat,
+    Long,
+    Population,
+    Timezone,
+}
@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GeocodeCache::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GeocodeCache::Key)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(GeocodeCache::Payload).text().not_null())
+                    .col(ColumnDef::new(GeocodeCache::Provider).string().not_null())
+                    .col(
+                        ColumnDef::new(GeocodeCache::FetchedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(GeocodeCache::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_geocode_cache_expires_at")
+                    .table(GeocodeCache::Table)
+                    .col(GeocodeCache::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GeocodeCache::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GeocodeCache {
+    Table,
+    Key,
+    Payload,
+    Provider,
+    FetchedAt,
+    ExpiresAt,
+}
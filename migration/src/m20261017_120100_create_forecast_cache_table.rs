@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ForecastCache::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ForecastCache::Key)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ForecastCache::Payload).text().not_null())
+                    .col(ColumnDef::new(ForecastCache::Provider).string().not_null())
+                    .col(
+                        ColumnDef::new(ForecastCache::FetchedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ForecastCache::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_forecast_cache_expires_at")
+                    .table(ForecastCache::Table)
+                    .col(ForecastCache::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ForecastCache::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ForecastCache {
+    Table,
+    Key,
+    Payload,
+    Provider,
+    FetchedAt,
+    ExpiresAt,
+}
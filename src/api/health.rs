@@ -0,0 +1,115 @@
+use crate::services::upstream::{BreakerState, BreakerStatus};
+use crate::state::AppState;
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Liveness plus the circuit breaker of every upstream API.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    /// `degraded` while any breaker is not closed; the service itself is still up.
+    status: String,
+    upstreams: Vec<BreakerStatus>,
+}
+
+/// Always answers `200`, so an upstream outage does not get the app restarted.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses((status = 200, description = "The service is up", body = HealthResponse))
+)]
+pub async fn get(State(state): State<AppState>) -> Json<HealthResponse> {
+    let upstreams: Vec<BreakerStatus> = state
+        .breakers
+        .iter()
+        .map(|breaker| breaker.status())
+        .collect();
+    let degraded = upstreams
+        .iter()
+        .any(|upstream| upstream.state != BreakerState::Closed);
+
+    Json(HealthResponse {
+        status: if degraded { "degraded" } else { "ok" }.to_string(),
+        upstreams,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::v1::weather;
+    use crate::config::UpstreamConfig;
+    use crate::test_support::{setup_test_db, MockOpenMeteo, FORECAST_PATH};
+    use axum::{http::StatusCode, routing::get, Router};
+    use axum_test::TestServer;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_health_reports_breakers() {
+        let mock = MockOpenMeteo::start().await;
+        let mut config = mock.app_config();
+        config.upstream = UpstreamConfig {
+            max_retries: 0,
+            breaker_failure_threshold: 1,
+            ..config.upstream
+        };
+        let app = Router::new()
+            .route("/api/health", get(super::get))
+            .route("/api/v1/weather", get(weather::get))
+            .with_state(AppState::new(setup_test_db().await, &config));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let body: serde_json::Value = server.get("/api/health").await.json();
+        assert_eq!(body["status"], "ok");
+        let names: Vec<&str> = body["upstreams"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|upstream| upstream["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "open_meteo_geocoding",
+                "open_meteo_forecast",
+                "open_meteo_archive",
+                "open_meteo_air_quality",
+                "open_meteo_marine"
+            ]
+        );
+
+        mock.fail_with(FORECAST_PATH, 503).await;
+        server
+            .get("/api/v1/weather")
+            .add_query_param("city", "London")
+            .await
+            .assert_status(StatusCode::BAD_GATEWAY);
+        let requests = mock.request_count().await;
+
+        let response = server.get("/api/health").await;
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["status"], "degraded");
+        let forecast = &body["upstreams"][1];
+        assert_eq!(forecast["state"], "open");
+        assert_eq!(forecast["consecutive_failures"], 1);
+        assert_eq!(forecast["retry_in_secs"], 29);
+        assert_eq!(body["upstreams"][0]["state"], "closed");
+
+        // While open, forecasts fail without reaching the upstream.
+        let response = server
+            .get("/api/v1/weather")
+            .add_query_param("city", "London")
+            .await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "upstream_unavailable");
+        assert_eq!(
+            body["detail"],
+            "A weather service is failing, so we have paused calls to it; please try again later."
+        );
+        // London's places are cached and it is known to be inland, so nothing went out.
+        assert_eq!(mock.request_count().await, requests);
+    }
+}
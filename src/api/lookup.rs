@@ -0,0 +1,474 @@
+//! Query parsing and the lookups behind the weather endpoints, shared by every version
+//! of the API and by the weather pages; each only shapes the results into its own bodies.
+
+use crate::conditional::{self, Freshness};
+use crate::errors::AppError;
+use crate::services::air_quality::AirQuality;
+use crate::services::forecast::{ForecastRequest, ForecastWindow, HourlyVariable, UnitSystem};
+use crate::services::places::{self, LocationQuery, Resolution};
+use crate::services::validation::{parse_timezone, validate_date_range, validate_forecast_window};
+use crate::services::weather_service::{marine_for, MarineData, Place, ServiceError, WeatherData};
+use crate::state::AppState;
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::IntoParams;
+
+/// Where and what to look up; a place is given by `city` or by `lat` and `lon`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QueryParams {
+    /// Place name to geocode.
+    #[param(example = "London")]
+    city: Option<String>,
+    /// Latitude, within ±90; skips geocoding together with `lon`.
+    lat: Option<f32>,
+    /// Longitude, within ±180.
+    lon: Option<f32>,
+    /// Name to show for `lat` and `lon`, e.g. that of a picked candidate; `country` and
+    /// `admin1` then describe it too.
+    #[param(example = "Springfield")]
+    name: Option<String>,
+    /// ISO 3166-1 alpha-2 code narrowing down `city`.
+    #[param(example = "GB")]
+    country: Option<String>,
+    /// First-level division (state, region) narrowing down `city`.
+    admin1: Option<String>,
+    /// Comma-separated hourly variables; defaults to `temperature_2m`.
+    variables: Option<String>,
+    /// IANA zone to report times in; defaults to the location's own.
+    tz: Option<String>,
+    /// `metric` (default), `imperial` or `si`.
+    units: Option<String>,
+    /// Forecast days from today, 1 to 16; defaults to 7.
+    days: Option<u32>,
+    /// Days of recent history before today, 0 to 92; defaults to 0.
+    past_days: Option<u32>,
+    /// First day of a history lookup, `YYYY-MM-DD`.
+    start: Option<String>,
+    /// Last day of a history lookup, inclusive.
+    end: Option<String>,
+    /// Comma-separated extras to add to a forecast, e.g. `air_quality`.
+    include: Option<String>,
+}
+
+/// Optional extras a forecast response can carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Include {
+    AirQuality,
+    Marine,
+}
+
+impl FromStr for Include {
+    type Err = ServiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "air_quality" => Ok(Self::AirQuality),
+            "marine" => Ok(Self::Marine),
+            other => Err(ServiceError::InvalidInput(format!(
+                "Unknown include {other:?}; expected air_quality or marine"
+            ))),
+        }
+    }
+}
+
+/// Where a caller's defaults differ from the API's.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Hourly variables to look up when the query names none.
+    pub variables: &'static [HourlyVariable],
+    /// Also fetch the provider's daily aggregates with a forecast.
+    pub with_daily: bool,
+    /// Units when the query names none, e.g. those chosen on an earlier visit.
+    pub remembered_units: Option<UnitSystem>,
+    /// Fetch air quality with every forecast, as if `include=air_quality` were given.
+    pub air_quality: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            variables: &[HourlyVariable::Temperature2m],
+            with_daily: false,
+            remembered_units: None,
+            air_quality: false,
+        }
+    }
+}
+
+impl QueryParams {
+    fn location(&self) -> Result<LocationQuery, ServiceError> {
+        LocationQuery::from_params(
+            self.city.as_deref(),
+            self.lat,
+            self.lon,
+            self.name.as_deref(),
+            self.country.as_deref(),
+            self.admin1.as_deref(),
+        )
+    }
+
+    fn variables(&self, options: &Options) -> Result<Vec<HourlyVariable>, ServiceError> {
+        self.variables
+            .as_deref()
+            .map_or(Ok(options.variables.to_vec()), HourlyVariable::parse_list)
+    }
+
+    fn timezone(&self) -> Result<Option<Tz>, ServiceError> {
+        self.tz.as_deref().map(parse_timezone).transpose()
+    }
+
+    fn window(&self) -> Result<ForecastWindow, ServiceError> {
+        validate_forecast_window(self.days, self.past_days)
+    }
+
+    fn includes(&self) -> Result<Vec<Include>, ServiceError> {
+        self.include
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    fn date_range(&self, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), ServiceError> {
+        match (self.start.as_deref(), self.end.as_deref()) {
+            (Some(start), Some(end)) => validate_date_range(start, end, today),
+            _ => Err(ServiceError::InvalidInput(
+                "Both start and end dates are required".to_string(),
+            )),
+        }
+    }
+
+    /// The requested units, else the remembered ones, else metric.
+    fn units(&self, options: &Options) -> Result<UnitSystem, ServiceError> {
+        match self.units.as_deref() {
+            Some(raw) => raw.parse(),
+            None => Ok(options.remembered_units.unwrap_or_default()),
+        }
+    }
+
+    /// Whether the query picks its units rather than falling back on a default.
+    pub fn names_units(&self) -> bool {
+        self.units.is_some()
+    }
+
+    /// Every parameter except those named, for a page form to resubmit alongside its own.
+    pub fn params_except(&self, excluded: &[&str]) -> Vec<(&'static str, String)> {
+        [
+            ("city", self.city.clone()),
+            ("lat", self.lat.map(|lat| lat.to_string())),
+            ("lon", self.lon.map(|lon| lon.to_string())),
+            ("name", self.name.clone()),
+            ("country", self.country.clone()),
+            ("admin1", self.admin1.clone()),
+            ("variables", self.variables.clone()),
+            ("tz", self.tz.clone()),
+            ("units", self.units.clone()),
+            ("days", self.days.map(|days| days.to_string())),
+            ("past_days", self.past_days.map(|days| days.to_string())),
+            ("start", self.start.clone()),
+            ("end", self.end.clone()),
+            ("include", self.include.clone()),
+        ]
+        .into_iter()
+        .filter(|(name, _)| !excluded.contains(name))
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+
+    /// The query picking `place` out of this search's candidates: its coordinates, name,
+    /// country, region and zone, plus every other parameter given.
+    pub fn pick(&self, place: &Place) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("lat", place.latitude.to_string()),
+            ("lon", place.longitude.to_string()),
+            ("name", place.name.clone()),
+        ];
+        params.extend(place.country_code.clone().map(|code| ("country", code)));
+        params.extend(place.admin1.clone().map(|admin1| ("admin1", admin1)));
+        if self.tz.is_none() {
+            params.extend(place.timezone.clone().map(|zone| ("tz", zone)));
+        }
+        params.extend(self.params_except(&["city", "lat", "lon", "name", "country", "admin1"]));
+        params
+    }
+}
+
+pub enum Lookup<T> {
+    Found(T),
+    /// The query matched several comparable places.
+    Ambiguous {
+        city: String,
+        places: Vec<Place>,
+    },
+}
+
+/// A forecast for one place, trimmed to the requested window.
+pub struct Forecast {
+    /// The city asked for, if the query named one.
+    pub city: Option<String>,
+    pub place: Place,
+    /// Zone of every time in the response.
+    pub zone: Tz,
+    pub units: UnitSystem,
+    pub window: ForecastWindow,
+    /// The hourly variables requested, in request order.
+    pub variables: Vec<HourlyVariable>,
+    pub weather: WeatherData,
+    /// Present with `include=air_quality`, or [`Options::air_quality`].
+    pub air_quality: Option<AirQuality>,
+    /// Why `include=air_quality` came back without air quality.
+    pub air_quality_error: Option<String>,
+    /// Present for coastal places or with `include=marine`.
+    pub marine: Option<MarineData>,
+    /// Why `include=marine` came back without marine data.
+    pub marine_error: Option<String>,
+}
+
+/// Recorded conditions for one place over a past date range.
+pub struct History {
+    pub city: Option<String>,
+    pub place: Place,
+    pub zone: Tz,
+    pub units: UnitSystem,
+    pub variables: Vec<HourlyVariable>,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub weather: WeatherData,
+}
+
+impl Forecast {
+    /// The city asked for, or else the name of the place found.
+    pub fn name(&self) -> String {
+        self.city.clone().unwrap_or_else(|| self.place.name.clone())
+    }
+}
+
+impl History {
+    /// As in [`Forecast::name`].
+    pub fn name(&self) -> String {
+        self.city.clone().unwrap_or_else(|| self.place.name.clone())
+    }
+}
+
+/// Lookup results whose responses carry validators.
+pub trait Fresh {
+    fn freshness(&self) -> Freshness;
+}
+
+impl Fresh for Forecast {
+    fn freshness(&self) -> Freshness {
+        Freshness::of(&self.weather)
+    }
+}
+
+impl Fresh for History {
+    fn freshness(&self) -> Freshness {
+        Freshness::of(&self.weather)
+    }
+}
+
+/// Reported in place of air quality or marine data when their lookup fails; the
+/// upstream's own error is only logged.
+const AIR_QUALITY_UNAVAILABLE: &str = "Air quality is unavailable right now";
+const MARINE_UNAVAILABLE: &str = "Marine data is unavailable right now";
+
+/// Hourly series the daily summary falls back on when the provider has no daily aggregates.
+const DAILY_FALLBACK_VARIABLES: [HourlyVariable; 2] =
+    [HourlyVariable::Temperature2m, HourlyVariable::Precipitation];
+
+/// Serves what was found as `body`, conditionally on `request`'s validators; an ambiguous
+/// query gets `300 Multiple Choices` with the `candidates` body.
+pub fn respond<T: Fresh, B: Serialize, C: Serialize>(
+    request: &HeaderMap,
+    result: Result<Lookup<T>, ServiceError>,
+    body: impl FnOnce(T) -> B,
+    candidates: impl FnOnce(String, Vec<Place>) -> C,
+) -> axum::response::Response {
+    let err = match result {
+        Ok(Lookup::Found(found)) => {
+            let freshness = found.freshness();
+            match serde_json::to_vec(&body(found)) {
+                Ok(json) => {
+                    return conditional::respond(request, freshness, "application/json", json)
+                }
+                Err(err) => ServiceError::from(err),
+            }
+        }
+        Ok(Lookup::Ambiguous { city, places }) => {
+            return (StatusCode::MULTIPLE_CHOICES, Json(candidates(city, places))).into_response()
+        }
+        Err(err) => err,
+    };
+    AppError::from(err).into_response()
+}
+
+async fn locate(state: &AppState, query: &QueryParams) -> Result<Lookup<Place>, ServiceError> {
+    let location = query.location()?;
+
+    match places::resolve_location(
+        state.geocoder.as_ref(),
+        state.reverse_geocoder.as_ref(),
+        &location,
+    )
+    .await?
+    {
+        Resolution::Unique(place) => Ok(Lookup::Found(place)),
+        Resolution::Ambiguous(places) => Ok(Lookup::Ambiguous {
+            city: query.city.clone().unwrap_or_default(),
+            places,
+        }),
+    }
+}
+
+pub async fn fetch_forecast(
+    state: &AppState,
+    query: &QueryParams,
+    options: &Options,
+) -> Result<Lookup<Forecast>, ServiceError> {
+    let variables = query.variables(options)?;
+    let timezone = query.timezone()?;
+    let units = query.units(options)?;
+    let window = query.window()?;
+    let includes = query.includes()?;
+    let place = match locate(state, query).await? {
+        Lookup::Found(place) => place,
+        Lookup::Ambiguous { city, places } => return Ok(Lookup::Ambiguous { city, places }),
+    };
+    let mut request = ForecastRequest::new(place.coords(), variables)
+        .with_timezone(timezone.or_else(|| place.tz()))
+        .with_units(units)
+        .with_window(window);
+    if options.with_daily {
+        request = request.with_daily();
+    }
+    let marine_requested = includes.contains(&Include::Marine);
+    let (weather, air_quality, marine) = tokio::join!(
+        state.weather.fetch_weather(&request),
+        async {
+            if options.air_quality || includes.contains(&Include::AirQuality) {
+                Some(state.air_quality.fetch_air_quality(&request).await)
+            } else {
+                None
+            }
+        },
+        marine_for(state.marine.as_ref(), &request, marine_requested)
+    );
+    let mut weather = weather?;
+    let (air_quality, air_quality_error) = match air_quality {
+        Some(Ok(air_quality)) => (Some(air_quality), None),
+        Some(Err(err)) => {
+            warn!("Air quality unavailable: {err}");
+            (None, Some(AIR_QUALITY_UNAVAILABLE.to_string()))
+        }
+        None => (None, None),
+    };
+    let (marine, marine_error) = match marine {
+        Ok(marine) => (marine, None),
+        Err(err) => {
+            warn!("Marine data unavailable: {err}");
+            (
+                None,
+                marine_requested.then(|| MARINE_UNAVAILABLE.to_string()),
+            )
+        }
+    };
+    weather.keep_days(window.total_days());
+
+    Ok(Lookup::Found(Forecast {
+        city: query.city.clone(),
+        place,
+        // Honour an explicit zone even if the provider answered in another one.
+        zone: timezone.unwrap_or(weather.timezone),
+        units,
+        window,
+        variables: request.variables,
+        weather,
+        air_quality,
+        air_quality_error,
+        marine,
+        marine_error,
+    }))
+}
+
+/// A forecast to summarize per day, in the zone the provider grouped its days by.
+pub async fn fetch_daily(
+    state: &AppState,
+    query: &QueryParams,
+) -> Result<Lookup<Forecast>, ServiceError> {
+    let timezone = query.timezone()?;
+    let units = query.units(&Options::default())?;
+    let window = query.window()?;
+    let place = match locate(state, query).await? {
+        Lookup::Found(place) => place,
+        Lookup::Ambiguous { city, places } => return Ok(Lookup::Ambiguous { city, places }),
+    };
+    let request = ForecastRequest::new(place.coords(), DAILY_FALLBACK_VARIABLES.to_vec())
+        .with_daily()
+        .with_timezone(timezone.or_else(|| place.tz()))
+        .with_units(units)
+        .with_window(window);
+    let mut weather = state.weather.fetch_weather(&request).await?;
+    weather.keep_days(window.total_days());
+
+    Ok(Lookup::Found(Forecast {
+        city: query.city.clone(),
+        place,
+        zone: weather.timezone,
+        units,
+        window,
+        variables: request.variables,
+        weather,
+        air_quality: None,
+        air_quality_error: None,
+        marine: None,
+        marine_error: None,
+    }))
+}
+
+/// Recorded conditions; of `options`, only the default variables and units apply.
+pub async fn fetch_history(
+    state: &AppState,
+    query: &QueryParams,
+    options: &Options,
+) -> Result<Lookup<History>, ServiceError> {
+    let variables = query.variables(options)?;
+    let timezone = query.timezone()?;
+    let units = query.units(options)?;
+    let (start, end) = query.date_range(Utc::now().date_naive())?;
+    let place = match locate(state, query).await? {
+        Lookup::Found(place) => place,
+        Lookup::Ambiguous { city, places } => return Ok(Lookup::Ambiguous { city, places }),
+    };
+    let mut weather = state
+        .history
+        .fetch_historical(&place.coords(), start, end, &variables)
+        .await?;
+    weather.convert_metric(units);
+    // The archive answers in the location's zone; an explicit one also regroups the days.
+    if let Some(zone) = timezone {
+        weather.timezone = zone;
+    }
+
+    Ok(Lookup::Found(History {
+        city: query.city.clone(),
+        place,
+        zone: weather.timezone,
+        units,
+        variables,
+        start,
+        end,
+        weather,
+    }))
+}
@@ -1 +1,6 @@
+pub mod health;
+pub mod lookup;
+pub mod openapi;
+pub mod places;
+pub mod v1;
 pub mod weather;
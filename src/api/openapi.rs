@@ -0,0 +1,288 @@
+//! The `OpenAPI` document for `/api`, generated from the handlers and the types they take
+//! and return, so it cannot be edited apart from the routes it describes.
+
+use crate::api::{health, places, v1, weather};
+use crate::errors::Problem;
+use crate::state::AppState;
+use axum::{middleware, Router};
+use utoipa::OpenApi;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Weather Forecast Service",
+        description = "Forecasts, daily summaries and recorded conditions for places worldwide. \
+                       Errors are RFC 7807 problem details."
+    ),
+    tags(
+        (name = "weather", description = "Forecasts and history; `/api/v1` is the stable contract"),
+        (name = "places", description = "Place lookups"),
+        (name = "health", description = "Service and upstream status")
+    ),
+    components(schemas(Problem))
+)]
+struct ApiDoc;
+
+/// The `/api` routes, each registered together with its operation in the document.
+#[allow(deprecated)] // The unversioned weather routes are still served.
+fn routes() -> OpenApiRouter<AppState> {
+    let v1 = OpenApiRouter::new()
+        .routes(routes!(v1::places::reverse))
+        .routes(routes!(v1::weather::get))
+        .routes(routes!(v1::weather::daily))
+        .routes(routes!(v1::weather::history));
+
+    // Unversioned weather routes, superseded by v1
+    let legacy = OpenApiRouter::new()
+        .routes(routes!(weather::get))
+        .routes(routes!(weather::daily))
+        .routes(routes!(weather::history))
+        .route_layer(middleware::map_response(weather::deprecated));
+
+    let api = OpenApiRouter::new()
+        .routes(routes!(health::get))
+        .routes(routes!(places::reverse))
+        .merge(legacy)
+        .nest("/v1", v1);
+
+    OpenApiRouter::with_openapi(ApiDoc::openapi()).nest("/api", api)
+}
+
+/// The `/api` routes plus the document describing them at `/api/openapi.json`, rendered
+/// by Swagger UI at `/api/docs`.
+pub fn router() -> Router<AppState> {
+    let (router, openapi) = routes().split_for_parts();
+
+    router.merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", openapi))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use axum::http::header::CONTENT_TYPE;
+    use axum_test::TestServer;
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    /// A request for every documented operation, and the status it should get.
+    const SAMPLES: &[(&str, &str, u16)] = &[
+        ("/api/health", "", 200),
+        ("/api/places/reverse", "lat=52.52&lon=13.41", 200),
+        ("/api/places/reverse", "lat=-45&lon=170", 404),
+        (
+            "/api/weather",
+            "city=Brighton&variables=temperature_2m,precipitation&include=air_quality,marine",
+            200,
+        ),
+        ("/api/weather", "city=Springfield", 300),
+        ("/api/weather/daily", "city=Berlin", 200),
+        (
+            "/api/weather/history",
+            "city=Berlin&start=2024-03-01&end=2024-03-02",
+            200,
+        ),
+        (
+            "/api/v1/weather",
+            "city=Brighton&variables=temperature_2m,wind_speed_10m&include=air_quality,marine",
+            200,
+        ),
+        ("/api/v1/weather", "city=Springfield", 300),
+        ("/api/v1/weather", "city=Atlantis", 404),
+        ("/api/v1/weather", "city=London&include=pollen", 400),
+        ("/api/v1/weather", "city=London&days=abc", 400),
+        ("/api/v1/weather/daily", "lat=48.85&lon=2.35", 200),
+        ("/api/v1/places/reverse", "lat=52.52&lon=13.41", 200),
+        ("/api/v1/places/reverse", "lat=-45&lon=170", 404),
+        ("/api/v1/places/reverse", "lon=13.41", 400),
+        (
+            "/api/v1/weather/history",
+            "city=Berlin&start=2024-03-01&end=2024-03-02&variables=precipitation",
+            200,
+        ),
+    ];
+
+    fn spec() -> Value {
+        serde_json::to_value(routes().split_for_parts().1).unwrap()
+    }
+
+    /// Checks `value` against `schema`, following `$ref`s into `spec`; returns the first
+    /// difference, located by `at`.
+    fn check(spec: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            let schema = &spec["components"]["schemas"][name];
+            assert!(!schema.is_null(), "{at}: no schema {name}");
+            return check(spec, schema, value, at);
+        }
+        for alternatives in [&schema["oneOf"], &schema["anyOf"]] {
+            if let Some(alternatives) = alternatives.as_array() {
+                return alternatives
+                    .iter()
+                    .find_map(|schema| check(spec, schema, value, at).ok())
+                    .ok_or_else(|| format!("{at}: {value} matches none of {alternatives:?}"));
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                return Err(format!("{at}: {value} is not one of {values:?}"));
+            }
+        }
+
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(kind) => vec![kind.as_str()],
+            Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let matches = |kind: &str| match kind {
+            "null" => value.is_null(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "string" => value.is_string(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => false,
+        };
+        if !types.is_empty() && !types.iter().copied().any(matches) {
+            return Err(format!("{at}: {value} is not {types:?}"));
+        }
+
+        if let Some(items) = value.as_array() {
+            for (index, item) in items.iter().enumerate() {
+                check(spec, &schema["items"], item, &format!("{at}[{index}]"))?;
+            }
+        }
+        if let Some(fields) = value.as_object() {
+            let (properties, required) = object_schema(spec, schema);
+            for name in &required {
+                if !fields.contains_key(name) {
+                    return Err(format!("{at}: missing required {name}"));
+                }
+            }
+            for (name, field) in fields {
+                let schema = properties
+                    .get(name)
+                    .ok_or_else(|| format!("{at}: undocumented field {name}"))?;
+                check(spec, schema, field, &format!("{at}.{name}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The properties and required fields of an object schema, merging `allOf` parts.
+    fn object_schema(
+        spec: &Value,
+        schema: &Value,
+    ) -> (serde_json::Map<String, Value>, BTreeSet<String>) {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return object_schema(spec, &spec["components"]["schemas"][name]);
+        }
+        let mut properties = schema["properties"]
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        let mut required: BTreeSet<String> = schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect();
+        for part in schema["allOf"].as_array().into_iter().flatten() {
+            let (more, also_required) = object_schema(spec, part);
+            properties.extend(more);
+            required.extend(also_required);
+        }
+        (properties, required)
+    }
+
+    #[test]
+    fn test_samples_cover_every_operation() {
+        let spec = spec();
+        let mut documented = BTreeSet::new();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                documented.insert(format!("{method} {path}"));
+            }
+        }
+        let sampled: BTreeSet<String> = SAMPLES
+            .iter()
+            .map(|(path, _, _)| format!("get {path}"))
+            .collect();
+
+        assert_eq!(documented, sampled);
+    }
+
+    #[test]
+    fn test_unversioned_weather_operations_are_deprecated() {
+        let spec = spec();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            let deprecated = item["get"]["deprecated"].as_bool().unwrap_or_default();
+            assert_eq!(deprecated, path.starts_with("/api/weather"), "{path}");
+        }
+    }
+
+    /// Every documented operation is routed, and answers with a documented status and a
+    /// body matching the documented schema.
+    #[tokio::test]
+    async fn test_router_matches_document() {
+        let spec = spec();
+        let server =
+            TestServer::new(router().with_state(test_state().await).into_make_service()).unwrap();
+
+        for &(path, query, status) in SAMPLES {
+            let response = server.get(&format!("{path}?{query}")).await;
+            let at = format!("GET {path}?{query}");
+            assert_eq!(response.status_code().as_u16(), status, "{at}");
+
+            let documented = &spec["paths"][path]["get"]["responses"][status.to_string()];
+            assert!(!documented.is_null(), "{at}: status {status} undocumented");
+            let content_type = response.header(CONTENT_TYPE);
+            let content_type = content_type.to_str().unwrap();
+            let schema = &documented["content"][content_type]["schema"];
+            assert!(!schema.is_null(), "{at}: {content_type} undocumented");
+            let body: Value = response.json();
+            if let Err(difference) = check(&spec, schema, &body, "body") {
+                panic!("{at}: {difference}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_document_is_served() {
+        let server =
+            TestServer::new(router().with_state(test_state().await).into_make_service()).unwrap();
+
+        let response = server.get("/api/openapi.json").await;
+
+        assert_eq!(response.status_code(), 200);
+        let document: Value = response.json();
+        assert_eq!(document, spec());
+        assert_eq!(document["info"]["title"], "Weather Forecast Service");
+        assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+    }
+
+    #[tokio::test]
+    async fn test_docs_are_served_without_a_cdn() {
+        let server =
+            TestServer::new(router().with_state(test_state().await).into_make_service()).unwrap();
+
+        let response = server.get("/api/docs/").await;
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("swagger-ui-bundle.js"));
+        assert!(!html.contains("https://"));
+
+        let response = server.get("/api/docs/swagger-initializer.js").await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("/api/openapi.json"));
+
+        let response = server.get("/api/docs/swagger-ui-bundle.js").await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("SwaggerUIBundle"));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::errors::AppError;
+use crate::extract::Query;
+use crate::services::reverse_geocoder::NearbyPlace;
+use crate::services::validation::validate_coordinates;
+use crate::services::weather_service::ServiceError;
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReverseParams {
+    /// Within ±90.
+    #[param(example = 52.52)]
+    lat: f32,
+    /// Within ±180.
+    #[param(example = 13.41)]
+    lon: f32,
+}
+
+/// The named place nearest to some coordinates, from the gazetteer and past searches.
+#[utoipa::path(
+    get,
+    path = "/places/reverse",
+    tag = "places",
+    params(ReverseParams),
+    responses(
+        (status = 200, description = "The nearest place", body = NearbyPlace),
+        AppError,
+    )
+)]
+pub async fn reverse(
+    State(state): State<AppState>,
+    Query(query): Query<ReverseParams>,
+) -> impl IntoResponse {
+    match lookup(&state, &query).await {
+        Ok(nearby) => (StatusCode::OK, Json(nearby)).into_response(),
+        Err(err) => AppError::from(err).into_response(),
+    }
+}
+
+pub async fn lookup(state: &AppState, query: &ReverseParams) -> Result<NearbyPlace, ServiceError> {
+    let coords = validate_coordinates(query.lat, query.lon)?;
+    state.reverse_geocoder.reverse_geocode(&coords).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use axum::{routing::get, Router};
+    use axum_test::TestServer;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_reverse_geocode_api() {
+        let app = Router::new()
+            .route("/api/places/reverse", get(reverse))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/places/reverse")
+            .add_query_param("lat", "52.52")
+            .add_query_param("lon", "13.40")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: NearbyPlace = response.json();
+        assert_eq!(body.place.name, "Berlin");
+        assert!(body.distance_km < 2.0);
+
+        let response = server
+            .get("/api/places/reverse")
+            .add_query_param("lat", "-45.0")
+            .add_query_param("lon", "170.0")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "place_not_found");
+
+        let response = server
+            .get("/api/places/reverse")
+            .add_query_param("lat", "120")
+            .add_query_param("lon", "0")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_geocode_api_rejects_malformed_queries() {
+        let app = Router::new()
+            .route("/api/places/reverse", get(reverse))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/places/reverse")
+            .add_query_param("lat", "x")
+            .add_query_param("lon", "13.40")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "invalid_request");
+
+        let response = server
+            .get("/api/places/reverse")
+            .add_query_param("lon", "13.40")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "invalid_request");
+        assert!(body["detail"]
+            .as_str()
+            .unwrap()
+            .contains("missing field `lat`"));
+    }
+}
@@ -0,0 +1,7 @@
+//! Version 1 of the JSON API, served under `/api/v1`.
+//!
+//! Bodies are a stable contract: within v1 fields may be added, but none are renamed,
+//! retyped or removed.
+
+pub mod places;
+pub mod weather;
@@ -0,0 +1,84 @@
+use crate::api::places::{self, ReverseParams};
+use crate::api::v1::weather::Location;
+use crate::errors::AppError;
+use crate::extract::Query;
+use crate::services::reverse_geocoder;
+use crate::state::AppState;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The named place nearest to some coordinates, served by `/api/v1/places/reverse`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::NearbyPlace)]
+pub struct NearbyPlace {
+    pub location: Location,
+    /// From the coordinates asked for to the place.
+    pub distance_km: f64,
+}
+
+impl From<reverse_geocoder::NearbyPlace> for NearbyPlace {
+    fn from(nearby: reverse_geocoder::NearbyPlace) -> Self {
+        Self {
+            location: nearby.place.into(),
+            distance_km: nearby.distance_km,
+        }
+    }
+}
+
+/// The named place nearest to some coordinates, from the gazetteer and past searches.
+#[utoipa::path(
+    get,
+    path = "/places/reverse",
+    tag = "places",
+    params(ReverseParams),
+    responses(
+        (status = 200, description = "The nearest place", body = NearbyPlace),
+        AppError,
+    )
+)]
+pub async fn reverse(
+    State(state): State<AppState>,
+    Query(query): Query<ReverseParams>,
+) -> impl IntoResponse {
+    match places::lookup(&state, &query).await {
+        Ok(nearby) => (StatusCode::OK, Json(NearbyPlace::from(nearby))).into_response(),
+        Err(err) => AppError::from(err).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use axum::{routing::get, Router};
+    use axum_test::TestServer;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_reverse_geocode_body() {
+        let app = Router::new()
+            .route("/api/v1/places/reverse", get(reverse))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/v1/places/reverse")
+            .add_query_param("lat", "52.52")
+            .add_query_param("lon", "13.40")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["location"]["name"], "Berlin");
+        assert!(body.get("place").is_none());
+        assert!(body["distance_km"].as_f64().unwrap() < 2.0);
+
+        let response = server
+            .get("/api/v1/places/reverse")
+            .add_query_param("lat", "x")
+            .add_query_param("lon", "13.40")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+    }
+}
@@ -0,0 +1,861 @@
+use crate::api::lookup::{self, Options, QueryParams};
+use crate::errors::AppError;
+use crate::extract::Query;
+use crate::services::air_quality::{self, AirQualityReading};
+use crate::services::daily::{self, DaySummary};
+use crate::services::forecast::{self, ForecastWindow, HourlyVariable};
+use crate::services::weather_service::{HourlyData, MarineData, MarineHourly, Place, WeatherData};
+use crate::services::ProviderKind;
+use crate::state::AppState;
+use axum::{extract::State, http::HeaderMap, response::IntoResponse};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Hourly forecast, served by `/api/v1/weather`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Forecast)]
+pub struct Forecast {
+    pub location: Location,
+    /// IANA zone of every time in the response.
+    pub timezone: String,
+    pub units: Units,
+    pub provider: Provider,
+    pub window: Window,
+    /// The hourly variables requested, in request order.
+    pub variables: Vec<Variable>,
+    pub hourly: Vec<Hour>,
+    /// Present with `include=air_quality`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub air_quality: Option<AirQuality>,
+    /// Why `include=air_quality` came back without air quality; the forecast itself is
+    /// unaffected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub air_quality_error: Option<String>,
+    /// Present for coastal places or with `include=marine`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marine: Option<Marine>,
+    /// Why `include=marine` came back without marine data; the forecast itself is unaffected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marine_error: Option<String>,
+}
+
+/// Per-day summary of the forecast, served by `/api/v1/weather/daily`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::DailyForecast)]
+pub struct DailyForecast {
+    pub location: Location,
+    /// IANA zone whose calendar days are summarized.
+    pub timezone: String,
+    pub units: Units,
+    pub provider: Provider,
+    pub window: Window,
+    pub days: Vec<Day>,
+}
+
+/// Recorded conditions over a past date range, served by `/api/v1/weather/history`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::History)]
+pub struct History {
+    pub location: Location,
+    /// IANA zone of every time in the response.
+    pub timezone: String,
+    pub units: Units,
+    pub provider: Provider,
+    pub variables: Vec<Variable>,
+    pub start: NaiveDate,
+    /// Inclusive.
+    pub end: NaiveDate,
+    /// Per-day summaries computed from the requested hourly series.
+    pub days: Vec<Day>,
+    pub hourly: Vec<Hour>,
+}
+
+/// Body of a 300 Multiple Choices reply: the query matched several comparable places.
+///
+/// Repeat the request with `country` and/or `admin1` taken from a candidate to pin it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Candidates)]
+pub struct Candidates {
+    /// The city asked for.
+    pub query: String,
+    pub candidates: Vec<Location>,
+}
+
+/// The place a response is for.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Location)]
+pub struct Location {
+    pub name: String,
+    pub latitude: f32,
+    pub longitude: f32,
+    /// Metres above sea level.
+    pub elevation: Option<f32>,
+    /// ISO 3166-1 alpha-2.
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    /// First-level division, e.g. a state or region.
+    pub admin1: Option<String>,
+    /// The place's own IANA zone.
+    pub timezone: Option<String>,
+    pub population: Option<u64>,
+}
+
+/// Units of the values in a response; percentages and degrees of direction never change.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Units)]
+pub struct Units {
+    pub system: UnitSystem,
+    /// Also of sea surface temperature.
+    pub temperature: String,
+    pub wind_speed: String,
+    pub precipitation: String,
+    pub wave_height: String,
+}
+
+/// The service that supplied the data.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Provider)]
+pub struct Provider {
+    pub id: ProviderId,
+    /// For attribution.
+    pub name: String,
+    /// When the provider last updated the forecast: MET Norway's model run. Open-Meteo
+    /// does not say, so its forecasts have `null`.
+    pub updated_at: Option<DateTime<Utc>>,
+    /// When the forecast was fetched, if it is past its cache lifetime: being refreshed,
+    /// or kept because the providers are failing.
+    pub stale_as_of: Option<DateTime<FixedOffset>>,
+}
+
+/// Span of days a forecast covers: `past_days` before today plus `days` from today on.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Window)]
+pub struct Window {
+    pub days: u32,
+    pub past_days: u32,
+}
+
+/// A service forecasts come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = v1::ProviderId)]
+pub enum ProviderId {
+    OpenMeteo,
+    MetNorway,
+}
+
+/// An hourly variable, named as in the `variables` parameter and the fields of `Hour`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = v1::Variable)]
+pub enum Variable {
+    #[serde(rename = "temperature_2m")]
+    Temperature2m,
+    #[serde(rename = "relative_humidity_2m")]
+    RelativeHumidity2m,
+    Precipitation,
+    PrecipitationProbability,
+    #[serde(rename = "wind_speed_10m")]
+    WindSpeed10m,
+    #[serde(rename = "wind_direction_10m")]
+    WindDirection10m,
+    #[serde(rename = "wind_gusts_10m")]
+    WindGusts10m,
+    CloudCover,
+    WeatherCode,
+}
+
+/// Units the values are in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[schema(as = v1::UnitSystem)]
+pub enum UnitSystem {
+    /// °C, km/h, mm.
+    Metric,
+    /// °F, mph, inches.
+    Imperial,
+    /// K, m/s, mm.
+    Si,
+}
+
+/// Band of an air quality index. The European index uses `good`, `fair`, `moderate`,
+/// `poor`, `very_poor` and `extremely_poor`; the US one `good`, `moderate`,
+/// `unhealthy_for_sensitive_groups`, `unhealthy`, `very_unhealthy` and `hazardous`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[schema(as = v1::AqiCategory)]
+pub enum AqiCategory {
+    Good,
+    Fair,
+    Moderate,
+    Poor,
+    VeryPoor,
+    ExtremelyPoor,
+    UnhealthyForSensitiveGroups,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+/// One hour; only the requested variables with data for that hour are present, named
+/// as in `variables`.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Hour)]
+pub struct Hour {
+    pub time: DateTime<FixedOffset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_2m: Option<f64>,
+    /// Percent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_humidity_2m: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precipitation: Option<f64>,
+    /// Percent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precipitation_probability: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_speed_10m: Option<f64>,
+    /// Degrees the wind comes from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_direction_10m: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_gusts_10m: Option<f64>,
+    /// Percent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_cover: Option<f64>,
+    /// WMO weather interpretation code, 0 to 99.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather_code: Option<u8>,
+}
+
+/// One local calendar day.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Day)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub temperature_min: Option<f64>,
+    pub temperature_max: Option<f64>,
+    pub temperature_mean: Option<f64>,
+    pub precipitation_sum: Option<f64>,
+    /// Only known when the provider reports it.
+    pub sunrise: Option<DateTime<FixedOffset>>,
+    pub sunset: Option<DateTime<FixedOffset>>,
+}
+
+/// Pollutant levels and UV index over the forecast window (at most seven days ahead).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::AirQuality)]
+pub struct AirQuality {
+    pub current: Option<AirQualityHour>,
+    /// Band of the current European AQI.
+    pub european_aqi_category: Option<AqiCategory>,
+    /// Band of the current US AQI.
+    pub us_aqi_category: Option<AqiCategory>,
+    pub hourly: Vec<AirQualityHour>,
+}
+
+/// Concentrations in µg/m³.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::AirQualityHour)]
+pub struct AirQualityHour {
+    pub time: DateTime<FixedOffset>,
+    pub pm2_5: Option<f64>,
+    pub pm10: Option<f64>,
+    pub ozone: Option<f64>,
+    pub european_aqi: Option<f64>,
+    pub us_aqi: Option<f64>,
+    pub uv_index: Option<f64>,
+}
+
+/// Sea state at the sea grid cell nearest the place.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::Marine)]
+pub struct Marine {
+    /// Centre of the grid cell.
+    pub latitude: f32,
+    pub longitude: f32,
+    /// From the place to the grid cell.
+    pub distance_km: f64,
+    /// Whether the cell is close enough for the place to count as coastal.
+    pub coastal: bool,
+    pub hourly: Vec<MarineHour>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[schema(as = v1::MarineHour)]
+pub struct MarineHour {
+    pub time: DateTime<FixedOffset>,
+    pub wave_height: Option<f64>,
+    /// Seconds.
+    pub wave_period: Option<f64>,
+    /// Degrees the swell comes from.
+    pub swell_wave_direction: Option<f64>,
+    pub sea_surface_temperature: Option<f64>,
+}
+
+impl Forecast {
+    fn new(forecast: lookup::Forecast) -> Self {
+        let lookup::Forecast {
+            place,
+            zone,
+            units,
+            window,
+            variables,
+            weather,
+            air_quality,
+            air_quality_error,
+            marine,
+            marine_error,
+            ..
+        } = forecast;
+
+        Self {
+            timezone: zone.name().to_string(),
+            units: units.into(),
+            provider: Provider::new(&weather, zone),
+            window: window.into(),
+            hourly: Hour::series(&weather.hourly, &variables, zone),
+            air_quality: air_quality.map(|air_quality| AirQuality::new(&air_quality, zone)),
+            marine: marine.map(|marine| Marine::new(&marine, &place, zone)),
+            location: place.into(),
+            variables: variables.into_iter().map(Variable::from).collect(),
+            air_quality_error,
+            marine_error,
+        }
+    }
+}
+
+impl DailyForecast {
+    fn new(forecast: lookup::Forecast) -> Self {
+        Self {
+            location: forecast.place.into(),
+            timezone: forecast.zone.name().to_string(),
+            units: forecast.units.into(),
+            provider: Provider::new(&forecast.weather, forecast.zone),
+            window: forecast.window.into(),
+            days: Day::summarize(&forecast.weather),
+        }
+    }
+}
+
+impl History {
+    fn new(history: lookup::History) -> Self {
+        let weather = &history.weather;
+
+        Self {
+            location: history.place.into(),
+            timezone: history.zone.name().to_string(),
+            units: history.units.into(),
+            provider: Provider::new(weather, history.zone),
+            start: history.start,
+            end: history.end,
+            days: Day::summarize(weather),
+            hourly: Hour::series(&weather.hourly, &history.variables, history.zone),
+            variables: history.variables.into_iter().map(Variable::from).collect(),
+        }
+    }
+}
+
+impl Candidates {
+    fn new(query: String, places: Vec<Place>) -> Self {
+        Self {
+            query,
+            candidates: places.into_iter().map(Location::from).collect(),
+        }
+    }
+}
+
+impl From<Place> for Location {
+    fn from(place: Place) -> Self {
+        Self {
+            name: place.name,
+            latitude: place.latitude,
+            longitude: place.longitude,
+            elevation: place.elevation,
+            country_code: place.country_code,
+            country: place.country,
+            admin1: place.admin1,
+            timezone: place.timezone,
+            population: place.population,
+        }
+    }
+}
+
+impl From<forecast::UnitSystem> for Units {
+    fn from(system: forecast::UnitSystem) -> Self {
+        Self {
+            system: system.into(),
+            temperature: system.temperature().to_string(),
+            wind_speed: system.wind_speed().to_string(),
+            precipitation: system.precipitation().to_string(),
+            wave_height: system.wave_height().to_string(),
+        }
+    }
+}
+
+impl Provider {
+    fn new(weather: &WeatherData, zone: Tz) -> Self {
+        Self {
+            id: weather.provider.into(),
+            name: weather.provider.label().to_string(),
+            updated_at: weather.updated_at,
+            stale_as_of: weather
+                .stale_as_of
+                .map(|time| time.with_timezone(&zone).fixed_offset()),
+        }
+    }
+}
+
+impl From<ProviderKind> for ProviderId {
+    fn from(kind: ProviderKind) -> Self {
+        match kind {
+            ProviderKind::OpenMeteo => Self::OpenMeteo,
+            ProviderKind::MetNorway => Self::MetNorway,
+        }
+    }
+}
+
+impl From<HourlyVariable> for Variable {
+    fn from(variable: HourlyVariable) -> Self {
+        match variable {
+            HourlyVariable::Temperature2m => Self::Temperature2m,
+            HourlyVariable::RelativeHumidity2m => Self::RelativeHumidity2m,
+            HourlyVariable::Precipitation => Self::Precipitation,
+            HourlyVariable::PrecipitationProbability => Self::PrecipitationProbability,
+            HourlyVariable::WindSpeed10m => Self::WindSpeed10m,
+            HourlyVariable::WindDirection10m => Self::WindDirection10m,
+            HourlyVariable::WindGusts10m => Self::WindGusts10m,
+            HourlyVariable::CloudCover => Self::CloudCover,
+            HourlyVariable::WeatherCode => Self::WeatherCode,
+        }
+    }
+}
+
+impl From<forecast::UnitSystem> for UnitSystem {
+    fn from(system: forecast::UnitSystem) -> Self {
+        match system {
+            forecast::UnitSystem::Metric => Self::Metric,
+            forecast::UnitSystem::Imperial => Self::Imperial,
+            forecast::UnitSystem::Si => Self::Si,
+        }
+    }
+}
+
+impl From<air_quality::AqiCategory> for AqiCategory {
+    fn from(category: air_quality::AqiCategory) -> Self {
+        match category {
+            air_quality::AqiCategory::Good => Self::Good,
+            air_quality::AqiCategory::Fair => Self::Fair,
+            air_quality::AqiCategory::Moderate => Self::Moderate,
+            air_quality::AqiCategory::Poor => Self::Poor,
+            air_quality::AqiCategory::VeryPoor => Self::VeryPoor,
+            air_quality::AqiCategory::ExtremelyPoor => Self::ExtremelyPoor,
+            air_quality::AqiCategory::UnhealthyForSensitiveGroups => {
+                Self::UnhealthyForSensitiveGroups
+            }
+            air_quality::AqiCategory::Unhealthy => Self::Unhealthy,
+            air_quality::AqiCategory::VeryUnhealthy => Self::VeryUnhealthy,
+            air_quality::AqiCategory::Hazardous => Self::Hazardous,
+        }
+    }
+}
+
+impl From<ForecastWindow> for Window {
+    fn from(window: ForecastWindow) -> Self {
+        Self {
+            days: window.days,
+            past_days: window.past_days,
+        }
+    }
+}
+
+impl Hour {
+    fn series(hourly: &HourlyData, variables: &[HourlyVariable], zone: Tz) -> Vec<Self> {
+        (0..hourly.time.len())
+            .map(|hour| Self::at(hourly, hour, variables, zone))
+            .collect()
+    }
+
+    fn at(hourly: &HourlyData, hour: usize, variables: &[HourlyVariable], zone: Tz) -> Self {
+        let mut values = Self {
+            time: hourly.time[hour].with_timezone(&zone).fixed_offset(),
+            ..Self::default()
+        };
+
+        for &variable in variables {
+            let value = hourly.value(variable, hour);
+            let field = match variable {
+                HourlyVariable::Temperature2m => &mut values.temperature_2m,
+                HourlyVariable::RelativeHumidity2m => &mut values.relative_humidity_2m,
+                HourlyVariable::Precipitation => &mut values.precipitation,
+                HourlyVariable::PrecipitationProbability => &mut values.precipitation_probability,
+                HourlyVariable::WindSpeed10m => &mut values.wind_speed_10m,
+                HourlyVariable::WindDirection10m => &mut values.wind_direction_10m,
+                HourlyVariable::WindGusts10m => &mut values.wind_gusts_10m,
+                HourlyVariable::CloudCover => &mut values.cloud_cover,
+                HourlyVariable::WeatherCode => {
+                    values.weather_code = value.and_then(weather_code);
+                    continue;
+                }
+            };
+            *field = value;
+        }
+
+        values
+    }
+}
+
+/// The WMO code a series value stands for; codes run from 0 to 99.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn weather_code(value: f64) -> Option<u8> {
+    (value.fract() == 0.0 && (0.0..=99.0).contains(&value)).then_some(value as u8)
+}
+
+impl Day {
+    fn summarize(weather: &WeatherData) -> Vec<Self> {
+        daily::summarize(weather)
+            .into_iter()
+            .map(Self::from)
+            .collect()
+    }
+}
+
+impl From<DaySummary> for Day {
+    fn from(summary: DaySummary) -> Self {
+        Self {
+            date: summary.date,
+            temperature_min: summary.temperature_min,
+            temperature_max: summary.temperature_max,
+            temperature_mean: summary.temperature_mean,
+            precipitation_sum: summary.precipitation_sum,
+            sunrise: summary.sunrise,
+            sunset: summary.sunset,
+        }
+    }
+}
+
+impl AirQuality {
+    fn new(air_quality: &air_quality::AirQuality, zone: Tz) -> Self {
+        let hour = |reading: &AirQualityReading| AirQualityHour::new(reading, zone);
+        let current = air_quality.current.as_ref().map(|reading| reading.values);
+
+        Self {
+            current: air_quality.current.as_ref().map(hour),
+            european_aqi_category: current
+                .and_then(|values| values.european_aqi)
+                .map(|aqi| air_quality::AqiCategory::european(aqi).into()),
+            us_aqi_category: current
+                .and_then(|values| values.us_aqi)
+                .map(|aqi| air_quality::AqiCategory::us(aqi).into()),
+            hourly: air_quality.hourly.iter().map(hour).collect(),
+        }
+    }
+}
+
+impl AirQualityHour {
+    fn new(reading: &AirQualityReading, zone: Tz) -> Self {
+        let values = reading.values;
+
+        Self {
+            time: reading.time.with_timezone(&zone).fixed_offset(),
+            pm2_5: values.pm2_5,
+            pm10: values.pm10,
+            ozone: values.ozone,
+            european_aqi: values.european_aqi,
+            us_aqi: values.us_aqi,
+            uv_index: values.uv_index,
+        }
+    }
+}
+
+impl Marine {
+    fn new(marine: &MarineData, place: &Place, zone: Tz) -> Self {
+        let coords = place.coords();
+        let hourly = &marine.hourly;
+        let value =
+            |series: &Option<Vec<Option<f64>>>, hour| MarineHourly::value(series.as_ref(), hour);
+
+        Self {
+            latitude: marine.latitude,
+            longitude: marine.longitude,
+            distance_km: (marine.distance_km(&coords) * 10.0).round() / 10.0,
+            coastal: marine.is_coastal(&coords),
+            hourly: hourly
+                .time
+                .iter()
+                .enumerate()
+                .map(|(hour, time)| MarineHour {
+                    time: time.with_timezone(&zone).fixed_offset(),
+                    wave_height: value(&hourly.wave_height, hour),
+                    wave_period: value(&hourly.wave_period, hour),
+                    swell_wave_direction: value(&hourly.swell_wave_direction, hour),
+                    sea_surface_temperature: value(&hourly.sea_surface_temperature, hour),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Hourly forecast for a place.
+#[utoipa::path(
+    get,
+    path = "/weather",
+    tag = "weather",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The forecast", body = Forecast),
+        (status = 300, description = "Several places match", body = Candidates),
+        (status = 304, description = "Not modified since the request's validators"),
+        AppError,
+    )
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<QueryParams>,
+) -> impl IntoResponse {
+    let result = lookup::fetch_forecast(&state, &query, &Options::default()).await;
+    lookup::respond(&headers, result, Forecast::new, Candidates::new)
+}
+
+/// Per-day summary of the forecast for a place.
+#[utoipa::path(
+    get,
+    path = "/weather/daily",
+    tag = "weather",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The daily summary", body = DailyForecast),
+        (status = 300, description = "Several places match", body = Candidates),
+        (status = 304, description = "Not modified since the request's validators"),
+        AppError,
+    )
+)]
+pub async fn daily(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<QueryParams>,
+) -> impl IntoResponse {
+    let result = lookup::fetch_daily(&state, &query).await;
+    lookup::respond(&headers, result, DailyForecast::new, Candidates::new)
+}
+
+/// Recorded conditions for a place over past days.
+#[utoipa::path(
+    get,
+    path = "/weather/history",
+    tag = "weather",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The recorded conditions", body = History),
+        (status = 300, description = "Several places match", body = Candidates),
+        (status = 304, description = "Not modified since the request's validators"),
+        AppError,
+    )
+)]
+pub async fn history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<QueryParams>,
+) -> impl IntoResponse {
+    let result = lookup::fetch_history(&state, &query, &Options::default()).await;
+    lookup::respond(&headers, result, History::new, Candidates::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::v1::weather;
+    use crate::test_support::{test_state, StaticProvider};
+    use axum::{http::StatusCode, routing::get, Router};
+    use axum_test::TestServer;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    async fn server() -> TestServer {
+        let app = Router::new()
+            .route("/api/v1/weather", get(weather::get))
+            .route("/api/v1/weather/daily", get(weather::daily))
+            .route("/api/v1/weather/history", get(weather::history))
+            .with_state(test_state().await);
+        TestServer::new(app.into_make_service()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_forecast_body() {
+        let server = server().await;
+
+        let response = server
+            .get("/api/v1/weather")
+            .add_query_param("city", "London")
+            .add_query_param("country", "GB")
+            .add_query_param("variables", "precipitation,wind_speed_10m,weather_code")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["location"],
+            json!({
+                "name": "London",
+                "latitude": 51.508_53,
+                "longitude": -0.125_74,
+                "elevation": null,
+                "country_code": "GB",
+                "country": null,
+                "admin1": "England",
+                "timezone": "Europe/London",
+                "population": 7_556_900,
+            })
+        );
+        assert_eq!(body["timezone"], "Europe/London");
+        assert_eq!(
+            body["units"],
+            json!({
+                "system": "metric",
+                "temperature": "°C",
+                "wind_speed": "km/h",
+                "precipitation": "mm",
+                "wave_height": "m",
+            })
+        );
+        assert_eq!(
+            body["provider"],
+            json!({
+                "id": "open_meteo",
+                "name": "Open-Meteo",
+                "updated_at": "2024-10-25T23:00:00Z",
+                "stale_as_of": null,
+            })
+        );
+        assert_eq!(body["window"], json!({"days": 7, "past_days": 0}));
+        assert_eq!(
+            body["hourly"][14],
+            json!({
+                "time": "2024-10-26T14:00:00+01:00",
+                "precipitation": 1.2,
+                "wind_speed_10m": 24.0,
+                "weather_code": 61,
+            })
+        );
+        let body: Forecast = response.json();
+        assert_eq!(
+            body.hourly.len(),
+            StaticProvider::HOURS as usize * ForecastWindow::default().total_days()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forecast_extras() {
+        let server = server().await;
+
+        let response = server
+            .get("/api/v1/weather")
+            .add_query_param("city", "Brighton")
+            .add_query_param("units", "imperial")
+            .add_query_param("include", "air_quality")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Forecast = response.json();
+        assert_eq!(body.units.wave_height, "ft");
+        let marine = body.marine.unwrap();
+        assert!(marine.coastal);
+        assert_eq!(marine.hourly[0].wave_height, Some(3.28));
+        let air_quality = body.air_quality.unwrap();
+        assert_eq!(air_quality.current.unwrap().pm2_5, Some(12.5));
+        assert_eq!(air_quality.european_aqi_category, Some(AqiCategory::Fair));
+    }
+
+    #[tokio::test]
+    async fn test_daily_and_history_bodies() {
+        let server = server().await;
+
+        let response = server
+            .get("/api/v1/weather/daily")
+            .add_query_param("city", "Berlin")
+            .add_query_param("days", "3")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: DailyForecast = response.json();
+        assert_eq!(body.location.name, "Berlin");
+        assert_eq!(body.timezone, "Europe/Berlin");
+        assert_eq!(body.provider.id, ProviderId::OpenMeteo);
+        assert_eq!(body.days.len(), 3);
+
+        let response = server
+            .get("/api/v1/weather/history")
+            .add_query_param("lat", "52.52")
+            .add_query_param("lon", "13.41")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-02")
+            .add_query_param("variables", "temperature_2m,precipitation")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: History = response.json();
+        assert_eq!(body.location.name, "Berlin");
+        assert_eq!(body.variables.len(), 2);
+        assert_eq!(body.days[1].precipitation_sum, Some(7.2));
+        assert_eq!(body.hourly.len(), 2 * StaticProvider::HOURS as usize);
+        assert_eq!(
+            body.hourly[0].time.to_rfc3339(),
+            "2024-03-01T00:00:00+01:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ambiguous_city_lists_locations() {
+        let server = server().await;
+
+        let response = server
+            .get("/api/v1/weather")
+            .add_query_param("city", "Springfield")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::MULTIPLE_CHOICES);
+        let body: Candidates = response.json();
+        assert_eq!(body.query, "Springfield");
+        assert_eq!(body.candidates.len(), 3);
+        assert!(body
+            .candidates
+            .iter()
+            .all(|location| location.country_code.as_deref() == Some("US")));
+
+        let response = server
+            .get("/api/v1/weather")
+            .add_query_param("city", "Atlantis")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+    }
+
+    #[test]
+    fn test_weather_codes_are_whole_wmo_codes() {
+        assert_eq!(weather_code(61.0), Some(61));
+        assert_eq!(weather_code(0.0), Some(0));
+        assert_eq!(weather_code(61.5), None);
+        assert_eq!(weather_code(-1.0), None);
+        assert_eq!(weather_code(100.0), None);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_query_is_a_problem() {
+        let server = server().await;
+
+        let response = server
+            .get("/api/v1/weather")
+            .add_query_param("city", "London")
+            .add_query_param("days", "abc")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.header("content-type"), "application/problem+json");
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "invalid_request");
+        assert!(body["detail"].as_str().unwrap().contains("invalid digit"));
+    }
+}
@@ -1,93 +1,492 @@
-use crate::services::weather_service::{ServiceError, WeatherService};
-use axum::{extract::Query, http::StatusCode, response::IntoResponse, Json};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
+//! The unversioned weather endpoints, kept for existing clients; new ones should use
+//! [`crate::api::v1`].
 
-#[derive(Debug, Deserialize)]
-pub struct QueryParams {
-    city: String,
-}
+use crate::api::lookup::{self, Forecast, History, Options, QueryParams};
+use crate::errors::AppError;
+use crate::extract::Query;
+use crate::services::air_quality::{AirQuality, AirQualityReading, AirQualityValues, AqiCategory};
+use crate::services::daily::{self, DaySummary};
+use crate::services::forecast::{ForecastWindow, HourlyVariable, UnitSystem};
+use crate::services::weather_service::{HourlyData, LatLong, MarineData, MarineHourly, Place};
+use crate::services::ProviderKind;
+use crate::state::AppState;
+use axum::{
+    extract::{OriginalUri, State},
+    http::{HeaderMap, HeaderValue},
+    response::IntoResponse,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Response {
     city: String,
-    temperature: Temperature,
+    place: Place,
+    /// IANA zone of every time in the response.
+    timezone: String,
+    units: Units,
+    window: ForecastWindow,
+    /// Service that supplied the forecast, the first healthy one in priority order.
+    provider: ProviderKind,
+    /// When the forecast was fetched, if it is past its cache lifetime: being refreshed,
+    /// or kept because the providers are failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stale_as_of: Option<DateTime<FixedOffset>>,
+    /// The hourly variables requested, in request order.
+    variables: Vec<HourlyVariable>,
+    /// Omitted when `temperature_2m` was not requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature: Option<Temperature>,
     hourly_forecast: Vec<HourlyForecast>,
+    /// Present with `include=air_quality`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    air_quality: Option<AirQualityResponse>,
+    /// Why `include=air_quality` came back without air quality; the forecast itself is
+    /// unaffected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    air_quality_error: Option<String>,
+    /// Present for coastal places or with `include=marine`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    marine: Option<MarineResponse>,
+    /// Why `include=marine` came back without marine data; the forecast itself is unaffected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    marine_error: Option<String>,
+}
+
+/// Sea state at the sea grid cell nearest the place.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MarineResponse {
+    /// Centre of the grid cell.
+    latitude: f32,
+    longitude: f32,
+    /// From the place to the grid cell.
+    distance_km: f64,
+    /// Whether the cell is close enough for the place to count as coastal.
+    coastal: bool,
+    wave_height_unit: String,
+    sea_surface_temperature_unit: String,
+    hourly: Vec<MarineHour>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MarineHour {
+    time: DateTime<FixedOffset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wave_height: Option<f64>,
+    /// Seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wave_period: Option<f64>,
+    /// Degrees the swell comes from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    swell_wave_direction: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sea_surface_temperature: Option<f64>,
+}
+
+impl MarineResponse {
+    fn new(marine: &MarineData, coords: &LatLong, units: UnitSystem, zone: Tz) -> Self {
+        let hourly = &marine.hourly;
+        let value =
+            |series: &Option<Vec<Option<f64>>>, hour| MarineHourly::value(series.as_ref(), hour);
+
+        Self {
+            latitude: marine.latitude,
+            longitude: marine.longitude,
+            distance_km: (marine.distance_km(coords) * 10.0).round() / 10.0,
+            coastal: marine.is_coastal(coords),
+            wave_height_unit: units.wave_height().to_string(),
+            sea_surface_temperature_unit: units.temperature().to_string(),
+            hourly: hourly
+                .time
+                .iter()
+                .enumerate()
+                .map(|(hour, time)| MarineHour {
+                    time: time.with_timezone(&zone).fixed_offset(),
+                    wave_height: value(&hourly.wave_height, hour),
+                    wave_period: value(&hourly.wave_period, hour),
+                    swell_wave_direction: value(&hourly.swell_wave_direction, hour),
+                    sea_surface_temperature: value(&hourly.sea_surface_temperature, hour),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Pollutant levels and UV index over the forecast window (at most seven days ahead).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AirQualityResponse {
+    current: Option<AirQualityHour>,
+    /// Band of the current European AQI.
+    european_aqi_category: Option<AqiCategory>,
+    /// Band of the current US AQI.
+    us_aqi_category: Option<AqiCategory>,
+    hourly: Vec<AirQualityHour>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AirQualityHour {
+    time: DateTime<FixedOffset>,
+    #[serde(flatten)]
+    values: AirQualityValues,
+}
+
+impl AirQualityResponse {
+    fn new(air_quality: &AirQuality, zone: Tz) -> Self {
+        let hour = |reading: &AirQualityReading| AirQualityHour {
+            time: reading.time.with_timezone(&zone).fixed_offset(),
+            values: reading.values,
+        };
+        let current = air_quality.current.as_ref().map(|reading| reading.values);
+
+        Self {
+            current: air_quality.current.as_ref().map(hour),
+            european_aqi_category: current
+                .and_then(|values| values.european_aqi)
+                .map(AqiCategory::european),
+            us_aqi_category: current
+                .and_then(|values| values.us_aqi)
+                .map(AqiCategory::us),
+            hourly: air_quality.hourly.iter().map(hour).collect(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Temperature {
     min: f64,
     max: f64,
+    unit: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Temperature {
+    fn of(hourly: &HourlyData, units: UnitSystem) -> Option<Self> {
+        hourly.temperature_range().map(|(min, max)| Self {
+            min,
+            max,
+            unit: units.temperature().to_string(),
+        })
+    }
+}
+
+/// Units of the values in a response; percentages and degrees of direction never change.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Units {
+    system: UnitSystem,
+    temperature: String,
+    wind_speed: String,
+    precipitation: String,
+}
+
+impl From<UnitSystem> for Units {
+    fn from(system: UnitSystem) -> Self {
+        Self {
+            system,
+            temperature: system.temperature().to_string(),
+            wind_speed: system.wind_speed().to_string(),
+            precipitation: system.precipitation().to_string(),
+        }
+    }
+}
+
+/// One hour of the forecast; only requested variables with data for that hour are present.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct HourlyForecast {
-    time: String,
-    temperature: f64,
+    time: DateTime<FixedOffset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    relative_humidity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precipitation: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precipitation_probability: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wind_speed: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wind_direction: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wind_gusts: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cloud_cover: Option<f64>,
+    /// WMO weather interpretation code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    weather_code: Option<f64>,
 }
 
-pub async fn get(Query(query): Query<QueryParams>) -> impl IntoResponse {
-    match fetch_data(&query.city).await {
-        Ok(weather) => (StatusCode::OK, Json(weather)).into_response(),
-        Err(err) => {
-            let (status, message) = match err {
-                ServiceError::CityNotFound(msg) => (StatusCode::NOT_FOUND, msg),
-                _ => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+impl HourlyForecast {
+    fn at(hourly: &HourlyData, hour: usize, variables: &[HourlyVariable], zone: Tz) -> Self {
+        let mut forecast = Self {
+            time: hourly.time[hour].with_timezone(&zone).fixed_offset(),
+            ..Self::default()
+        };
+
+        for &variable in variables {
+            let value = hourly.value(variable, hour);
+            let field = match variable {
+                HourlyVariable::Temperature2m => &mut forecast.temperature,
+                HourlyVariable::RelativeHumidity2m => &mut forecast.relative_humidity,
+                HourlyVariable::Precipitation => &mut forecast.precipitation,
+                HourlyVariable::PrecipitationProbability => &mut forecast.precipitation_probability,
+                HourlyVariable::WindSpeed10m => &mut forecast.wind_speed,
+                HourlyVariable::WindDirection10m => &mut forecast.wind_direction,
+                HourlyVariable::WindGusts10m => &mut forecast.wind_gusts,
+                HourlyVariable::CloudCover => &mut forecast.cloud_cover,
+                HourlyVariable::WeatherCode => &mut forecast.weather_code,
             };
-            (status, Json(json!({ "error": message }))).into_response()
+            *field = value;
         }
+
+        forecast
     }
 }
 
-async fn fetch_data(city: &str) -> Result<Response, ServiceError> {
-    let service = WeatherService::new();
-
-    let coords = service.fetch_coordinates(city).await?;
-    let weather = service.fetch_weather(&coords).await?;
-
-    let min_temp = weather
-        .hourly
-        .temperature_2m
-        .iter()
-        .fold(f64::INFINITY, |a, &b| a.min(b));
-    let max_temp = weather
-        .hourly
-        .temperature_2m
-        .iter()
-        .fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-
-    let hourly_forecast = weather
-        .hourly
-        .time
-        .iter()
-        .zip(weather.hourly.temperature_2m.iter())
-        .map(|(time, temp)| HourlyForecast {
-            time: time.clone(),
-            temperature: *temp,
-        })
-        .collect();
+/// Body of a 300 Multiple Choices reply: the query matched several comparable places.
+///
+/// Repeat the request with `country` and/or `admin1` taken from a candidate to pin it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Candidates {
+    city: String,
+    candidates: Vec<Place>,
+}
+
+/// Per-day summary of the forecast, served by `/api/weather/daily`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DailyResponse {
+    city: String,
+    place: Place,
+    /// IANA zone whose calendar days are summarized.
+    timezone: String,
+    units: Units,
+    window: ForecastWindow,
+    provider: ProviderKind,
+    /// As in [`Response`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stale_as_of: Option<DateTime<FixedOffset>>,
+    days: Vec<DaySummary>,
+}
 
-    Ok(Response {
-        city: city.to_string(),
-        temperature: Temperature {
-            min: min_temp,
-            max: max_temp,
-        },
-        hourly_forecast,
-    })
+/// Recorded conditions over a past date range, served by `/api/weather/history`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HistoryResponse {
+    city: String,
+    place: Place,
+    /// IANA zone of every time in the response.
+    timezone: String,
+    units: Units,
+    variables: Vec<HourlyVariable>,
+    start: NaiveDate,
+    end: NaiveDate,
+    /// Omitted when `temperature_2m` was not requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temperature: Option<Temperature>,
+    /// Per-day summaries computed from the requested hourly series.
+    days: Vec<DaySummary>,
+    hourly: Vec<HourlyForecast>,
+}
+
+impl Response {
+    fn new(forecast: Forecast) -> Self {
+        let city = forecast.name();
+        let Forecast {
+            place,
+            zone,
+            units,
+            window,
+            variables,
+            weather,
+            air_quality,
+            air_quality_error,
+            marine,
+            marine_error,
+            ..
+        } = forecast;
+
+        Self {
+            city,
+            timezone: zone.name().to_string(),
+            units: units.into(),
+            window,
+            provider: weather.provider,
+            stale_as_of: weather
+                .stale_as_of
+                .map(|time| time.with_timezone(&zone).fixed_offset()),
+            temperature: Temperature::of(&weather.hourly, units),
+            hourly_forecast: (0..weather.hourly.time.len())
+                .map(|hour| HourlyForecast::at(&weather.hourly, hour, &variables, zone))
+                .collect(),
+            air_quality: air_quality.map(|air_quality| AirQualityResponse::new(&air_quality, zone)),
+            marine: marine.map(|marine| MarineResponse::new(&marine, &place.coords(), units, zone)),
+            place,
+            variables,
+            air_quality_error,
+            marine_error,
+        }
+    }
+}
+
+impl DailyResponse {
+    fn new(forecast: Forecast) -> Self {
+        let weather = &forecast.weather;
+
+        Self {
+            city: forecast.name(),
+            timezone: forecast.zone.name().to_string(),
+            units: forecast.units.into(),
+            window: forecast.window,
+            provider: weather.provider,
+            stale_as_of: weather
+                .stale_as_of
+                .map(|time| time.with_timezone(&forecast.zone).fixed_offset()),
+            days: daily::summarize(weather),
+            place: forecast.place,
+        }
+    }
+}
+
+impl HistoryResponse {
+    fn new(history: History) -> Self {
+        let weather = &history.weather;
+
+        Self {
+            city: history.name(),
+            timezone: history.zone.name().to_string(),
+            units: history.units.into(),
+            start: history.start,
+            end: history.end,
+            temperature: Temperature::of(&weather.hourly, history.units),
+            days: daily::summarize(weather),
+            hourly: (0..weather.hourly.time.len())
+                .map(|hour| {
+                    HourlyForecast::at(&weather.hourly, hour, &history.variables, history.zone)
+                })
+                .collect(),
+            place: history.place,
+            variables: history.variables,
+        }
+    }
+}
+
+impl Candidates {
+    fn new(city: String, candidates: Vec<Place>) -> Self {
+        Self { city, candidates }
+    }
+}
+
+/// Hourly forecast for a place.
+#[utoipa::path(
+    get,
+    path = "/weather",
+    tag = "weather",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The forecast", body = Response),
+        (status = 300, description = "Several places match", body = Candidates),
+        (status = 304, description = "Not modified since the request's validators"),
+        AppError,
+    )
+)]
+#[deprecated = "superseded by /api/v1/weather"]
+pub async fn get(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<QueryParams>,
+) -> impl IntoResponse {
+    let result = lookup::fetch_forecast(&state, &query, &Options::default()).await;
+    lookup::respond(&headers, result, Response::new, Candidates::new)
+}
+
+/// Per-day summary of the forecast for a place.
+#[utoipa::path(
+    get,
+    path = "/weather/daily",
+    tag = "weather",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The daily summary", body = DailyResponse),
+        (status = 300, description = "Several places match", body = Candidates),
+        (status = 304, description = "Not modified since the request's validators"),
+        AppError,
+    )
+)]
+#[deprecated = "superseded by /api/v1/weather/daily"]
+pub async fn daily(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<QueryParams>,
+) -> impl IntoResponse {
+    let result = lookup::fetch_daily(&state, &query).await;
+    lookup::respond(&headers, result, DailyResponse::new, Candidates::new)
+}
+
+/// Recorded conditions for a place over past days.
+#[utoipa::path(
+    get,
+    path = "/weather/history",
+    tag = "weather",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The recorded conditions", body = HistoryResponse),
+        (status = 300, description = "Several places match", body = Candidates),
+        (status = 304, description = "Not modified since the request's validators"),
+        AppError,
+    )
+)]
+#[deprecated = "superseded by /api/v1/weather/history"]
+pub async fn history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<QueryParams>,
+) -> impl IntoResponse {
+    let result = lookup::fetch_history(&state, &query, &Options::default()).await;
+    lookup::respond(&headers, result, HistoryResponse::new, Candidates::new)
+}
+
+/// When these endpoints were deprecated, for the `Deprecation` header (RFC 9745).
+const DEPRECATED_AT: &str = "@1792195200";
+
+/// Marks a response from these endpoints as deprecated and links the `/api/v1` one that
+/// replaces it.
+pub async fn deprecated(
+    OriginalUri(uri): OriginalUri,
+    mut response: axum::response::Response,
+) -> axum::response::Response {
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static(DEPRECATED_AT));
+    if let Some(path) = uri.path().strip_prefix("/api/") {
+        if let Ok(link) =
+            HeaderValue::from_str(&format!("</api/v1/{path}>; rel=\"successor-version\""))
+        {
+            headers.insert(axum::http::header::LINK, link);
+        }
+    }
+    response
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use super::*;
     use crate::api::weather;
-    use axum::{routing::get, Router};
+    use crate::config::{AppConfig, CacheConfig, WeatherConfig};
+    use crate::test_support::{
+        setup_test_db, test_state, MockMetNorway, MockOpenMeteo, StaticProvider, AIR_QUALITY_PATH,
+        FORECAST_PATH, MARINE_PATH, MET_NORWAY_PATH,
+    };
+    use axum::http::header::{
+        CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    };
+    use axum::{http::StatusCode, routing::get, Router};
     use axum_test::TestServer;
+    use chrono::NaiveDate;
+    use serde_json::json;
 
     #[tokio::test]
     async fn test_get_weather_api() {
-        let app = Router::new().route("/api/weather", get(weather::get));
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
         let server = TestServer::new(app.into_make_service()).unwrap();
 
         // Test a successful case
@@ -99,6 +498,16 @@ mod tests {
         assert_eq!(response.status_code(), StatusCode::OK);
         let body: Response = response.json();
         assert_eq!(body.city, "London");
+        assert_eq!(body.window, ForecastWindow::default());
+        assert_eq!(
+            body.hourly_forecast.len(),
+            StaticProvider::HOURS as usize * ForecastWindow::default().total_days()
+        );
+        let temperature = body.temperature.unwrap();
+        assert!((temperature.min - 5.0).abs() < f64::EPSILON);
+        assert!((temperature.max - 16.5).abs() < f64::EPSILON);
+        assert_eq!(body.variables, [HourlyVariable::Temperature2m]);
+        assert_eq!(body.hourly_forecast[0].precipitation, None);
 
         // Test invalid city
         let response = server
@@ -108,4 +517,740 @@ mod tests {
 
         assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn test_get_weather_api_variables() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("variables", "precipitation,wind_speed_10m,weather_code")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["variables"],
+            json!(["precipitation", "wind_speed_10m", "weather_code"])
+        );
+        assert!(body.get("temperature").is_none());
+        assert_eq!(
+            body["hourly_forecast"][14],
+            json!({
+                "time": "2024-10-26T14:00:00+01:00",
+                "precipitation": 1.2,
+                "wind_speed": 24.0,
+                "weather_code": 61.0,
+            })
+        );
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("variables", "temperature_2m,snowfall")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "invalid_request");
+        assert!(body["detail"].as_str().unwrap().contains("snowfall"));
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_units() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["units"],
+            json!({"system": "metric", "temperature": "°C", "wind_speed": "km/h", "precipitation": "mm"})
+        );
+        assert_eq!(body["temperature"]["unit"], "°C");
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("units", "imperial")
+            .add_query_param("variables", "temperature_2m,wind_speed_10m,precipitation")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["units"]["system"], "imperial");
+        assert_eq!(
+            body["temperature"],
+            json!({"min": 41.0, "max": 61.7, "unit": "°F"})
+        );
+        assert_eq!(body["hourly_forecast"][14]["wind_speed"], 14.91);
+        assert_eq!(body["hourly_forecast"][14]["precipitation"], 0.05);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("units", "si")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["temperature"]["min"], 278.15);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("units", "nautical")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_timezone() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Berlin")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["timezone"], "Europe/Berlin");
+        assert_eq!(
+            body["hourly_forecast"][0]["time"],
+            "2024-10-26T00:00:00+02:00"
+        );
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Berlin")
+            .add_query_param("tz", "Asia/Tokyo")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["timezone"], "Asia/Tokyo");
+        assert_eq!(
+            body["hourly_forecast"][0]["time"],
+            "2024-10-26T00:00:00+09:00"
+        );
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Berlin")
+            .add_query_param("tz", "CEST")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_daily_weather_api() {
+        let app = Router::new()
+            .route("/api/weather/daily", get(weather::daily))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather/daily")
+            .add_query_param("city", "Berlin")
+            .add_query_param("days", "1")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: DailyResponse = response.json();
+        assert_eq!(body.city, "Berlin");
+        assert_eq!(
+            body.days,
+            [DaySummary {
+                date: NaiveDate::from_ymd_opt(2024, 10, 26).unwrap(),
+                temperature_min: Some(5.0),
+                temperature_max: Some(16.5),
+                temperature_mean: Some(10.8),
+                precipitation_sum: Some(7.2),
+                sunrise: None,
+                sunset: None,
+            }]
+        );
+
+        let response = server
+            .get("/api/weather/daily")
+            .add_query_param("city", "Springfield")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::MULTIPLE_CHOICES);
+
+        let response = server
+            .get("/api/weather/daily")
+            .add_query_param("city", "Atlantis")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_window() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .route("/api/weather/daily", get(weather::daily))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("days", "2")
+            .add_query_param("past_days", "1")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Response = response.json();
+        assert_eq!(
+            body.window,
+            ForecastWindow {
+                days: 2,
+                past_days: 1
+            }
+        );
+        assert_eq!(
+            body.hourly_forecast.len(),
+            3 * StaticProvider::HOURS as usize
+        );
+        assert_eq!(
+            body.hourly_forecast[0].time.to_rfc3339(),
+            "2024-10-25T00:00:00+01:00"
+        );
+
+        let response = server
+            .get("/api/weather/daily")
+            .add_query_param("city", "London")
+            .add_query_param("days", "16")
+            .add_query_param("past_days", "92")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: DailyResponse = response.json();
+        assert_eq!(body.days.len(), 108);
+        assert_eq!(
+            body.days[92].date,
+            NaiveDate::from_ymd_opt(2024, 10, 26).unwrap()
+        );
+
+        for (name, value) in [("days", "0"), ("days", "17"), ("past_days", "93")] {
+            let response = server
+                .get("/api/weather")
+                .add_query_param("city", "London")
+                .add_query_param(name, value)
+                .await;
+            assert_eq!(
+                response.status_code(),
+                StatusCode::BAD_REQUEST,
+                "{name}={value}"
+            );
+        }
+    }
+
+    /// Config failing over from the Open-Meteo stand-in to the MET Norway one.
+    fn failover_config(open_meteo: &MockOpenMeteo, met_norway: &MockMetNorway) -> AppConfig {
+        AppConfig {
+            met_norway: met_norway.config(),
+            weather: WeatherConfig::default(),
+            ..open_meteo.app_config()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_fails_over_to_met_norway() {
+        let open_meteo = MockOpenMeteo::start().await;
+        let met_norway = MockMetNorway::start().await;
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .route("/api/weather/daily", get(weather::daily))
+            .with_state(AppState::new(
+                setup_test_db().await,
+                &failover_config(&open_meteo, &met_norway),
+            ));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["provider"], "open_meteo");
+        assert_eq!(met_norway.request_count().await, 0);
+
+        open_meteo.fail_with(FORECAST_PATH, 503).await;
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("variables", "temperature_2m,wind_speed_10m")
+            .add_query_param("days", "1")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["provider"], "met_norway");
+        // The first local day of the fixture runs from 01:00 BST to midnight.
+        let hours = body["hourly_forecast"].as_array().unwrap();
+        assert_eq!(hours.len(), 23);
+        assert_eq!(hours[0]["time"], "2024-10-26T01:00:00+01:00");
+        assert_eq!(hours[0]["temperature"], 9.8);
+        assert_eq!(hours[0]["wind_speed"], 10.8);
+
+        let response = server
+            .get("/api/weather/daily")
+            .add_query_param("city", "London")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["provider"], "met_norway");
+        assert_eq!(body["days"][0]["date"], "2024-10-26");
+
+        met_norway.fail_with(MET_NORWAY_PATH, 503).await;
+        // A fresh state, so London's forecast is not served from the cache.
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(AppState::new(
+                setup_test_db().await,
+                &failover_config(&open_meteo, &met_norway),
+            ));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_GATEWAY);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["code"], "forecast_failed");
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_answers_conditional_requests() {
+        let open_meteo = MockOpenMeteo::start().await;
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(AppState::new(
+                setup_test_db().await,
+                &open_meteo.app_config(),
+            ));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let etag = response.header(ETAG);
+        // Open-Meteo does not say when it last updated the forecast.
+        assert!(response.maybe_header(LAST_MODIFIED).is_none());
+        // Cached responses live as long as the forecast stays in the cache.
+        let cache_control = response.header(CACHE_CONTROL);
+        let max_age: u64 = cache_control
+            .to_str()
+            .unwrap()
+            .strip_prefix("public, max-age=")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((590..=600).contains(&max_age), "{cache_control:?}");
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_MODIFIED);
+        assert!(response.as_bytes().is_empty());
+        assert_eq!(response.header(ETAG), etag);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_header(
+                IF_MODIFIED_SINCE,
+                HeaderValue::from_static("Sat, 26 Oct 2024 00:00:00 GMT"),
+            )
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+
+        // Another representation of the same forecast has its own ETag.
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("units", "imperial")
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_ne!(response.header(ETAG), etag);
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_uncached_responses_must_be_revalidated() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.header(CACHE_CONTROL), "no-cache");
+        assert_eq!(
+            response.header(LAST_MODIFIED),
+            "Fri, 25 Oct 2024 23:00:00 GMT"
+        );
+
+        // Lookups that did not find a single place carry no validators.
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "NonExistentCity123")
+            .await;
+        assert!(response.maybe_header(ETAG).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_serves_stale_forecast_when_upstream_fails() {
+        let open_meteo = MockOpenMeteo::start().await;
+        let config = AppConfig {
+            cache: CacheConfig {
+                forecast_ttl_secs: 1,
+                forecast_stale_while_revalidate_secs: 0,
+                ..CacheConfig::default()
+            },
+            ..open_meteo.app_config()
+        };
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .route("/api/weather/daily", get(weather::daily))
+            .with_state(AppState::new(setup_test_db().await, &config));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        for path in ["/api/weather", "/api/weather/daily"] {
+            let body: serde_json::Value = server
+                .get(path)
+                .add_query_param("city", "London")
+                .await
+                .json();
+            assert!(body.get("stale_as_of").is_none());
+        }
+
+        open_meteo.fail_with(FORECAST_PATH, 503).await;
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        for path in ["/api/weather", "/api/weather/daily"] {
+            let response = server.get(path).add_query_param("city", "London").await;
+            assert_eq!(response.status_code(), StatusCode::OK);
+            let body: serde_json::Value = response.json();
+            let stale_as_of = body["stale_as_of"].as_str().unwrap();
+            // In the place's zone, like every other time in the response.
+            assert!(
+                stale_as_of.ends_with("+01:00") || stale_as_of.ends_with("+00:00"),
+                "unexpected stale_as_of: {stale_as_of}"
+            );
+            assert!(!body["place"].is_null());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_marine() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        // Coastal places get marine data without asking.
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Brighton")
+            .add_query_param("units", "imperial")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let marine = &body["marine"];
+        assert_eq!(marine["coastal"], true);
+        assert_eq!(marine["wave_height_unit"], "ft");
+        assert_eq!(marine["sea_surface_temperature_unit"], "°F");
+        let hourly = marine["hourly"].as_array().unwrap();
+        assert_eq!(hourly.len(), 7 * StaticProvider::HOURS as usize);
+        assert_eq!(hourly[0]["time"], "2024-10-26T00:00:00+01:00");
+        assert_eq!(hourly[0]["wave_height"], 3.28);
+        assert_eq!(hourly[0]["swell_wave_direction"], 225.0);
+
+        // Inland places only with include=marine.
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert!(body.get("marine").is_none());
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("include", "marine")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["marine"]["coastal"], false);
+        assert!(body.get("marine_error").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_marine_failure() {
+        let mock = MockOpenMeteo::start().await;
+        mock.fail_with(MARINE_PATH, 503).await;
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(AppState::new(setup_test_db().await, &mock.app_config()));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("include", "marine")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert!(body.get("marine").is_none());
+        assert_eq!(body["marine_error"], "Marine data is unavailable right now");
+        assert!(body["hourly_forecast"]
+            .as_array()
+            .is_some_and(|hours| !hours.is_empty()));
+
+        // Without include=marine a failed probe is not worth mentioning.
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert!(body.get("marine_error").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_air_quality_failure() {
+        let mock = MockOpenMeteo::start().await;
+        mock.fail_with(AIR_QUALITY_PATH, 503).await;
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(AppState::new(setup_test_db().await, &mock.app_config()));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("include", "air_quality")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert!(body.get("air_quality").is_none());
+        assert_eq!(
+            body["air_quality_error"],
+            "Air quality is unavailable right now"
+        );
+        assert!(body["hourly_forecast"]
+            .as_array()
+            .is_some_and(|hours| !hours.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_air_quality() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .await;
+        let body: serde_json::Value = response.json();
+        assert!(body.get("air_quality").is_none());
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("include", "air_quality")
+            .add_query_param("days", "10")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let air_quality = &body["air_quality"];
+        assert_eq!(air_quality["current"]["time"], "2024-10-26T12:00:00+01:00");
+        assert_eq!(air_quality["current"]["pm2_5"], 12.5);
+        assert_eq!(air_quality["current"]["uv_index"], 3.0);
+        assert_eq!(air_quality["european_aqi_category"], "fair");
+        assert_eq!(air_quality["us_aqi_category"], "moderate");
+        assert_eq!(
+            air_quality["hourly"].as_array().unwrap().len(),
+            7 * StaticProvider::HOURS as usize
+        );
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("include", "pollen")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_history_api() {
+        let app = Router::new()
+            .route("/api/weather/history", get(weather::history))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather/history")
+            .add_query_param("city", "Berlin")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-02")
+            .add_query_param("variables", "temperature_2m,precipitation")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: HistoryResponse = response.json();
+        assert_eq!(body.city, "Berlin");
+        assert_eq!(body.timezone, "Europe/Berlin");
+        assert_eq!(body.start, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert_eq!(body.hourly.len(), 2 * StaticProvider::HOURS as usize);
+        assert_eq!(
+            body.hourly[0].time.to_rfc3339(),
+            "2024-03-01T00:00:00+01:00"
+        );
+        assert_eq!(body.days.len(), 2);
+        assert_eq!(
+            body.days[1].date,
+            NaiveDate::from_ymd_opt(2024, 3, 2).unwrap()
+        );
+        assert_eq!(body.days[1].temperature_mean, Some(10.8));
+        assert_eq!(body.days[1].precipitation_sum, Some(7.2));
+
+        let response = server
+            .get("/api/weather/history")
+            .add_query_param("city", "Berlin")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-01")
+            .add_query_param("units", "imperial")
+            .await;
+        let body: HistoryResponse = response.json();
+        assert_eq!(body.temperature.unwrap().unit, "°F");
+        assert_eq!(body.hourly[0].temperature, Some(41.0));
+
+        for (start, end) in [
+            (Some("2024-03-01"), None),
+            (Some("2024-03-02"), Some("2024-03-01")),
+            (Some("1 March"), Some("2024-03-01")),
+            (Some("2024-03-01"), Some("2999-01-01")),
+        ] {
+            let mut request = server
+                .get("/api/weather/history")
+                .add_query_param("city", "Berlin");
+            if let Some(start) = start {
+                request = request.add_query_param("start", start);
+            }
+            if let Some(end) = end {
+                request = request.add_query_param("end", end);
+            }
+            assert_eq!(
+                request.await.status_code(),
+                StatusCode::BAD_REQUEST,
+                "{start:?} to {end:?}"
+            );
+        }
+
+        let response = server
+            .get("/api/weather/history")
+            .add_query_param("city", "Atlantis")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-01")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_by_coordinates() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("lat", "48.85")
+            .add_query_param("lon", "2.35")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Response = response.json();
+        assert_eq!(body.city, "Paris");
+        assert_eq!(body.place.country_code.as_deref(), Some("FR"));
+        assert!((body.place.latitude - 48.85).abs() < f32::EPSILON);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("lat", "95")
+            .add_query_param("lon", "2.35")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("lat", "48.85")
+            .await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_ambiguous_city() {
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Springfield")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::MULTIPLE_CHOICES);
+        let body: Candidates = response.json();
+        assert_eq!(body.candidates.len(), 3);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Springfield")
+            .add_query_param("country", "US")
+            .add_query_param("admin1", "Illinois")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::OK);
+        let body: Response = response.json();
+        assert_eq!(body.place.admin1.as_deref(), Some("Illinois"));
+    }
+
+    #[tokio::test]
+    async fn test_get_weather_api_rejects_invalid_city() {
+        let mock = MockOpenMeteo::start().await;
+        let state = AppState::new(setup_test_db().await, &mock.app_config());
+        let app = Router::new()
+            .route("/api/weather", get(weather::get))
+            .with_state(state);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Paris&count=10")
+            .await;
+
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert!(body["detail"]
+            .as_str()
+            .unwrap()
+            .contains("invalid character"));
+    }
 }
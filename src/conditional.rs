@@ -0,0 +1,267 @@
+//! Conditional GET for forecast responses: validators, `Cache-Control` and
+//! `304 Not Modified`.
+
+use crate::services::weather_service::WeatherData;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use headers::{CacheControl, ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, LastModified};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, SystemTime};
+
+/// What the validators and lifetime of a forecast response derive from.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Freshness {
+    /// When the forecast was last updated, sent as `Last-Modified`.
+    pub updated_at: Option<DateTime<Utc>>,
+    /// When the cached forecast goes out of date. Responses built from uncached data
+    /// must be revalidated on every use.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Freshness {
+    pub fn of(weather: &WeatherData) -> Self {
+        Self {
+            updated_at: weather.updated_at,
+            expires_at: weather.expires_at,
+        }
+    }
+
+    /// A strong validator for `body`, built from this forecast.
+    ///
+    /// The standard library's hasher may change between Rust releases; after such an
+    /// upgrade clients just download each response in full once more.
+    fn etag(&self, body: &[u8]) -> ETag {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        self.updated_at.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+            .parse()
+            .expect("a quoted hex digest is a valid ETag")
+    }
+
+    /// Shared caches may keep the response for as long as the forecast stays fresh, so a
+    /// stale forecast has to be revalidated straight away.
+    fn cache_control(&self, now: DateTime<Utc>) -> CacheControl {
+        match self.expires_at {
+            Some(expires_at) => {
+                CacheControl::new()
+                    .with_public()
+                    .with_max_age(Duration::from_secs(
+                        u64::try_from((expires_at - now).num_seconds()).unwrap_or(0),
+                    ))
+            }
+            None => CacheControl::new().with_no_cache(),
+        }
+    }
+}
+
+/// Responds with `body`, or with `304 Not Modified` when the request's `If-None-Match`
+/// (or, lacking one, `If-Modified-Since`) shows the client has it already.
+///
+/// Both carry `ETag`, `Last-Modified` when known, and `Cache-Control`.
+pub fn respond(
+    request: &HeaderMap,
+    freshness: Freshness,
+    content_type: &'static str,
+    body: Vec<u8>,
+) -> Response {
+    let etag = freshness.etag(&body);
+    let last_modified = freshness.updated_at.map(SystemTime::from);
+    let not_modified = match request.typed_get::<IfNoneMatch>() {
+        Some(if_none_match) => !if_none_match.precondition_passes(&etag),
+        None => request
+            .typed_get::<IfModifiedSince>()
+            .zip(last_modified)
+            .is_some_and(|(since, modified)| !since.is_modified(modified)),
+    };
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        ([(CONTENT_TYPE, content_type)], body).into_response()
+    };
+    let headers = response.headers_mut();
+    headers.typed_insert(etag);
+    if let Some(modified) = last_modified {
+        headers.typed_insert(LastModified::from(modified));
+    }
+    headers.typed_insert(freshness.cache_control(Utc::now()));
+    response
+}
+
+/// Keeps `response` out of shared caches, for responses that set a cookie: its
+/// `Cache-Control` becomes `private`, with the lifetime it already had.
+pub fn make_private(response: &mut Response) {
+    let headers = response.headers_mut();
+    let cache_control = match headers
+        .typed_get::<CacheControl>()
+        .and_then(|cache_control| cache_control.max_age())
+    {
+        Some(max_age) => CacheControl::new().with_private().with_max_age(max_age),
+        None => CacheControl::new().with_private().with_no_cache(),
+    };
+    headers.typed_insert(cache_control);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::{
+        CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    };
+    use axum::http::HeaderValue;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn freshness() -> Freshness {
+        Freshness {
+            updated_at: Utc.with_ymd_and_hms(2024, 10, 25, 23, 12, 8).single(),
+            expires_at: Some(Utc::now() + chrono::Duration::minutes(10)),
+        }
+    }
+
+    fn request(name: axum::http::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_etag_follows_body_and_update_time() {
+        let etag = freshness().etag(b"{}");
+
+        assert_eq!(freshness().etag(b"{}"), etag);
+        assert_ne!(freshness().etag(b"[]"), etag);
+        let updated = Freshness {
+            updated_at: Some(Utc::now()),
+            ..freshness()
+        };
+        assert_ne!(updated.etag(b"{}"), etag);
+    }
+
+    #[test]
+    fn test_response_carries_validators_and_lifetime() {
+        let response = respond(
+            &HeaderMap::new(),
+            freshness(),
+            "application/json",
+            b"{}".to_vec(),
+        );
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[LAST_MODIFIED], "Fri, 25 Oct 2024 23:12:08 GMT");
+        let max_age = headers[CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .strip_prefix("public, max-age=")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        assert!((590..=600).contains(&max_age), "max-age={max_age}");
+    }
+
+    #[test]
+    fn test_stale_and_uncached_responses_must_be_revalidated() {
+        let stale = Freshness {
+            expires_at: Some(Utc::now() - chrono::Duration::minutes(1)),
+            ..freshness()
+        };
+        let response = respond(&HeaderMap::new(), stale, "text/html", Vec::new());
+        assert_eq!(response.headers()[CACHE_CONTROL], "public, max-age=0");
+
+        let response = respond(
+            &HeaderMap::new(),
+            Freshness::default(),
+            "text/html",
+            Vec::new(),
+        );
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+        assert!(response.headers().get(LAST_MODIFIED).is_none());
+    }
+
+    #[test]
+    fn test_private_responses_keep_their_lifetime() {
+        let mut response = respond(&HeaderMap::new(), freshness(), "text/html", Vec::new());
+        make_private(&mut response);
+        let cache_control = response.headers()[CACHE_CONTROL].to_str().unwrap();
+        assert!(
+            cache_control.starts_with("private, max-age="),
+            "{cache_control}"
+        );
+
+        let mut response = respond(
+            &HeaderMap::new(),
+            Freshness::default(),
+            "text/html",
+            Vec::new(),
+        );
+        make_private(&mut response);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache, private");
+    }
+
+    #[tokio::test]
+    async fn test_matching_etag_is_not_modified() {
+        let etag = respond(
+            &HeaderMap::new(),
+            freshness(),
+            "application/json",
+            b"{}".to_vec(),
+        )
+        .headers()[ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = respond(
+            &request(IF_NONE_MATCH, &etag),
+            freshness(),
+            "application/json",
+            b"{}".to_vec(),
+        );
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        assert!(response.headers().contains_key(CACHE_CONTROL));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let response = respond(
+            &request(IF_NONE_MATCH, &etag),
+            freshness(),
+            "application/json",
+            b"[]".to_vec(),
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_if_modified_since_applies_without_if_none_match() {
+        let since = "Fri, 25 Oct 2024 23:12:08 GMT";
+        let response = respond(
+            &request(IF_MODIFIED_SINCE, since),
+            freshness(),
+            "application/json",
+            b"{}".to_vec(),
+        );
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = respond(
+            &request(IF_MODIFIED_SINCE, "Fri, 25 Oct 2024 23:00:00 GMT"),
+            freshness(),
+            "application/json",
+            b"{}".to_vec(),
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A mismatched ETag wins over a matching date.
+        let mut headers = request(IF_MODIFIED_SINCE, since);
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        let response = respond(&headers, freshness(), "application/json", b"{}".to_vec());
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,780 @@
+use crate::services::ProviderKind;
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde::Deserialize;
+use std::{net::SocketAddr, num::NonZeroUsize, path::Path, time::Duration};
+use thiserror::Error;
+
+/// Environment variable pointing at an optional TOML configuration file.
+pub const CONFIG_PATH_ENV: &str = "FORECAST_CONFIG";
+
+/// File picked up from the working directory when `FORECAST_CONFIG` is not set.
+const DEFAULT_CONFIG_FILE: &str = "forecast.toml";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse config file {path}: {source}")]
+    Parse {
+        path: String,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid value {value:?} for environment variable {key}: {reason}")]
+    InvalidEnv {
+        key: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub open_meteo: OpenMeteoConfig,
+    pub met_norway: MetNorwayConfig,
+    pub weather: WeatherConfig,
+    pub upstream: UpstreamConfig,
+    pub cache: CacheConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub connect_timeout_secs: u64,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+}
+
+// Field names double as the TOML keys, where the `_url` suffix reads naturally.
+#[allow(clippy::struct_field_names)]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenMeteoConfig {
+    pub geocoding_url: String,
+    pub forecast_url: String,
+    pub archive_url: String,
+    pub air_quality_url: String,
+    pub marine_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetNorwayConfig {
+    pub forecast_url: String,
+    /// MET Norway's terms require an identifying `User-Agent`, ideally with contact details.
+    pub user_agent: String,
+}
+
+/// Which providers serve forecasts, in priority order, and how long each gets to answer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherConfig {
+    pub providers: Vec<ProviderKind>,
+    pub provider_timeout_secs: u64,
+}
+
+/// How every call to an upstream API is bounded and retried, and when its circuit
+/// breaker opens.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    pub request_timeout_ms: u64,
+    /// Retries after the first attempt; `0` disables retrying.
+    pub max_retries: u32,
+    pub retry_base_delay_ms: u64,
+    /// Also the longest `Retry-After` worth waiting for.
+    pub retry_max_delay_ms: u64,
+    pub breaker_failure_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+}
+
+/// How long geocoding results and forecasts are kept in memory, and how many of each.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub geocode_ttl_secs: u64,
+    pub geocode_max_entries: usize,
+    pub forecast_ttl_secs: u64,
+    pub forecast_max_entries: usize,
+    /// How long after expiry a forecast is still served while a fresh one is fetched
+    /// in the background; `0` disables.
+    pub forecast_stale_while_revalidate_secs: u64,
+    /// How long after expiry a forecast is served when fetching a fresh one fails;
+    /// `0` disables.
+    pub forecast_stale_if_error_secs: u64,
+    /// How often expired entries are deleted from the database cache tables.
+    pub sweep_interval_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 3000,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite://weather.db".to_string(),
+            max_connections: 100,
+            min_connections: 5,
+            connect_timeout_secs: 8,
+            acquire_timeout_secs: 8,
+            idle_timeout_secs: 8,
+        }
+    }
+}
+
+impl Default for OpenMeteoConfig {
+    fn default() -> Self {
+        Self {
+            geocoding_url: "https://geocoding-api.open-meteo.com/v1/search".to_string(),
+            forecast_url: "https://api.open-meteo.com/v1/forecast".to_string(),
+            archive_url: "https://archive-api.open-meteo.com/v1/archive".to_string(),
+            air_quality_url: "https://air-quality-api.open-meteo.com/v1/air-quality".to_string(),
+            marine_url: "https://marine-api.open-meteo.com/v1/marine".to_string(),
+        }
+    }
+}
+
+impl Default for MetNorwayConfig {
+    fn default() -> Self {
+        Self {
+            forecast_url: "https://api.met.no/weatherapi/locationforecast/2.0/compact".to_string(),
+            user_agent: concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string(),
+        }
+    }
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            providers: vec![ProviderKind::OpenMeteo, ProviderKind::MetNorway],
+            provider_timeout_secs: 15,
+        }
+    }
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout_ms: 3_000,
+            max_retries: 2,
+            retry_base_delay_ms: 250,
+            retry_max_delay_ms: 2_000,
+            breaker_failure_threshold: 5,
+            breaker_cooldown_secs: 30,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            geocode_ttl_secs: 7 * 24 * 60 * 60,
+            geocode_max_entries: 10_000,
+            forecast_ttl_secs: 600,
+            forecast_max_entries: 1_000,
+            forecast_stale_while_revalidate_secs: 300,
+            forecast_stale_if_error_secs: 24 * 60 * 60,
+            sweep_interval_secs: 300,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn socket_addr(&self) -> Result<SocketAddr, ConfigError> {
+        format!("{}:{}", self.host, self.port)
+            .parse()
+            .map_err(|e| ConfigError::Invalid(format!("server.host {:?}: {e}", self.host)))
+    }
+}
+
+impl DatabaseConfig {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+}
+
+impl WeatherConfig {
+    pub fn provider_timeout(&self) -> Duration {
+        Duration::from_secs(self.provider_timeout_secs)
+    }
+}
+
+impl UpstreamConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_millis(self.request_timeout_ms)
+    }
+
+    pub fn retry_base_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_base_delay_ms)
+    }
+
+    pub fn retry_max_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_max_delay_ms)
+    }
+
+    pub fn breaker_cooldown(&self) -> Duration {
+        Duration::from_secs(self.breaker_cooldown_secs)
+    }
+
+    /// The longest a call can take: every attempt timing out, with the longest wait
+    /// (a backoff or a `Retry-After`) before each retry.
+    pub fn longest_call(&self) -> Duration {
+        self.request_timeout()
+            .saturating_mul(self.max_retries.saturating_add(1))
+            .saturating_add(self.retry_max_delay().saturating_mul(self.max_retries))
+    }
+}
+
+impl CacheConfig {
+    pub fn geocode_ttl(&self) -> Duration {
+        Duration::from_secs(self.geocode_ttl_secs)
+    }
+
+    pub fn forecast_ttl(&self) -> Duration {
+        Duration::from_secs(self.forecast_ttl_secs)
+    }
+
+    pub fn forecast_stale_while_revalidate(&self) -> Duration {
+        Duration::from_secs(self.forecast_stale_while_revalidate_secs)
+    }
+
+    pub fn forecast_stale_if_error(&self) -> Duration {
+        Duration::from_secs(self.forecast_stale_if_error_secs)
+    }
+
+    /// How long past expiry a forecast may still be served.
+    pub fn forecast_retention(&self) -> Duration {
+        self.forecast_stale_while_revalidate()
+            .max(self.forecast_stale_if_error())
+    }
+
+    pub fn sweep_interval(&self) -> Duration {
+        Duration::from_secs(self.sweep_interval_secs)
+    }
+
+    fn apply_env(&mut self, env: &impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(value) = parse_env(env, "GEO_CACHE_TTL_SECS")? {
+            self.geocode_ttl_secs = value;
+        }
+        if let Some(value) = parse_env(env, "GEO_CACHE_MAX_ENTRIES")? {
+            self.geocode_max_entries = value;
+        }
+        if let Some(value) = parse_env(env, "CACHE_TTL_SECS")? {
+            self.forecast_ttl_secs = value;
+        }
+        if let Some(value) = parse_env(env, "CACHE_MAX_ENTRIES")? {
+            self.forecast_max_entries = value;
+        }
+        if let Some(value) = parse_env(env, "CACHE_STALE_WHILE_REVALIDATE_SECS")? {
+            self.forecast_stale_while_revalidate_secs = value;
+        }
+        if let Some(value) = parse_env(env, "CACHE_STALE_IF_ERROR_SECS")? {
+            self.forecast_stale_if_error_secs = value;
+        }
+        if let Some(value) = parse_env(env, "CACHE_SWEEP_INTERVAL_SECS")? {
+            self.sweep_interval_secs = value;
+        }
+
+        Ok(())
+    }
+
+    // Validated by `AppConfig::validate`.
+    pub fn geocode_capacity(&self) -> NonZeroUsize {
+        NonZeroUsize::new(self.geocode_max_entries).expect("geocode_max_entries is 0")
+    }
+
+    pub fn forecast_capacity(&self) -> NonZeroUsize {
+        NonZeroUsize::new(self.forecast_max_entries).expect("forecast_max_entries is 0")
+    }
+}
+
+impl AppConfig {
+    /// Loads the configuration from the optional TOML file and the process environment.
+    ///
+    /// Values are layered: built-in defaults, then the file, then environment variables.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = std::env::var(CONFIG_PATH_ENV).ok();
+        let contents = match &path {
+            Some(path) => Some(read_file(path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Some(read_file(DEFAULT_CONFIG_FILE)?)
+            }
+            None => None,
+        };
+        let path = path.unwrap_or_else(|| DEFAULT_CONFIG_FILE.to_string());
+
+        Self::from_sources(contents.as_deref().map(|c| (path.as_str(), c)), |key| {
+            std::env::var(key).ok()
+        })
+    }
+
+    /// Builds a configuration from a `(path, contents)` TOML source and an environment lookup.
+    pub fn from_sources(
+        file: Option<(&str, &str)>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = match file {
+            Some((path, contents)) => {
+                toml::from_str(contents).map_err(|source| ConfigError::Parse {
+                    path: path.to_string(),
+                    source,
+                })?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env(env)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(host) = env("HOST") {
+            self.server.host = host;
+        }
+        if let Some(port) = parse_env(&env, "PORT")? {
+            self.server.port = port;
+        }
+
+        if let Some(url) = env("DATABASE_URL") {
+            self.database.url = url;
+        }
+        if let Some(value) = parse_env(&env, "DB_MAX_CONNECTIONS")? {
+            self.database.max_connections = value;
+        }
+        if let Some(value) = parse_env(&env, "DB_MIN_CONNECTIONS")? {
+            self.database.min_connections = value;
+        }
+        if let Some(value) = parse_env(&env, "DB_CONNECT_TIMEOUT_SECS")? {
+            self.database.connect_timeout_secs = value;
+        }
+        if let Some(value) = parse_env(&env, "DB_ACQUIRE_TIMEOUT_SECS")? {
+            self.database.acquire_timeout_secs = value;
+        }
+        if let Some(value) = parse_env(&env, "DB_IDLE_TIMEOUT_SECS")? {
+            self.database.idle_timeout_secs = value;
+        }
+
+        if let Some(url) = env("GEOCODING_API_URL") {
+            self.open_meteo.geocoding_url = url;
+        }
+        if let Some(url) = env("WEATHER_API_URL") {
+            self.open_meteo.forecast_url = url;
+        }
+        if let Some(url) = env("ARCHIVE_API_URL") {
+            self.open_meteo.archive_url = url;
+        }
+        if let Some(url) = env("AIR_QUALITY_API_URL") {
+            self.open_meteo.air_quality_url = url;
+        }
+        if let Some(url) = env("MARINE_API_URL") {
+            self.open_meteo.marine_url = url;
+        }
+
+        if let Some(url) = env("MET_NORWAY_API_URL") {
+            self.met_norway.forecast_url = url;
+        }
+        if let Some(user_agent) = env("MET_NORWAY_USER_AGENT") {
+            self.met_norway.user_agent = user_agent;
+        }
+
+        if let Some(value) = env("WEATHER_PROVIDERS") {
+            self.weather.providers = value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|reason| ConfigError::InvalidEnv {
+                    key: "WEATHER_PROVIDERS",
+                    value: value.clone(),
+                    reason,
+                })?;
+        }
+        if let Some(value) = parse_env(&env, "PROVIDER_TIMEOUT_SECS")? {
+            self.weather.provider_timeout_secs = value;
+        }
+
+        if let Some(value) = parse_env(&env, "UPSTREAM_TIMEOUT_MS")? {
+            self.upstream.request_timeout_ms = value;
+        }
+        if let Some(value) = parse_env(&env, "UPSTREAM_MAX_RETRIES")? {
+            self.upstream.max_retries = value;
+        }
+        if let Some(value) = parse_env(&env, "UPSTREAM_RETRY_BASE_DELAY_MS")? {
+            self.upstream.retry_base_delay_ms = value;
+        }
+        if let Some(value) = parse_env(&env, "UPSTREAM_RETRY_MAX_DELAY_MS")? {
+            self.upstream.retry_max_delay_ms = value;
+        }
+        if let Some(value) = parse_env(&env, "BREAKER_FAILURE_THRESHOLD")? {
+            self.upstream.breaker_failure_threshold = value;
+        }
+        if let Some(value) = parse_env(&env, "BREAKER_COOLDOWN_SECS")? {
+            self.upstream.breaker_cooldown_secs = value;
+        }
+
+        self.cache.apply_env(&env)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.server.socket_addr()?;
+
+        if self.database.url.trim().is_empty() {
+            return Err(ConfigError::Invalid(
+                "database.url must not be empty".to_string(),
+            ));
+        }
+        if self.database.max_connections == 0 {
+            return Err(ConfigError::Invalid(
+                "database.max_connections must be greater than 0".to_string(),
+            ));
+        }
+        if self.database.min_connections > self.database.max_connections {
+            return Err(ConfigError::Invalid(format!(
+                "database.min_connections ({}) must not exceed database.max_connections ({})",
+                self.database.min_connections, self.database.max_connections
+            )));
+        }
+
+        validate_url("open_meteo.geocoding_url", &self.open_meteo.geocoding_url)?;
+        validate_url("open_meteo.forecast_url", &self.open_meteo.forecast_url)?;
+        validate_url("open_meteo.archive_url", &self.open_meteo.archive_url)?;
+        validate_url(
+            "open_meteo.air_quality_url",
+            &self.open_meteo.air_quality_url,
+        )?;
+        validate_url("open_meteo.marine_url", &self.open_meteo.marine_url)?;
+        validate_url("met_norway.forecast_url", &self.met_norway.forecast_url)?;
+        let user_agent = &self.met_norway.user_agent;
+        if user_agent.trim().is_empty() || HeaderValue::from_str(user_agent).is_err() {
+            return Err(ConfigError::Invalid(format!(
+                "met_norway.user_agent {user_agent:?} must be a non-empty header value"
+            )));
+        }
+
+        let providers = &self.weather.providers;
+        if providers.is_empty() {
+            return Err(ConfigError::Invalid(
+                "weather.providers must name at least one provider".to_string(),
+            ));
+        }
+        if let Some((index, provider)) = providers
+            .iter()
+            .enumerate()
+            .find(|(index, provider)| providers[..*index].contains(provider))
+        {
+            return Err(ConfigError::Invalid(format!(
+                "weather.providers lists {} twice (entry {})",
+                provider.as_str(),
+                index + 1
+            )));
+        }
+        if self.weather.provider_timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "weather.provider_timeout_secs must be greater than 0".to_string(),
+            ));
+        }
+
+        let upstream = &self.upstream;
+        if upstream.request_timeout_ms == 0 {
+            return Err(ConfigError::Invalid(
+                "upstream.request_timeout_ms must be greater than 0".to_string(),
+            ));
+        }
+        if upstream.retry_base_delay_ms > upstream.retry_max_delay_ms {
+            return Err(ConfigError::Invalid(format!(
+                "upstream.retry_base_delay_ms ({}) must not exceed upstream.retry_max_delay_ms ({})",
+                upstream.retry_base_delay_ms, upstream.retry_max_delay_ms
+            )));
+        }
+        if upstream.breaker_failure_threshold == 0 {
+            return Err(ConfigError::Invalid(
+                "upstream.breaker_failure_threshold must be greater than 0".to_string(),
+            ));
+        }
+        // A provider cut off mid-call would skip its retries and never tell its breaker.
+        if self.weather.provider_timeout() < upstream.longest_call() {
+            return Err(ConfigError::Invalid(format!(
+                "weather.provider_timeout_secs ({}) must allow for a whole upstream call with \
+                 its retries: request_timeout_ms × (max_retries + 1) + max_retries × \
+                 retry_max_delay_ms = {}ms",
+                self.weather.provider_timeout_secs,
+                upstream.longest_call().as_millis()
+            )));
+        }
+
+        let cache = &self.cache;
+        for (name, is_zero) in [
+            ("cache.geocode_ttl_secs", cache.geocode_ttl_secs == 0),
+            ("cache.geocode_max_entries", cache.geocode_max_entries == 0),
+            ("cache.forecast_ttl_secs", cache.forecast_ttl_secs == 0),
+            (
+                "cache.forecast_max_entries",
+                cache.forecast_max_entries == 0,
+            ),
+            ("cache.sweep_interval_secs", cache.sweep_interval_secs == 0),
+        ] {
+            if is_zero {
+                return Err(ConfigError::Invalid(format!(
+                    "{name} must be greater than 0"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_file(path: &str) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_string(),
+        source,
+    })
+}
+
+fn parse_env<T>(
+    env: &impl Fn(&str) -> Option<String>,
+    key: &'static str,
+) -> Result<Option<T>, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    env(key)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|e: T::Err| ConfigError::InvalidEnv {
+                    key,
+                    reason: e.to_string(),
+                    value,
+                })
+        })
+        .transpose()
+}
+
+fn validate_url(name: &str, value: &str) -> Result<(), ConfigError> {
+    let url =
+        Url::parse(value).map_err(|e| ConfigError::Invalid(format!("{name} {value:?}: {e}")))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ConfigError::Invalid(format!(
+            "{name} {value:?}: scheme must be http or https"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn env_from(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn test_defaults_match_previous_hardcoded_values() {
+        let config = AppConfig::from_sources(None, env_from(&[])).unwrap();
+
+        assert_eq!(
+            config.server.socket_addr().unwrap().to_string(),
+            "0.0.0.0:3000"
+        );
+        assert_eq!(config.database.url, "sqlite://weather.db");
+        assert_eq!(config.database.max_connections, 100);
+        assert_eq!(config.database.connect_timeout(), Duration::from_secs(8));
+        assert_eq!(
+            config.open_meteo.forecast_url,
+            "https://api.open-meteo.com/v1/forecast"
+        );
+        assert_eq!(
+            config.open_meteo.archive_url,
+            "https://archive-api.open-meteo.com/v1/archive"
+        );
+        assert_eq!(config.upstream.request_timeout(), Duration::from_secs(3));
+        assert_eq!(config.upstream.max_retries, 2);
+        assert_eq!(config.cache.forecast_ttl(), Duration::from_secs(600));
+        assert_eq!(config.cache.geocode_ttl(), Duration::from_secs(604_800));
+    }
+
+    #[test]
+    fn test_file_values_are_overridden_by_env() {
+        let file = r#"
+            [server]
+            port = 4000
+
+            [open_meteo]
+            geocoding_url = "http://127.0.0.1:9000/v1/search"
+        "#;
+        let config = AppConfig::from_sources(
+            Some(("forecast.toml", file)),
+            env_from(&[
+                ("PORT", "5000"),
+                ("DB_MAX_CONNECTIONS", "10"),
+                ("ARCHIVE_API_URL", "http://127.0.0.1:9000/v1/archive"),
+                ("CACHE_STALE_IF_ERROR_SECS", "0"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, 5000);
+        assert_eq!(config.database.max_connections, 10);
+        assert_eq!(
+            config.open_meteo.geocoding_url,
+            "http://127.0.0.1:9000/v1/search"
+        );
+        assert_eq!(
+            config.open_meteo.forecast_url,
+            OpenMeteoConfig::default().forecast_url
+        );
+        assert_eq!(
+            config.open_meteo.archive_url,
+            "http://127.0.0.1:9000/v1/archive"
+        );
+        assert_eq!(config.cache.forecast_stale_if_error(), Duration::ZERO);
+        assert_eq!(config.cache.forecast_retention(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_provider_priority() {
+        let config = AppConfig::from_sources(None, env_from(&[])).unwrap();
+        assert_eq!(
+            config.weather.providers,
+            [ProviderKind::OpenMeteo, ProviderKind::MetNorway]
+        );
+
+        let file = r#"
+            [weather]
+            providers = ["met_norway"]
+            provider_timeout_secs = 30
+        "#;
+        let config = AppConfig::from_sources(Some(("forecast.toml", file)), env_from(&[])).unwrap();
+        assert_eq!(config.weather.providers, [ProviderKind::MetNorway]);
+        assert_eq!(config.weather.provider_timeout(), Duration::from_secs(30));
+
+        let config = AppConfig::from_sources(
+            Some(("forecast.toml", file)),
+            env_from(&[("WEATHER_PROVIDERS", "met_norway, open_meteo")]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.weather.providers,
+            [ProviderKind::MetNorway, ProviderKind::OpenMeteo]
+        );
+
+        let err =
+            AppConfig::from_sources(None, env_from(&[("WEATHER_PROVIDERS", "yr")])).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ConfigError::InvalidEnv {
+                    key: "WEATHER_PROVIDERS",
+                    ..
+                }
+            ),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_env_value_names_the_variable() {
+        let err = AppConfig::from_sources(None, env_from(&[("PORT", "eighty")])).unwrap_err();
+
+        assert!(
+            matches!(err, ConfigError::InvalidEnv { key: "PORT", .. }),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_unknown_file_key_is_rejected() {
+        let err = AppConfig::from_sources(
+            Some(("forecast.toml", "[server]\nprot = 1\n")),
+            env_from(&[]),
+        )
+        .unwrap_err();
+
+        assert!(
+            matches!(err, ConfigError::Parse { .. }),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let cases = [
+            vec![("HOST", "not a host")],
+            vec![("DB_MIN_CONNECTIONS", "20"), ("DB_MAX_CONNECTIONS", "10")],
+            vec![("DB_MAX_CONNECTIONS", "0")],
+            vec![("WEATHER_API_URL", "ftp://example.com/forecast")],
+            vec![("GEOCODING_API_URL", "not a url")],
+            vec![("MET_NORWAY_USER_AGENT", " ")],
+            vec![("WEATHER_PROVIDERS", "met_norway,open_meteo,met_norway")],
+            vec![("PROVIDER_TIMEOUT_SECS", "0")],
+            vec![("PROVIDER_TIMEOUT_SECS", "5")],
+            vec![("UPSTREAM_MAX_RETRIES", "5")],
+            vec![("UPSTREAM_TIMEOUT_MS", "0")],
+            vec![
+                ("UPSTREAM_RETRY_BASE_DELAY_MS", "2000"),
+                ("UPSTREAM_RETRY_MAX_DELAY_MS", "1000"),
+            ],
+            vec![("BREAKER_FAILURE_THRESHOLD", "0")],
+            vec![("CACHE_TTL_SECS", "0")],
+            vec![("GEO_CACHE_MAX_ENTRIES", "0")],
+            vec![("CACHE_SWEEP_INTERVAL_SECS", "0")],
+        ];
+
+        for pairs in cases {
+            let result = AppConfig::from_sources(None, env_from(&pairs));
+            assert!(
+                matches!(result, Err(ConfigError::Invalid(_))),
+                "expected validation error for {pairs:?}, got {result:?}"
+            );
+        }
+    }
+}
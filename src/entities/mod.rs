@@ -3,3 +3,6 @@
 pub mod prelude;
 
 pub mod cities;
+pub mod forecast_cache;
+pub mod gazetteer;
+pub mod geocode_cache;
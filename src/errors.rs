@@ -1,22 +1,292 @@
+use crate::repositories::city_repository::RepositoryError;
 use crate::services::weather_service::ServiceError;
+use askama_axum::Template;
 use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
 };
+use log::{error, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+use utoipa::openapi::{self, RefOr};
+use utoipa::{IntoResponses, ToSchema};
 
-impl IntoResponse for ServiceError {
-    fn into_response(self) -> Response {
-        let (status, message) = match self {
-            ServiceError::CityNotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            ServiceError::GeocodingError(msg) | ServiceError::WeatherError(msg) => {
-                (StatusCode::BAD_GATEWAY, msg)
+/// Everything a request can fail with. The JSON API answers with RFC 7807 problem
+/// details, the pages with `error.html`; both share the status and the code.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Service(#[from] ServiceError),
+
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+
+    #[error("Failed to render page: {0}")]
+    Render(#[from] askama::Error),
+}
+
+/// An `application/problem+json` body (RFC 7807).
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Problem {
+    /// Always `about:blank`; `code` tells problems apart.
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    kind: &'static str,
+    /// The status's reason phrase.
+    #[schema(example = "Not Found")]
+    title: &'static str,
+    #[schema(example = 404)]
+    status: u16,
+    #[schema(example = "No coordinates found for Atlantis")]
+    detail: String,
+    /// A stable, machine-readable name for the problem.
+    #[schema(example = "place_not_found")]
+    code: &'static str,
+}
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Service(err) => match err {
+                ServiceError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+                ServiceError::CityNotFound(_) => StatusCode::NOT_FOUND,
+                ServiceError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+                ServiceError::GeocodingError(_)
+                | ServiceError::WeatherError(_)
+                | ServiceError::AirQualityError(_)
+                | ServiceError::MarineError(_)
+                | ServiceError::InvalidResponse(_) => StatusCode::BAD_GATEWAY,
+                ServiceError::UpstreamUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+                ServiceError::UpstreamTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            },
+            Self::Repository(_) | Self::Render(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// A stable, machine-readable name for the problem.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Service(err) => match err {
+                ServiceError::InvalidInput(_) => "invalid_request",
+                ServiceError::CityNotFound(_) => "place_not_found",
+                ServiceError::RateLimited(_) => "upstream_rate_limited",
+                ServiceError::GeocodingError(_) => "geocoding_failed",
+                ServiceError::WeatherError(_) => "forecast_failed",
+                ServiceError::AirQualityError(_) => "air_quality_failed",
+                ServiceError::MarineError(_) => "marine_failed",
+                ServiceError::InvalidResponse(_) => "upstream_invalid_response",
+                ServiceError::UpstreamUnavailable(_) => "upstream_unavailable",
+                ServiceError::UpstreamTimeout(_) => "upstream_timeout",
+            },
+            Self::Repository(_) => "database_error",
+            Self::Render(_) => "render_failed",
+        }
+    }
+
+    /// What went wrong, for people. Upstream failures and our own are logged rather than
+    /// shown, as their messages carry upstream URLs and response bodies.
+    fn detail(&self) -> String {
+        let Self::Service(err) = self else {
+            error!("{self}");
+            return "Something went wrong on our side; please try again later.".to_string();
+        };
+        let detail = match err {
+            ServiceError::CityNotFound(msg) | ServiceError::InvalidInput(msg) => {
+                return msg.clone();
+            }
+            ServiceError::GeocodingError(_) => "Looking up the place failed",
+            ServiceError::WeatherError(_) => "Fetching the forecast failed",
+            ServiceError::AirQualityError(_) => "Fetching air quality failed",
+            ServiceError::MarineError(_) => "Fetching marine data failed",
+            ServiceError::InvalidResponse(_) => {
+                "A weather service sent a response we could not read"
+            }
+            ServiceError::UpstreamUnavailable(_) => {
+                "A weather service is failing, so we have paused calls to it"
+            }
+            ServiceError::UpstreamTimeout(_) => "A weather service took too long to answer",
+            ServiceError::RateLimited(_) => {
+                "A weather service is turning our requests down for now"
             }
-            ServiceError::InvalidResponse(e) => (
+        };
+        warn!("{err}");
+        format!("{detail}; please try again later.")
+    }
+
+    fn title(&self) -> &'static str {
+        self.status().canonical_reason().unwrap_or("Error")
+    }
+
+    /// Renders the error as an HTML page.
+    pub fn into_page(self) -> Response {
+        let status = self.status();
+        let page = ErrorTemplate {
+            title: self.title(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+        };
+        match page.render() {
+            Ok(html) => (status, Html(html)).into_response(),
+            Err(err) => {
+                error!("Failed to render error page: {err}");
+                (status, page.detail).into_response()
+            }
+        }
+    }
+}
+
+/// Problem details, as served under `/api`.
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let problem = Problem {
+            kind: "about:blank",
+            title: self.title(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+        };
+
+        (status, [(CONTENT_TYPE, PROBLEM_JSON)], Json(problem)).into_response()
+    }
+}
+
+/// The problems a lookup can answer with, for the `OpenAPI` document.
+impl IntoResponses for AppError {
+    fn responses() -> BTreeMap<String, RefOr<openapi::Response>> {
+        [
+            (
+                StatusCode::BAD_REQUEST,
+                "Invalid parameters: `invalid_request`",
+            ),
+            (StatusCode::NOT_FOUND, "No such place: `place_not_found`"),
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                "An upstream rate-limited us: `upstream_rate_limited`",
+            ),
+            (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to process response: {e}"),
+                "Our own failure: `database_error`, `render_failed`",
             ),
-        };
+            (
+                StatusCode::BAD_GATEWAY,
+                "An upstream failed: `geocoding_failed`, `forecast_failed`, \
+                 `air_quality_failed`, `marine_failed`, `upstream_invalid_response`",
+            ),
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "An upstream's circuit breaker is open: `upstream_unavailable`",
+            ),
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                "An upstream took too long: `upstream_timeout`",
+            ),
+        ]
+        .into_iter()
+        .map(|(status, description)| {
+            let response = openapi::ResponseBuilder::new()
+                .description(description)
+                .content(
+                    PROBLEM_JSON,
+                    openapi::Content::new(Some(openapi::Ref::from_schema_name(Problem::name()))),
+                )
+                .build();
+            (status.as_str().to_string(), response.into())
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test_case(ServiceError::InvalidInput("bad".into()), 400, "invalid_request")]
+    #[test_case(ServiceError::CityNotFound("gone".into()), 404, "place_not_found")]
+    #[test_case(ServiceError::RateLimited("slow down".into()), 429, "upstream_rate_limited")]
+    #[test_case(ServiceError::WeatherError("503".into()), 502, "forecast_failed")]
+    #[test_case(ServiceError::GeocodingError("503".into()), 502, "geocoding_failed")]
+    #[test_case(ServiceError::UpstreamUnavailable("open".into()), 503, "upstream_unavailable")]
+    #[test_case(ServiceError::UpstreamTimeout("slow".into()), 504, "upstream_timeout")]
+    fn test_service_errors_map_to_status_and_code(err: ServiceError, status: u16, code: &str) {
+        let err = AppError::from(err);
+
+        assert_eq!(err.status().as_u16(), status);
+        assert_eq!(err.code(), code);
+    }
+
+    #[tokio::test]
+    async fn test_problem_details() {
+        let response = AppError::from(ServiceError::CityNotFound(
+            "No coordinates found for Atlantis".into(),
+        ))
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/problem+json");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            problem,
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "No coordinates found for Atlantis",
+                "code": "place_not_found",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_internal_errors_are_not_disclosed() {
+        let err = RepositoryError::Database(sea_orm::DbErr::Custom("disk I/O error".into()));
+        let response = AppError::from(err).into_page();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        assert!(html.contains("Internal Server Error"));
+        assert!(html.contains("database_error"));
+        assert!(!html.contains("disk I/O"));
+    }
+
+    #[tokio::test]
+    async fn test_upstream_errors_are_not_disclosed() {
+        let err = ServiceError::UpstreamTimeout(
+            "error sending request for url (https://api.open-meteo.com/v1/forecast?latitude=51.5)"
+                .into(),
+        );
+        let response = AppError::from(err).into_response();
 
-        (status, message).into_response()
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            problem["detail"],
+            "A weather service took too long to answer; please try again later."
+        );
     }
 }
@@ -0,0 +1,56 @@
+//! Query string extractors that answer malformed queries like any other invalid
+//! request: `400` with code `invalid_request`, instead of axum's plain-text rejection.
+
+use crate::errors::AppError;
+use crate::services::weather_service::ServiceError;
+use async_trait::async_trait;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::response::Response;
+use serde::de::DeserializeOwned;
+
+/// Deserializes the query string into `T`; fails with problem details, for `/api`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Query<T>(pub T);
+
+/// Deserializes the query string into `T`; fails with the error page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PageQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) = axum::extract::Query::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| invalid(&rejection))?;
+        Ok(Self(value))
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for PageQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::from_request_parts(parts, state)
+            .await
+            .map_err(AppError::into_page)?;
+        Ok(Self(value))
+    }
+}
+
+/// Keeps serde's account of what is wrong, e.g. "missing field `lat`".
+fn invalid(rejection: &QueryRejection) -> AppError {
+    ServiceError::InvalidInput(rejection.body_text()).into()
+}
@@ -1,8 +1,8 @@
+use crate::errors::AppError;
 use crate::repositories::CityRepository;
 use askama_axum::Template;
 use axum::extract::State;
-use axum::response::{Html, IntoResponse};
-use hyper::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
 use sea_orm::DatabaseConnection;
 
 #[derive(Template)]
@@ -19,36 +19,28 @@ struct SearchRecord {
     long: f32,
 }
 
-pub async fn show(State(db): State<DatabaseConnection>) -> impl IntoResponse {
+pub async fn show(State(db): State<DatabaseConnection>) -> Response {
+    match render(db).await {
+        Ok(html) => html.into_response(),
+        Err(err) => err.into_page(),
+    }
+}
+
+async fn render(db: DatabaseConnection) -> Result<Html<String>, AppError> {
     let repository = CityRepository::new(db);
-    match repository.get_recent_searches(10).await {
-        Ok(models) => {
-            let searches = models
-                .into_iter()
-                .map(|model| SearchRecord {
-                    name: model.name,
-                    created_at: model.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
-                    lat: model.lat,
-                    long: model.long,
-                })
-                .collect();
+    let searches = repository
+        .get_recent_searches(10)
+        .await?
+        .into_iter()
+        .map(|model| SearchRecord {
+            name: model.name,
+            created_at: model.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            lat: model.lat,
+            long: model.long,
+        })
+        .collect();
 
-            let template = StatsTemplate { searches };
-            match template.render() {
-                Ok(html) => Html(html).into_response(),
-                Err(_) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to render template",
-                )
-                    .into_response(),
-            }
-        }
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to fetch searches",
-        )
-            .into_response(),
-    }
+    Ok(Html(StatsTemplate { searches }.render()?))
 }
 
 #[cfg(test)]
@@ -1,149 +1,1079 @@
+use crate::api::lookup::{self, Forecast, Fresh, History, Lookup, Options, QueryParams};
+use crate::conditional::{self, Freshness};
+use crate::errors::AppError;
+use crate::extract::PageQuery;
 use crate::repositories::CityRepository;
-use crate::services::weather_service::{ServiceError, WeatherService};
+use crate::services::air_quality::{AirQualityValues, AqiCategory};
+use crate::services::daily::{self, DaySummary};
+use crate::services::forecast::{ForecastWindow, HourlyVariable, UnitSystem};
+use crate::services::weather_service::{HourlyData, MarineData, MarineHourly, Place};
+use crate::state::AppState;
 use askama_axum::Template;
-use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use axum::extract::State;
+use axum::http::header::{COOKIE, SET_COOKIE, VARY};
+use axum::http::{HeaderMap, HeaderValue};
 use axum::response::{Html, IntoResponse};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
 use log::warn;
-use sea_orm::DatabaseConnection;
-use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
-pub struct QueryParams {
-    city: String,
+/// Cookie remembering the visitor's unit system between page views.
+const UNITS_COOKIE: &str = "units";
+
+/// How long the unit choice is remembered, in seconds (one year).
+const UNITS_COOKIE_MAX_AGE: u32 = 365 * 24 * 60 * 60;
+
+/// Columns shown when the page is requested without `variables=`.
+const PAGE_VARIABLES: [HourlyVariable; 5] = [
+    HourlyVariable::Temperature2m,
+    HourlyVariable::RelativeHumidity2m,
+    HourlyVariable::Precipitation,
+    HourlyVariable::WindSpeed10m,
+    HourlyVariable::WeatherCode,
+];
+
+/// Reads the unit system stored by an earlier visit, ignoring unparsable values.
+fn remembered_units(headers: &HeaderMap) -> Option<UnitSystem> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == UNITS_COOKIE).then(|| value.parse().ok()).flatten()
+        })
 }
 
 #[derive(Template)]
 #[template(path = "weather.html")]
 struct WeatherTemplate {
     city: String,
-    min_temp: f64,
-    max_temp: f64,
-    hourly_forecasts: Vec<HourlyForecast>,
+    region: String,
+    timezone: String,
+    /// Attribution for the service that supplied the forecast.
+    provider: &'static str,
+    /// When an out-of-date forecast was fetched, e.g. `Sat 26 Oct 12:00`.
+    stale_as_of: Option<String>,
+    unit_choices: Vec<UnitChoice>,
+    /// Hidden fields for the unit switcher form.
+    switch_params: Vec<(&'static str, String)>,
+    window: ForecastWindow,
+    /// Hidden fields for the forecast window form.
+    window_params: Vec<(&'static str, String)>,
+    /// Hidden fields for the history form.
+    history_params: Vec<(&'static str, String)>,
+    /// Latest day the history form accepts, `YYYY-MM-DD`.
+    yesterday: String,
+    /// Omitted when the air-quality lookup fails.
+    air_quality: Option<AirQualitySummary>,
+    /// Only for coastal places, and omitted when the marine lookup fails.
+    sea_days: Vec<SeaDay>,
+    temperature: Option<TemperatureRange>,
+    days: Vec<DayCard>,
+    columns: Vec<&'static str>,
+    hourly_days: Vec<HourlyDay>,
+}
+
+/// One button of the unit switcher.
+#[derive(Debug)]
+struct UnitChoice {
+    value: &'static str,
+    label: String,
+    active: bool,
+}
+
+impl UnitChoice {
+    fn all(current: UnitSystem) -> Vec<Self> {
+        [UnitSystem::Metric, UnitSystem::Imperial, UnitSystem::Si]
+            .into_iter()
+            .map(|system| Self {
+                value: system.as_str(),
+                label: format!(
+                    "{}, {}, {}",
+                    system.temperature(),
+                    system.wind_speed(),
+                    system.precipitation()
+                ),
+                active: system == current,
+            })
+            .collect()
+    }
+}
+
+/// A day summary with every value already formatted; `–` where it is unknown.
+#[derive(Debug)]
+struct DayCard {
+    date: String,
+    low: String,
+    high: String,
+    mean: String,
+    precipitation: String,
+    sunrise: String,
+    sunset: String,
+}
+
+impl DayCard {
+    fn new(day: &DaySummary, units: UnitSystem) -> Self {
+        let temperature = |value: Option<f64>| {
+            value.map_or_else(
+                || "–".to_string(),
+                |t| HourlyVariable::Temperature2m.format(t, units),
+            )
+        };
+        let clock = |time: Option<DateTime<FixedOffset>>| {
+            time.map_or_else(|| "–".to_string(), |t| t.format("%H:%M").to_string())
+        };
+
+        Self {
+            low: temperature(day.temperature_min),
+            high: temperature(day.temperature_max),
+            mean: temperature(day.temperature_mean),
+            precipitation: day.precipitation_sum.map_or_else(
+                || "–".to_string(),
+                |sum| HourlyVariable::Precipitation.format(sum, units),
+            ),
+            sunrise: clock(day.sunrise),
+            sunset: clock(day.sunset),
+            date: day.date.format("%a %-d %b").to_string(),
+        }
+    }
+}
+
+/// Formatted lowest and highest temperature of the whole forecast.
+#[derive(Debug)]
+struct TemperatureRange {
+    min: String,
+    max: String,
+}
+
+/// Current air quality as colour-coded badges.
+#[derive(Debug)]
+struct AirQualitySummary {
+    badges: Vec<AqiBadge>,
+    uv_index: Option<String>,
+    pm2_5: Option<String>,
+}
+
+#[derive(Debug)]
+struct AqiBadge {
+    /// e.g. `EU AQI 32 · Fair`.
+    text: String,
+    style: &'static str,
+}
+
+impl AirQualitySummary {
+    fn new(values: AirQualityValues) -> Self {
+        let badge = |scale: &str, aqi: f64, category: AqiCategory| AqiBadge {
+            text: format!("{scale} {aqi:.0} · {}", category.label()),
+            style: category.badge_style(),
+        };
+
+        Self {
+            badges: [
+                values
+                    .european_aqi
+                    .map(|aqi| badge("EU AQI", aqi, AqiCategory::european(aqi))),
+                values
+                    .us_aqi
+                    .map(|aqi| badge("US AQI", aqi, AqiCategory::us(aqi))),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            uv_index: values.uv_index.map(|uv| format!("{uv:.1}")),
+            pm2_5: values.pm2_5.map(|pm| format!("{pm:.1} µg/m³")),
+        }
+    }
+}
+
+/// Sea conditions of one local calendar day; `–` where the model has no value.
+#[derive(Debug)]
+struct SeaDay {
+    date: String,
+    max_wave_height: String,
+    max_wave_period: String,
+    sea_temperature: String,
+}
+
+/// Daily maxima of the marine forecast, grouped by the local date in `zone`.
+fn sea_days(marine: &MarineData, units: UnitSystem, zone: Tz) -> Vec<SeaDay> {
+    let hourly = &marine.hourly;
+    let mut days: Vec<(NaiveDate, [Option<f64>; 3])> = Vec::new();
+    for (hour, time) in hourly.time.iter().enumerate() {
+        let date = time.with_timezone(&zone).date_naive();
+        if days.last().is_none_or(|&(day, _)| day != date) {
+            days.push((date, [None; 3]));
+        }
+        if let Some((_, maxima)) = days.last_mut() {
+            let values = [
+                &hourly.wave_height,
+                &hourly.wave_period,
+                &hourly.sea_surface_temperature,
+            ]
+            .map(|series| MarineHourly::value(series.as_ref(), hour));
+            for (max, value) in maxima.iter_mut().zip(values) {
+                *max = match (*max, value) {
+                    (Some(max), Some(value)) => Some(max.max(value)),
+                    (max, value) => max.or(value),
+                };
+            }
+        }
+    }
+
+    let format = |value: Option<f64>, unit: &str| {
+        value.map_or_else(|| "–".to_string(), |value| format!("{value:.1} {unit}"))
+    };
+    days.into_iter()
+        .map(|(date, [height, period, temperature])| SeaDay {
+            date: date.format("%a %-d %b").to_string(),
+            max_wave_height: format(height, units.wave_height()),
+            max_wave_period: format(period, "s"),
+            sea_temperature: temperature.map_or_else(
+                || "–".to_string(),
+                |t| HourlyVariable::Temperature2m.format(t, units),
+            ),
+        })
+        .collect()
+}
+
+#[derive(Template)]
+#[template(path = "history.html")]
+struct HistoryTemplate {
+    city: String,
+    region: String,
+    /// The requested range in words.
+    period: String,
+    timezone: String,
+    /// Query for the link back to the forecast.
+    forecast_params: Vec<(&'static str, String)>,
+    temperature: Option<TemperatureRange>,
+    days: Vec<DayCard>,
+    columns: Vec<&'static str>,
+    hourly_days: Vec<HourlyDay>,
+}
+
+#[derive(Template)]
+#[template(path = "candidates.html")]
+struct CandidatesTemplate {
+    city: String,
+    /// The page each candidate links to, the one the search was made on.
+    path: &'static str,
+    candidates: Vec<Candidate>,
+}
+
+/// A place to pick, with the query showing it.
+#[derive(Debug)]
+struct Candidate {
+    place: Place,
+    params: Vec<(&'static str, String)>,
+}
+
+impl CandidatesTemplate {
+    fn new(city: String, path: &'static str, query: &QueryParams, places: Vec<Place>) -> Self {
+        Self {
+            city,
+            path,
+            candidates: places
+                .into_iter()
+                .map(|place| Candidate {
+                    params: query.pick(&place),
+                    place,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The hourly rows of one local calendar day.
+#[derive(Debug)]
+struct HourlyDay {
+    date: String,
+    forecasts: Vec<HourlyForecast>,
 }
 
 #[derive(Debug)]
 struct HourlyForecast {
+    /// Local wall-clock time, `HH:MM`.
     time: String,
-    temp: f64,
+    /// One formatted value per column; `–` where the hour has no data.
+    cells: Vec<String>,
+}
+
+/// How the pages look things up: more columns, daily aggregates, the remembered units
+/// and always air quality.
+fn options(headers: &HeaderMap) -> Options {
+    Options {
+        variables: &PAGE_VARIABLES,
+        with_daily: true,
+        remembered_units: remembered_units(headers),
+        air_quality: true,
+    }
 }
 
 pub async fn show(
-    State(db): State<DatabaseConnection>,
-    Query(query): Query<QueryParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    PageQuery(query): PageQuery<QueryParams>,
+) -> impl IntoResponse {
+    let forecast = match lookup::fetch_forecast(&state, &query, &options(&headers)).await {
+        Ok(Lookup::Found(forecast)) => forecast,
+        Ok(Lookup::Ambiguous { city, places }) => {
+            return candidates(&headers, city, "/weather", &query, places)
+        }
+        Err(err) => return AppError::from(err).into_page(),
+    };
+
+    save_search(&state, &forecast).await;
+    let mut response = match render(&weather_page(&query, &forecast)) {
+        Ok(html) => page(&headers, forecast.freshness(), html),
+        Err(err) => return err.into_page(),
+    };
+    if query.names_units() {
+        let cookie = format!(
+            "{UNITS_COOKIE}={}; Path=/; Max-Age={UNITS_COOKIE_MAX_AGE}; SameSite=Lax",
+            forecast.units.as_str()
+        );
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().insert(SET_COOKIE, value);
+            conditional::make_private(&mut response);
+        }
+    }
+    response
+}
+
+pub async fn history(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    PageQuery(query): PageQuery<QueryParams>,
 ) -> impl IntoResponse {
-    let repository = CityRepository::new(db);
-
-    match generate_weather_response(repository, &query.city).await {
-        Ok(html) => (StatusCode::OK, html).into_response(),
-        Err(err) => {
-            let (status, message) = match err {
-                ServiceError::CityNotFound(msg) => (StatusCode::NOT_FOUND, msg),
-                _ => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
-            };
-            (status, Html(message)).into_response()
+    let history = match lookup::fetch_history(&state, &query, &options(&headers)).await {
+        Ok(Lookup::Found(history)) => history,
+        Ok(Lookup::Ambiguous { city, places }) => {
+            return candidates(&headers, city, "/weather/history", &query, places)
         }
+        Err(err) => return AppError::from(err).into_page(),
+    };
+
+    match render(&history_page(&query, &history)) {
+        Ok(html) => page(&headers, history.freshness(), html),
+        Err(err) => err.into_page(),
     }
 }
 
-async fn generate_weather_response(
-    repository: CityRepository,
-    city: &str,
-) -> Result<Html<String>, ServiceError> {
-    let service = WeatherService::new();
+/// Serves a rendered page conditionally on `request`'s validators.
+fn page(request: &HeaderMap, freshness: Freshness, html: Html<String>) -> axum::response::Response {
+    let mut response = conditional::respond(
+        request,
+        freshness,
+        "text/html; charset=utf-8",
+        html.0.into_bytes(),
+    );
+    // The units may come from a cookie rather than the query.
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("cookie"));
+    response
+}
 
-    let coords = service.fetch_coordinates(city).await?;
+/// The page listing the places an ambiguous search matched, each linking to `path`.
+fn candidates(
+    request: &HeaderMap,
+    city: String,
+    path: &'static str,
+    query: &QueryParams,
+    places: Vec<Place>,
+) -> axum::response::Response {
+    match render(&CandidatesTemplate::new(city, path, query, places)) {
+        Ok(html) => page(request, Freshness::default(), html),
+        Err(err) => err.into_page(),
+    }
+}
 
+async fn save_search(state: &AppState, forecast: &Forecast) {
+    let repository = CityRepository::new(state.db.clone());
     if let Err(err) = repository
-        .save_search(city.to_string(), &coords, None)
+        .save_search(forecast.name(), &forecast.place.coords(), None)
         .await
     {
-        warn!("Failed to save search history: {}", err);
+        warn!("Failed to save search history: {err}");
     }
+}
 
-    let weather = service.fetch_weather(&coords).await?;
+fn weather_page(query: &QueryParams, forecast: &Forecast) -> WeatherTemplate {
+    let Forecast {
+        place,
+        zone,
+        units,
+        window,
+        variables,
+        weather,
+        air_quality,
+        marine,
+        ..
+    } = forecast;
+    let hourly = &weather.hourly;
 
-    let min_temp = weather
-        .hourly
-        .temperature_2m
-        .iter()
-        .fold(f64::INFINITY, |a, &b| a.min(b));
-    let max_temp = weather
-        .hourly
-        .temperature_2m
-        .iter()
-        .fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+    WeatherTemplate {
+        city: forecast.name(),
+        region: place.region(),
+        timezone: zone.name().to_string(),
+        provider: weather.provider.label(),
+        stale_as_of: weather.stale_as_of.map(|time| {
+            time.with_timezone(zone)
+                .format("%a %-d %b %H:%M")
+                .to_string()
+        }),
+        unit_choices: UnitChoice::all(*units),
+        switch_params: query.params_except(&["units"]),
+        window: *window,
+        window_params: query.params_except(&["days", "past_days", "start", "end"]),
+        history_params: query.params_except(&["days", "past_days", "start", "end"]),
+        yesterday: (Utc::now().date_naive() - chrono::Duration::days(1)).to_string(),
+        // Air quality is a nice-to-have on the page; the forecast still renders without it.
+        air_quality: air_quality
+            .as_ref()
+            .and_then(|air_quality| air_quality.current.as_ref())
+            .map(|current| AirQualitySummary::new(current.values)),
+        sea_days: marine
+            .as_ref()
+            .map_or_else(Vec::new, |marine| sea_days(marine, *units, *zone)),
+        temperature: temperature_range(hourly, *units),
+        days: daily::summarize(weather)
+            .iter()
+            .map(|day| DayCard::new(day, *units))
+            .collect(),
+        columns: variables.iter().map(|v| v.label()).collect(),
+        hourly_days: hourly_days(hourly, variables, *units, *zone),
+    }
+}
 
-    let hourly_forecasts: Vec<HourlyForecast> = weather
-        .hourly
-        .time
-        .iter()
-        .zip(weather.hourly.temperature_2m.iter())
-        .map(|(time, temp)| HourlyForecast {
-            time: format_time(time),
-            temp: *temp,
-        })
-        .collect();
+fn history_page(query: &QueryParams, history: &History) -> HistoryTemplate {
+    let History {
+        place,
+        zone,
+        units,
+        variables,
+        start,
+        end,
+        weather,
+        ..
+    } = history;
+    let date = |day: &NaiveDate| day.format("%-d %B %Y").to_string();
 
-    let template = WeatherTemplate {
-        city: city.to_string(),
-        min_temp,
-        max_temp,
-        hourly_forecasts,
-    };
+    HistoryTemplate {
+        city: history.name(),
+        region: place.region(),
+        period: if start == end {
+            date(start)
+        } else {
+            format!("{} to {}", date(start), date(end))
+        },
+        timezone: zone.name().to_string(),
+        forecast_params: query.params_except(&["start", "end"]),
+        temperature: temperature_range(&weather.hourly, *units),
+        days: daily::summarize(weather)
+            .iter()
+            .map(|day| DayCard::new(day, *units))
+            .collect(),
+        columns: variables.iter().map(|v| v.label()).collect(),
+        hourly_days: hourly_days(&weather.hourly, variables, *units, *zone),
+    }
+}
 
-    let html = template
-        .render()
-        .map_err(|e| ServiceError::WeatherError(format!("Failed to render template: {e}")))?;
+fn temperature_range(hourly: &HourlyData, units: UnitSystem) -> Option<TemperatureRange> {
+    hourly
+        .temperature_range()
+        .map(|(min, max)| TemperatureRange {
+            min: HourlyVariable::Temperature2m.format(min, units),
+            max: HourlyVariable::Temperature2m.format(max, units),
+        })
+}
 
-    Ok(Html(html))
+/// Table rows for every hour, grouped under the local date in `zone`.
+fn hourly_days(
+    hourly: &HourlyData,
+    variables: &[HourlyVariable],
+    units: UnitSystem,
+    zone: Tz,
+) -> Vec<HourlyDay> {
+    let mut days: Vec<HourlyDay> = Vec::new();
+    for (hour, &time) in hourly.time.iter().enumerate() {
+        let local = time.with_timezone(&zone);
+        let date = local.format("%A, %-d %B %Y").to_string();
+        if days.last().is_none_or(|day| day.date != date) {
+            days.push(HourlyDay {
+                date,
+                forecasts: Vec::new(),
+            });
+        }
+
+        let cells = variables
+            .iter()
+            .map(|&variable| {
+                hourly
+                    .value(variable, hour)
+                    .map_or_else(|| "–".to_string(), |value| variable.format(value, units))
+            })
+            .collect();
+        if let Some(day) = days.last_mut() {
+            day.forecasts.push(HourlyForecast {
+                time: local.format("%H:%M").to_string(),
+                cells,
+            });
+        }
+    }
+    days
 }
 
-fn format_time(time: &str) -> String {
-    time.split('T')
-        .nth(1)
-        .unwrap_or(time)
-        .trim_end_matches(":00")
-        .to_string()
+fn render(template: &impl Template) -> Result<Html<String>, AppError> {
+    Ok(Html(template.render()?))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{AppConfig, CacheConfig, WeatherConfig};
     use crate::handlers;
+    use crate::test_support::{
+        setup_test_db, test_state, MockMetNorway, MockOpenMeteo, AIR_QUALITY_PATH, FORECAST_PATH,
+    };
+    use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
     use axum::{routing::get, Router};
     use axum_test::TestServer;
-    use sea_orm::Database;
-    use sea_orm_migration::MigratorTrait;
 
-    async fn setup_test_db() -> DatabaseConnection {
-        let db = Database::connect("sqlite::memory:")
+    #[tokio::test]
+    async fn test_show_weather_page() {
+        let state = test_state().await;
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(state.clone());
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("Weather for London"));
+        assert!(html.contains("°C"));
+        assert!(html.contains("<th>Wind Speed</th>"));
+        assert!(html.contains("<td>1.2 mm</td>"));
+        assert!(html.contains("<td>Light rain</td>"));
+        assert!(html.contains("Daily Summary"));
+        assert!(html.contains("7.2 mm"));
+        assert!(html.contains("Times in Europe/London"));
+        assert!(html.contains("Saturday, 26 October 2024"));
+        assert!(html.contains("<td>14:00</td>"));
+
+        let history = CityRepository::new(state.db)
+            .get_recent_searches(10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].name, "London");
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_selected_variables() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("variables", "cloud_cover")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("<th>Cloud Cover</th>"));
+        assert!(html.contains("<td>100%</td>"));
+        assert!(!html.contains("Temperature Range"));
+        assert!(!html.contains("<th>Wind Speed</th>"));
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("variables", "visibility")
+            .await;
+
+        assert_eq!(response.status_code(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_groups_hours_by_viewer_date() {
+        // The stand-in always answers with London's 2024-10-26, whatever zone is asked for.
+        let mock = MockOpenMeteo::start().await;
+        let state = AppState::new(setup_test_db().await, &mock.app_config());
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(state);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("tz", "Asia/Tokyo")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(
+            mock.last_query_param("timezone").await.as_deref(),
+            Some("Asia/Tokyo")
+        );
+        let html = response.text();
+        assert!(html.contains("Times in Asia/Tokyo"));
+        let first_day = html.find("Saturday, 26 October 2024").unwrap();
+        let second_day = html.find("Sunday, 27 October 2024").unwrap();
+        let first_hour = html.find("<td>08:00</td>").unwrap();
+        assert!(first_day < first_hour && first_hour < second_day);
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("tz", "Nowhere/Special")
+            .await;
+        assert_eq!(response.status_code(), 400);
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("days", "abc")
+            .await;
+        assert_eq!(response.status_code(), 400);
+        let html = response.text();
+        assert!(html.contains("Bad Request"));
+        assert!(html.contains("invalid_request"));
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_remembers_units() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("units", "imperial")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let cookie = response.header(SET_COOKIE);
+        assert!(cookie.to_str().unwrap().starts_with("units=imperial;"));
+        let cache_control = response.header(CACHE_CONTROL);
+        assert!(cache_control.to_str().unwrap().contains("private"));
+        let html = response.text();
+        assert!(html.contains("41°F"));
+        assert!(html.contains("mph"));
+        assert!(!html.contains("°C</td>"));
+        assert!(html.contains(r#"<input type="hidden" name="city" value="London">"#));
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_header(COOKIE, HeaderValue::from_static("theme=dark; units=si"))
+            .await;
+        assert!(response.text().contains("278.15 K"));
+        assert!(response.maybe_header(SET_COOKIE).is_none());
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_header(COOKIE, HeaderValue::from_static("units=furlongs"))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("5°C"));
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("units", "furlongs")
+            .await;
+        assert_eq!(response.status_code(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_air_quality() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let html = server
+            .get("/weather")
+            .add_query_param("city", "London")
             .await
-            .expect("Failed to create test database");
+            .text();
+        assert!(html.contains(
+            r#"<span class="badge aqi-badge" style="background-color: #50ccaa; color: #000">EU AQI 32 · Fair</span>"#
+        ));
+        assert!(html.contains("US AQI 58 · Moderate"));
+        assert!(html.contains("UV 3.0"));
+        assert!(html.contains("PM2.5 12.5 µg/m³"));
+
+        // Without air quality the forecast still renders.
+        let mock = MockOpenMeteo::start().await;
+        mock.fail_with(AIR_QUALITY_PATH, 503).await;
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(AppState::new(setup_test_db().await, &mock.app_config()));
+        let server = TestServer::new(app.into_make_service()).unwrap();
 
-        migration::Migrator::up(&db, None)
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("14.6°C"));
+        assert!(!html.contains("aqi-badge"));
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_sea_conditions() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let html = server
+            .get("/weather")
+            .add_query_param("city", "Brighton")
             .await
-            .expect("Failed to run migrations");
+            .text();
+        assert!(html.contains("Sea Conditions"));
+        assert!(html.contains("<td>Sat 26 Oct</td>"));
+        assert!(html.contains("<td>2.2 m</td>"));
+        assert!(html.contains("<td>6.5 s</td>"));
+        assert!(html.contains("<td>14.5°C</td>"));
 
-        db
+        let html = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await
+            .text();
+        assert!(!html.contains("Sea Conditions"));
     }
 
     #[tokio::test]
-    async fn test_show_weather_page() {
-        let db = setup_test_db().await;
+    async fn test_show_weather_page_answers_conditional_requests() {
         let app = Router::new()
             .route("/weather", get(handlers::weather::show))
-            .with_state(db);
+            .with_state(test_state().await);
         let server = TestServer::new(app.into_make_service()).unwrap();
 
         let response = server
             .get("/weather")
             .add_query_param("city", "London")
             .await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header(VARY), "cookie");
+        let etag = response.header(ETAG);
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .await;
+        assert_eq!(response.status_code(), 304);
+        assert!(response.text().is_empty());
 
+        // Remembered units change the page, and so its ETag.
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_header(IF_NONE_MATCH, etag)
+            .add_header(COOKIE, HeaderValue::from_static("units=imperial"))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("°F"));
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_flags_stale_forecast() {
+        let open_meteo = MockOpenMeteo::start().await;
+        let config = AppConfig {
+            cache: CacheConfig {
+                forecast_ttl_secs: 1,
+                forecast_stale_while_revalidate_secs: 0,
+                ..CacheConfig::default()
+            },
+            ..open_meteo.app_config()
+        };
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(AppState::new(setup_test_db().await, &config));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let html = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await
+            .text();
+        assert!(!html.contains("stale-forecast"));
+
+        open_meteo.fail_with(FORECAST_PATH, 503).await;
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await;
         assert_eq!(response.status_code(), 200);
         let html = response.text();
-        assert!(html.contains("Weather for London"));
-        assert!(html.contains("°C"));
+        assert!(html.contains("stale-forecast"));
+        assert!(html.contains("Showing the forecast as of"));
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_names_the_provider() {
+        let open_meteo = MockOpenMeteo::start().await;
+        let met_norway = MockMetNorway::start().await;
+        let config = AppConfig {
+            met_norway: met_norway.config(),
+            weather: WeatherConfig::default(),
+            ..open_meteo.app_config()
+        };
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(AppState::new(setup_test_db().await, &config));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let html = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await
+            .text();
+        assert!(html.contains(r#"Data from <span class="provider">Open-Meteo</span>"#));
+
+        open_meteo.fail_with(FORECAST_PATH, 503).await;
+        // A fresh state, so London's forecast is not served from the cache.
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(AppState::new(setup_test_db().await, &config));
+        let server = TestServer::new(app.into_make_service()).unwrap();
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains(r#"Data from <span class="provider">MET Norway</span>"#));
+        assert!(html.contains("Light rain"));
+    }
+
+    #[tokio::test]
+    async fn test_show_history_page() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .route("/weather/history", get(handlers::weather::history))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let html = server
+            .get("/weather")
+            .add_query_param("city", "Berlin")
+            .await
+            .text();
+        assert!(html.contains(r#"<form action="/weather/history" method="get""#));
+
+        let response = server
+            .get("/weather/history")
+            .add_query_param("city", "Berlin")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-02")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("Past weather for Berlin"));
+        assert!(html.contains("1 March 2024 to 2 March 2024"));
+        assert!(html.contains("Times in Europe/Berlin"));
+        assert!(html.contains("Friday, 1 March 2024"));
+        assert!(html.contains("Saturday, 2 March 2024"));
+        assert!(html.contains("Light rain"));
+        assert!(html.contains(r#"href="/weather?city=Berlin""#));
+
+        let response = server
+            .get("/weather/history")
+            .add_query_param("city", "Berlin")
+            .add_query_param("start", "2024-03-01")
+            .await;
+        assert_eq!(response.status_code(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_picking_an_ambiguous_city_shows_its_history() {
+        let app = Router::new()
+            .route("/weather/history", get(handlers::weather::history))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+        let picker = server
+            .get("/weather/history")
+            .add_query_param("city", "Springfield")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-02")
+            .await
+            .text();
+        assert!(picker.contains("Did you mean"));
+
+        let link = first_link(&picker, "/weather/history");
+        assert!(link.contains("&start=2024-03-01&end=2024-03-02"), "{link}");
+        let response = server.get(&link).await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("Past weather for Springfield"));
+        assert!(html.contains("1 March 2024 to 2 March 2024"));
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_window() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("days", "1")
+            .add_query_param("past_days", "1")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("Friday, 25 October 2024"));
+        assert!(html.contains("Saturday, 26 October 2024"));
+        assert!(!html.contains("Sunday, 27 October 2024"));
+        assert!(html.contains(r#"name="days" min="1" max="16""#));
+        assert!(html.contains(r#"value="1" class="form-control"#));
+        // The unit switcher keeps the window.
+        assert!(html.contains(r#"<input type="hidden" name="past_days" value="1">"#));
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .add_query_param("days", "30")
+            .await;
+        assert_eq!(response.status_code(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_by_coordinates() {
+        let state = test_state().await;
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(state.clone());
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("lat", "-33.8688")
+            .add_query_param("lon", "151.2093")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("Weather for -33.8688, 151.2093"));
+
+        let history = CityRepository::new(state.db)
+            .get_recent_searches(10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].name, "-33.8688, 151.2093");
+        assert!((history[0].lat - -33.8688).abs() < f32::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_ambiguous_city() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "Springfield")
+            .await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(html.contains("Did you mean"));
+        assert!(html.contains(
+            "/weather?lat=39.80172&amp;lon=-89.64371&amp;name=Springfield&amp;country=US\
+             &amp;admin1=Illinois&amp;tz=America/Chicago"
+        ));
+        assert!(html.contains("Massachusetts"));
+    }
+
+    /// The first link on `html` to `path`, unescaped.
+    fn first_link(html: &str, path: &str) -> String {
+        html.split("href=\"")
+            .skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .find(|href| href.starts_with(&format!("{path}?")))
+            .unwrap()
+            .replace("&amp;", "&")
+    }
+
+    #[tokio::test]
+    async fn test_picking_an_ambiguous_city_shows_its_forecast() {
+        // Nothing in the gazetteer or the search history to name coordinates after.
+        let mock = MockOpenMeteo::start().await;
+        let state = AppState::new(setup_test_db().await, &mock.app_config());
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(state.clone());
+        let server = TestServer::new(app.into_make_service()).unwrap();
+        let picker = server
+            .get("/weather")
+            .add_query_param("city", "Springfield")
+            .add_query_param("units", "imperial")
+            .add_query_param("days", "3")
+            .await
+            .text();
+
+        let response = server.get(&first_link(&picker, "/weather")).await;
+
+        assert_eq!(response.status_code(), 200);
+        let html = response.text();
+        assert!(!html.contains("Did you mean"));
+        assert!(html.contains("Springfield"));
+        assert!(html.contains("Missouri"));
+        assert!(html.contains("Times in America/Chicago"));
+        assert!(html.contains("<h1 class=\"mb-4\">Weather for Springfield</h1>"));
+        assert!(html.contains("°F"));
+        assert!(html.contains(r#"value="3""#));
+        let searches = CityRepository::new(state.db.clone())
+            .get_recent_searches(1)
+            .await
+            .unwrap();
+        assert_eq!(searches[0].name, "Springfield");
+    }
+
+    #[tokio::test]
+    async fn test_show_weather_page_unknown_city() {
+        let app = Router::new()
+            .route("/weather", get(handlers::weather::show))
+            .with_state(test_state().await);
+        let server = TestServer::new(app.into_make_service()).unwrap();
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "Atlantis")
+            .await;
+
+        assert_eq!(response.status_code(), 404);
+        let html = response.text();
+        assert!(html.contains("<h1 class=\"mb-4\">Not Found</h1>"));
+        assert!(html.contains("<code>place_not_found</code>"));
+        assert!(html.contains("Atlantis"));
     }
 }
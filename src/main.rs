@@ -1,16 +1,31 @@
 mod api;
+mod conditional;
+mod config;
 mod entities;
 mod errors;
+mod extract;
 mod handlers;
 mod repositories;
 mod services;
+mod state;
+#[cfg(test)]
+mod test_support;
 
-use axum::{http::Request, routing::get, Router};
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::{self, Next},
+    response::Response,
+    routing::get,
+    Router,
+};
 use bytes::Bytes;
+use config::AppConfig;
 use env_logger::{Builder, WriteStyle};
-use log::{debug, info, LevelFilter};
-use sea_orm::DatabaseConnection;
-use std::{net::SocketAddr, time::Duration};
+use log::{debug, error, info, LevelFilter};
+use services::cache::{spawn_sweeper, track_lookups};
+use state::AppState;
+use std::time::Duration;
 use tower_http::{classify::ServerErrorsFailureClass, trace::TraceLayer};
 use tracing::Span;
 
@@ -27,19 +42,25 @@ async fn main() {
         .write_style(WriteStyle::Auto) // This will auto-detect if colors should be used
         .init();
 
-    let db_url =
-        std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://weather.db".to_string());
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let db_config = &config.database;
 
-    info!("Connecting to database at {}", db_url);
+    info!("Connecting to database at {}", db_config.url);
 
     // Enable logging for database connections
     let db = sea_orm::Database::connect(
-        sea_orm::ConnectOptions::new(db_url)
-            .max_connections(100)
-            .min_connections(5)
-            .connect_timeout(Duration::from_secs(8))
-            .acquire_timeout(Duration::from_secs(8))
-            .idle_timeout(Duration::from_secs(8))
+        sea_orm::ConnectOptions::new(&db_config.url)
+            .max_connections(db_config.max_connections)
+            .min_connections(db_config.min_connections)
+            .connect_timeout(db_config.connect_timeout())
+            .acquire_timeout(db_config.acquire_timeout())
+            .idle_timeout(db_config.idle_timeout())
             .sqlx_logging(true)
             .sqlx_logging_level(log::LevelFilter::Warn)
             .to_owned(),
@@ -49,18 +70,60 @@ async fn main() {
 
     info!("Database connection established");
 
-    let app = create_router(db);
+    let mut args = std::env::args().skip(1);
+    if let Some(command) = args.next() {
+        if let ("import-gazetteer", Some(path)) = (command.as_str(), args.next()) {
+            import_gazetteer(db, &path).await;
+            return;
+        }
+
+        error!("Usage: forecast-rust [import-gazetteer <geonames-file>]");
+        std::process::exit(2);
+    }
+
+    // Validated by `AppConfig::load`
+    let addr = config.server.socket_addr().expect("Invalid server address");
+    spawn_sweeper(
+        repositories::CacheRepository::new(db.clone()),
+        &config.cache,
+    );
+    let app = create_router(AppState::new(db, &config));
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    info!("Listening on {}", addr);
+    info!("Listening on {addr}");
 
     axum::serve(listener, app.into_make_service())
         .await
         .unwrap();
 }
 
-fn create_router(db: DatabaseConnection) -> Router {
+/// Loads a geonames.org dump into the gazetteer used for offline reverse geocoding.
+async fn import_gazetteer(db: sea_orm::DatabaseConnection, path: &str) {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            error!("Failed to open {path}: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let repository = repositories::GazetteerRepository::new(db);
+    match repository
+        .import_geonames(std::io::BufReader::new(file))
+        .await
+    {
+        Ok(count) => {
+            let total = repository.count().await.unwrap_or_default();
+            info!("Imported {count} gazetteer entries from {path} ({total} in total)");
+        }
+        Err(err) => {
+            error!("Failed to import {path}: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn create_router(state: AppState) -> Router {
     // Create a trace layer with custom configuration
     let trace_layer = TraceLayer::new_for_http()
         .on_request(|request: &Request<_>, _span: &Span| {
@@ -87,19 +150,246 @@ fn create_router(db: DatabaseConnection) -> Router {
                 );
             },
         );
-    // API routes
-    let api_router = Router::new().route("/weather", get(api::weather::get));
+    // API routes, with the OpenAPI document generated from them
+    let api_router = api::openapi::router();
 
     // Page routes
     let page_router = Router::new()
         .route("/", get(handlers::pages::index))
         .route("/weather", get(handlers::weather::show))
+        .route("/weather/history", get(handlers::weather::history))
         .route("/stats", get(handlers::stats::show));
 
     // Combine them
     Router::new()
-        .nest("/api", api_router) // All API routes under /api
+        .merge(api_router) // All API routes under /api
         .merge(page_router) // HTML pages at root level
-        .with_state(db)
+        .with_state(state)
+        .layer(middleware::from_fn(cache_header))
         .layer(trace_layer)
 }
+
+/// Reports the cache lookups made for a response in `X-Cache`, e.g.
+/// `geocode=HIT, forecast=MISS`.
+async fn cache_header(request: Request, next: Next) -> Response {
+    let (mut response, lookups) = track_lookups(next.run(request)).await;
+    if lookups.is_empty() {
+        return response;
+    }
+
+    let value = lookups
+        .iter()
+        .map(|(cache, status)| format!("{cache}={}", status.as_str()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert("x-cache", value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        setup_test_db, test_state, MockOpenMeteo, FORECAST_PATH, GEOCODING_PATH,
+    };
+    use axum_test::TestServer;
+    use std::future::IntoFuture;
+
+    #[tokio::test]
+    async fn test_router_uses_injected_provider() {
+        let server =
+            TestServer::new(create_router(test_state().await).into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Paris")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.json::<serde_json::Value>()["city"], "Paris");
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "Berlin")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("Weather for Berlin"));
+
+        let response = server.get("/stats").await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("Berlin"));
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Atlantis")
+            .await;
+        assert_eq!(response.status_code(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_router_deprecates_unversioned_weather_api() {
+        let server =
+            TestServer::new(create_router(test_state().await).into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/v1/weather/daily")
+            .add_query_param("city", "Paris")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(
+            response.json::<serde_json::Value>()["location"]["name"],
+            "Paris"
+        );
+        assert!(response.maybe_header("deprecation").is_none());
+
+        for path in ["/api/weather", "/api/weather/daily"] {
+            let response = server.get(path).add_query_param("city", "Paris").await;
+            assert_eq!(response.status_code(), 200);
+            assert_eq!(response.header("deprecation"), "@1792195200");
+            assert_eq!(
+                response.header("link"),
+                format!("</api/v1{}>; rel=\"successor-version\"", &path[4..]).as_str()
+            );
+        }
+
+        // Errors too.
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Atlantis")
+            .await;
+        assert_eq!(response.status_code(), 404);
+        assert!(response.maybe_header("deprecation").is_some());
+        let response = server.get("/api/health").await;
+        assert!(response.maybe_header("deprecation").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_router_against_mock_open_meteo() {
+        let mock = MockOpenMeteo::start().await;
+        let state = AppState::new(setup_test_db().await, &mock.app_config());
+        let server = TestServer::new(create_router(state).into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Tokyo")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let body = response.json::<serde_json::Value>();
+        assert_eq!(body["hourly_forecast"].as_array().unwrap().len(), 24);
+        assert_eq!(body["temperature"]["min"], 7.4);
+        assert_eq!(body["temperature"]["max"], 14.6);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "London")
+            .add_query_param("include", "air_quality")
+            .await;
+        let air_quality = &response.json::<serde_json::Value>()["air_quality"];
+        assert_eq!(air_quality["current"]["time"], "2024-10-26T13:00:00+01:00");
+        assert_eq!(air_quality["current"]["european_aqi"], 36.0);
+        assert_eq!(air_quality["us_aqi_category"], "good");
+
+        let response = server
+            .get("/weather")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert!(response.text().contains("14.6°C"));
+
+        let response = server
+            .get("/api/weather/daily")
+            .add_query_param("city", "London")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let day = &response.json::<serde_json::Value>()["days"][0];
+        assert_eq!(day["date"], "2024-10-26");
+        assert_eq!(day["temperature_max"], 14.6);
+        assert_eq!(day["temperature_mean"], 10.6);
+        assert_eq!(day["sunset"], "2024-10-26T17:46:00+01:00");
+
+        let response = server
+            .get("/api/weather/history")
+            .add_query_param("city", "Berlin")
+            .add_query_param("start", "2024-03-01")
+            .add_query_param("end", "2024-03-01")
+            .add_query_param("variables", "temperature_2m,precipitation")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let body = response.json::<serde_json::Value>();
+        assert_eq!(body["timezone"], "Europe/Berlin");
+        assert_eq!(body["days"][0]["temperature_mean"], 4.1);
+        assert_eq!(body["days"][0]["precipitation_sum"], 1.5);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Nowhere")
+            .await;
+        assert_eq!(response.status_code(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_repeat_lookups_are_served_from_cache() {
+        let mock = MockOpenMeteo::start().await;
+        let state = AppState::new(setup_test_db().await, &mock.app_config());
+        let server = TestServer::new(create_router(state).into_make_service()).unwrap();
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Tokyo")
+            .await;
+        assert_eq!(response.header("x-cache"), "geocode=MISS, forecast=MISS");
+        let requests = mock.request_count().await;
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "tokyo")
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header("x-cache"), "geocode=HIT, forecast=HIT");
+        // Nothing went upstream; Tokyo is known to be inland as far as the stand-in's sea goes.
+        assert_eq!(mock.request_count().await, requests);
+
+        let response = server
+            .get("/api/weather")
+            .add_query_param("city", "Tokyo")
+            .add_query_param("units", "imperial")
+            .await;
+        assert_eq!(response.header("x-cache"), "geocode=HIT, forecast=MISS");
+
+        let response = server.get("/api/health").await;
+        assert!(response.maybe_header("x-cache").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_identical_requests_share_upstream_calls() {
+        let mock = MockOpenMeteo::start().await;
+        mock.delay_forecasts(Duration::from_millis(200)).await;
+        let state = AppState::new(setup_test_db().await, &mock.app_config());
+        let server = TestServer::new(create_router(state).into_make_service()).unwrap();
+        let request = || {
+            server
+                .get("/api/weather")
+                .add_query_param("city", "Tokyo")
+                .into_future()
+        };
+
+        let responses = tokio::join!(request(), request(), request(), request(), request());
+
+        for response in [
+            responses.0,
+            responses.1,
+            responses.2,
+            responses.3,
+            responses.4,
+        ] {
+            assert_eq!(response.status_code(), 200);
+            assert_eq!(
+                response.json::<serde_json::Value>()["temperature"]["max"],
+                14.6
+            );
+        }
+        assert_eq!(mock.requests_to(GEOCODING_PATH).await, 1);
+        assert_eq!(mock.requests_to(FORECAST_PATH).await, 1);
+    }
+}
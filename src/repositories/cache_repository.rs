@@ -0,0 +1,288 @@
+use crate::entities::{forecast_cache, geocode_cache};
+use crate::repositories::city_repository::RepositoryError;
+use sea_orm::prelude::DateTimeUtc;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+
+/// The tables backing the geocoding and forecast caches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheTable {
+    Geocode,
+    Forecast,
+}
+
+/// A cached payload as stored in either table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub key: String,
+    /// The cached value as JSON.
+    pub payload: String,
+    pub provider: String,
+    pub fetched_at: DateTimeUtc,
+    pub expires_at: DateTimeUtc,
+}
+
+impl From<geocode_cache::Model> for CacheEntry {
+    fn from(model: geocode_cache::Model) -> Self {
+        Self {
+            key: model.key,
+            payload: model.payload,
+            provider: model.provider,
+            fetched_at: model.fetched_at,
+            expires_at: model.expires_at,
+        }
+    }
+}
+
+impl From<forecast_cache::Model> for CacheEntry {
+    fn from(model: forecast_cache::Model) -> Self {
+        Self {
+            key: model.key,
+            payload: model.payload,
+            provider: model.provider,
+            fetched_at: model.fetched_at,
+            expires_at: model.expires_at,
+        }
+    }
+}
+
+/// Reads and writes the `geocode_cache` and `forecast_cache` tables, which keep cached
+/// lookups across restarts.
+#[derive(Clone)]
+pub struct CacheRepository {
+    db: DatabaseConnection,
+}
+
+impl CacheRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Returns the entry stored under `key` unless it had expired by `cutoff`.
+    ///
+    /// A `cutoff` before now also finds entries that expired since.
+    pub async fn find(
+        &self,
+        table: CacheTable,
+        key: &str,
+        cutoff: DateTimeUtc,
+    ) -> Result<Option<CacheEntry>, RepositoryError> {
+        let entry = match table {
+            CacheTable::Geocode => geocode_cache::Entity::find_by_id(key)
+                .filter(geocode_cache::Column::ExpiresAt.gt(cutoff))
+                .one(&self.db)
+                .await?
+                .map(CacheEntry::from),
+            CacheTable::Forecast => forecast_cache::Entity::find_by_id(key)
+                .filter(forecast_cache::Column::ExpiresAt.gt(cutoff))
+                .one(&self.db)
+                .await?
+                .map(CacheEntry::from),
+        };
+
+        Ok(entry)
+    }
+
+    /// Stores `entry`, replacing any entry with the same key.
+    pub async fn save(&self, table: CacheTable, entry: CacheEntry) -> Result<(), RepositoryError> {
+        match table {
+            CacheTable::Geocode => {
+                geocode_cache::Entity::insert(geocode_cache::ActiveModel {
+                    key: Set(entry.key),
+                    payload: Set(entry.payload),
+                    provider: Set(entry.provider),
+                    fetched_at: Set(entry.fetched_at),
+                    expires_at: Set(entry.expires_at),
+                })
+                .on_conflict(
+                    OnConflict::column(geocode_cache::Column::Key)
+                        .update_columns([
+                            geocode_cache::Column::Payload,
+                            geocode_cache::Column::Provider,
+                            geocode_cache::Column::FetchedAt,
+                            geocode_cache::Column::ExpiresAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&self.db)
+                .await?;
+            }
+            CacheTable::Forecast => {
+                forecast_cache::Entity::insert(forecast_cache::ActiveModel {
+                    key: Set(entry.key),
+                    payload: Set(entry.payload),
+                    provider: Set(entry.provider),
+                    fetched_at: Set(entry.fetched_at),
+                    expires_at: Set(entry.expires_at),
+                })
+                .on_conflict(
+                    OnConflict::column(forecast_cache::Column::Key)
+                        .update_columns([
+                            forecast_cache::Column::Payload,
+                            forecast_cache::Column::Provider,
+                            forecast_cache::Column::FetchedAt,
+                            forecast_cache::Column::ExpiresAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&self.db)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the entries of `table` that had expired by `cutoff`; returns how many.
+    pub async fn delete_expired(
+        &self,
+        table: CacheTable,
+        cutoff: DateTimeUtc,
+    ) -> Result<u64, RepositoryError> {
+        let result = match table {
+            CacheTable::Geocode => {
+                geocode_cache::Entity::delete_many()
+                    .filter(geocode_cache::Column::ExpiresAt.lte(cutoff))
+                    .exec(&self.db)
+                    .await?
+            }
+            CacheTable::Forecast => {
+                forecast_cache::Entity::delete_many()
+                    .filter(forecast_cache::Column::ExpiresAt.lte(cutoff))
+                    .exec(&self.db)
+                    .await?
+            }
+        };
+
+        Ok(result.rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::setup_test_db;
+    use chrono::{Duration, TimeZone, Utc};
+    use pretty_assertions::assert_eq;
+
+    fn entry(key: &str, payload: &str, expires_in_minutes: i64) -> CacheEntry {
+        let fetched_at = Utc.with_ymd_and_hms(2024, 10, 26, 12, 0, 0).unwrap();
+        CacheEntry {
+            key: key.to_string(),
+            payload: payload.to_string(),
+            provider: "open_meteo".to_string(),
+            fetched_at,
+            expires_at: fetched_at + Duration::minutes(expires_in_minutes),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_find_until_expiry() {
+        let repository = CacheRepository::new(setup_test_db().await);
+        let saved = entry("london", "[]", 10);
+        repository
+            .save(CacheTable::Geocode, saved.clone())
+            .await
+            .unwrap();
+
+        let now = saved.fetched_at + Duration::minutes(9);
+        assert_eq!(
+            repository
+                .find(CacheTable::Geocode, "london", now)
+                .await
+                .unwrap(),
+            Some(saved.clone())
+        );
+        // Each table has its own keys.
+        assert_eq!(
+            repository
+                .find(CacheTable::Forecast, "london", now)
+                .await
+                .unwrap(),
+            None
+        );
+
+        let now = saved.fetched_at + Duration::minutes(10);
+        assert_eq!(
+            repository
+                .find(CacheTable::Geocode, "london", now)
+                .await
+                .unwrap(),
+            None
+        );
+        // Still there for callers that accept expired entries.
+        assert_eq!(
+            repository
+                .find(CacheTable::Geocode, "london", now - Duration::minutes(1))
+                .await
+                .unwrap(),
+            Some(saved)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_replaces_existing_entry() {
+        let repository = CacheRepository::new(setup_test_db().await);
+        repository
+            .save(CacheTable::Forecast, entry("51.51,-0.13", "{}", 10))
+            .await
+            .unwrap();
+        let newer = CacheEntry {
+            provider: "met_norway".to_string(),
+            ..entry("51.51,-0.13", r#"{"hourly":{}}"#, 20)
+        };
+        repository
+            .save(CacheTable::Forecast, newer.clone())
+            .await
+            .unwrap();
+
+        let found = repository
+            .find(CacheTable::Forecast, "51.51,-0.13", newer.fetched_at)
+            .await
+            .unwrap();
+        assert_eq!(found, Some(newer));
+    }
+
+    #[tokio::test]
+    async fn test_delete_expired_sweeps_one_table() {
+        let repository = CacheRepository::new(setup_test_db().await);
+        for (table, key, minutes) in [
+            (CacheTable::Geocode, "london", 5),
+            (CacheTable::Geocode, "paris", 60),
+            (CacheTable::Forecast, "51.51,-0.13", 5),
+            (CacheTable::Forecast, "48.85,2.35", 60),
+        ] {
+            repository
+                .save(table, entry(key, "{}", minutes))
+                .await
+                .unwrap();
+        }
+        let now = entry("", "", 30).expires_at;
+
+        assert_eq!(
+            repository
+                .delete_expired(CacheTable::Forecast, now)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repository
+                .delete_expired(CacheTable::Forecast, now)
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(repository
+            .find(CacheTable::Forecast, "48.85,2.35", now)
+            .await
+            .unwrap()
+            .is_some());
+        let cutoff = entry("", "", 0).expires_at;
+        assert!(repository
+            .find(CacheTable::Geocode, "london", cutoff)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -2,7 +2,8 @@ use crate::entities::cities::{self, ActiveModel, Entity as Cities, Model};
 use crate::services::weather_service::LatLong;
 use sea_orm::prelude::DateTimeUtc;
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::Set, DatabaseConnection, EntityTrait, QueryOrder, QuerySelect,
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect,
 };
 use thiserror::Error;
 
@@ -10,6 +11,9 @@ use thiserror::Error;
 pub enum RepositoryError {
     #[error("Database error: {0}")]
     Database(#[from] sea_orm::DbErr),
+
+    #[error("Import failed: {0}")]
+    Import(String),
 }
 
 pub struct CityRepository {
@@ -52,6 +56,23 @@ impl CityRepository {
 
         Ok(cities)
     }
+
+    /// Returns past searches inside the box `delta` degrees either side of `coords`.
+    pub async fn find_within(
+        &self,
+        coords: &LatLong,
+        delta: f32,
+    ) -> Result<Vec<Model>, RepositoryError> {
+        let cities = Cities::find()
+            .filter(cities::Column::Lat.between(coords.latitude - delta, coords.latitude + delta))
+            .filter(
+                cities::Column::Long.between(coords.longitude - delta, coords.longitude + delta),
+            )
+            .all(&self.db)
+            .await?;
+
+        Ok(cities)
+    }
 }
 
 #[cfg(test)]
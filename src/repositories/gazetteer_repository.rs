@@ -0,0 +1,189 @@
+use crate::entities::gazetteer::{self, ActiveModel, Entity as Gazetteer, Model};
+use crate::repositories::city_repository::RepositoryError;
+use crate::services::weather_service::LatLong;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter};
+use std::io::BufRead;
+
+/// Rows written per `INSERT` while importing, keeping well under the `SQLite` variable limit.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+pub struct GazetteerRepository {
+    db: DatabaseConnection,
+}
+
+impl GazetteerRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    /// Imports a geonames.org `cities*.txt` / `allCountries.txt` dump, replacing rows with the same id.
+    ///
+    /// Only populated places (feature class `P`) are kept. Returns the number of rows written.
+    pub async fn import_geonames(&self, reader: impl BufRead) -> Result<usize, RepositoryError> {
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        let mut imported = 0;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line =
+                line.map_err(|e| RepositoryError::Import(format!("line {}: {e}", index + 1)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let Some(entry) = parse_geonames_line(&line)
+                .map_err(|e| RepositoryError::Import(format!("line {}: {e}", index + 1)))?
+            else {
+                continue;
+            };
+
+            batch.push(entry);
+            if batch.len() == IMPORT_BATCH_SIZE {
+                imported += self.upsert(std::mem::take(&mut batch)).await?;
+            }
+        }
+
+        if !batch.is_empty() {
+            imported += self.upsert(batch).await?;
+        }
+
+        Ok(imported)
+    }
+
+    /// Returns entries inside the box `delta` degrees either side of `coords`.
+    pub async fn find_within(
+        &self,
+        coords: &LatLong,
+        delta: f32,
+    ) -> Result<Vec<Model>, RepositoryError> {
+        let entries = Gazetteer::find()
+            .filter(
+                gazetteer::Column::Lat.between(coords.latitude - delta, coords.latitude + delta),
+            )
+            .filter(
+                gazetteer::Column::Long.between(coords.longitude - delta, coords.longitude + delta),
+            )
+            .all(&self.db)
+            .await?;
+
+        Ok(entries)
+    }
+
+    pub async fn count(&self) -> Result<u64, RepositoryError> {
+        Ok(Gazetteer::find().count(&self.db).await?)
+    }
+
+    async fn upsert(&self, entries: Vec<ActiveModel>) -> Result<usize, RepositoryError> {
+        let count = entries.len();
+
+        Gazetteer::insert_many(entries)
+            .on_conflict(
+                OnConflict::column(gazetteer::Column::Id)
+                    .update_columns([
+                        gazetteer::Column::Name,
+                        gazetteer::Column::CountryCode,
+                        gazetteer::Column::Admin1,
+                        gazetteer::Column::Lat,
+                        gazetteer::Column::Long,
+                        gazetteer::Column::Population,
+                        gazetteer::Column::Timezone,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await?;
+
+        Ok(count)
+    }
+}
+
+/// Parses one tab-separated geonames.org row; `Ok(None)` for rows that are not populated places.
+fn parse_geonames_line(line: &str) -> Result<Option<ActiveModel>, String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 18 {
+        return Err(format!(
+            "expected at least 18 columns, found {}",
+            fields.len()
+        ));
+    }
+
+    if fields[6] != "P" {
+        return Ok(None);
+    }
+
+    let parse_f32 = |index: usize, name: &str| {
+        fields[index]
+            .parse::<f32>()
+            .map_err(|e| format!("invalid {name} {:?}: {e}", fields[index]))
+    };
+    let optional = |index: usize| Some(fields[index].to_string()).filter(|v| !v.is_empty());
+
+    let model = Model {
+        id: fields[0]
+            .parse()
+            .map_err(|e| format!("invalid id {:?}: {e}", fields[0]))?,
+        name: fields[1].to_string(),
+        country_code: optional(8),
+        admin1: optional(10),
+        lat: parse_f32(4, "latitude")?,
+        long: parse_f32(5, "longitude")?,
+        population: fields[14].parse().ok().filter(|p| *p > 0),
+        timezone: optional(17),
+    };
+
+    Ok(Some(model.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::setup_test_db;
+    use pretty_assertions::assert_eq;
+
+    const SAMPLE: &str = "\
+2643743\tLondon\tLondon\tLondres\t51.50853\t-0.12574\tP\tPPLC\tGB\t\tENG\tGLA\t\t\t8961989\t\t25\tEurope/London\t2024-01-01
+2643741\tCity of London\tCity of London\t\t51.51279\t-0.09184\tP\tPPLA3\tGB\t\tENG\tGLA\t\t\t8071\t\t8\tEurope/London\t2024-01-01
+2635167\tUnited Kingdom\tUnited Kingdom\t\t54.75844\t-2.69531\tA\tPCLI\tGB\t\t00\t\t\t\t66488991\t\t131\tEurope/London\t2024-01-01
+2988507\tParis\tParis\t\t48.85341\t2.3488\tP\tPPLC\tFR\t\t11\t75\t\t\t2138551\t\t42\tEurope/Paris\t2024-01-01
+";
+
+    #[tokio::test]
+    async fn test_import_and_find_within() {
+        let repo = GazetteerRepository::new(setup_test_db().await);
+
+        let imported = repo.import_geonames(SAMPLE.as_bytes()).await.unwrap();
+        assert_eq!(imported, 3, "the country row should be skipped");
+
+        // Re-importing replaces rows instead of duplicating them
+        repo.import_geonames(SAMPLE.as_bytes()).await.unwrap();
+        assert_eq!(repo.count().await.unwrap(), 3);
+
+        let near_london = LatLong {
+            latitude: 51.5,
+            longitude: -0.1,
+        };
+        let mut names: Vec<_> = repo
+            .find_within(&near_london, 0.5)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["City of London", "London"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_reports_malformed_line() {
+        let repo = GazetteerRepository::new(setup_test_db().await);
+
+        let err = repo
+            .import_geonames("1\tBroken\tBroken\n".as_bytes())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("line 1"),
+            "unexpected error: {err}"
+        );
+    }
+}
@@ -1,3 +1,7 @@
+pub mod cache_repository;
 pub mod city_repository;
+pub mod gazetteer_repository;
 
+pub use cache_repository::{CacheEntry, CacheRepository, CacheTable};
 pub use city_repository::CityRepository;
+pub use gazetteer_repository::GazetteerRepository;
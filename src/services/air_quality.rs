@@ -0,0 +1,130 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Hourly series requested from the air-quality API, in Open-Meteo's names.
+pub const AIR_QUALITY_VARIABLES: &str = "pm2_5,pm10,ozone,european_aqi,us_aqi,uv_index";
+
+/// Longest horizon the air-quality API forecasts, in days.
+pub const MAX_AIR_QUALITY_DAYS: u32 = 7;
+
+/// Pollutant concentrations (µg/m³) and indices at one instant; `None` where the model
+/// has no value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AirQualityValues {
+    pub pm2_5: Option<f64>,
+    pub pm10: Option<f64>,
+    pub ozone: Option<f64>,
+    pub european_aqi: Option<f64>,
+    pub us_aqi: Option<f64>,
+    pub uv_index: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AirQualityReading {
+    pub time: DateTime<Utc>,
+    pub values: AirQualityValues,
+}
+
+/// Current conditions plus hourly readings over the forecast window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AirQuality {
+    pub current: Option<AirQualityReading>,
+    pub hourly: Vec<AirQualityReading>,
+}
+
+/// Band of an air quality index on the European (EEA) or US (EPA) scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AqiCategory {
+    Good,
+    Moderate,
+    // European bands.
+    Fair,
+    Poor,
+    VeryPoor,
+    ExtremelyPoor,
+    // US bands.
+    UnhealthyForSensitiveGroups,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+impl AqiCategory {
+    /// Band of a European AQI value: 20 points per band, extremely poor above 100.
+    pub fn european(aqi: f64) -> Self {
+        match aqi {
+            a if a <= 20.0 => Self::Good,
+            a if a <= 40.0 => Self::Fair,
+            a if a <= 60.0 => Self::Moderate,
+            a if a <= 80.0 => Self::Poor,
+            a if a <= 100.0 => Self::VeryPoor,
+            _ => Self::ExtremelyPoor,
+        }
+    }
+
+    /// Band of a US AQI value at the EPA breakpoints.
+    pub fn us(aqi: f64) -> Self {
+        match aqi {
+            a if a <= 50.0 => Self::Good,
+            a if a <= 100.0 => Self::Moderate,
+            a if a <= 150.0 => Self::UnhealthyForSensitiveGroups,
+            a if a <= 200.0 => Self::Unhealthy,
+            a if a <= 300.0 => Self::VeryUnhealthy,
+            _ => Self::Hazardous,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Good => "Good",
+            Self::Moderate => "Moderate",
+            Self::Fair => "Fair",
+            Self::Poor => "Poor",
+            Self::VeryPoor => "Very poor",
+            Self::ExtremelyPoor => "Extremely poor",
+            Self::UnhealthyForSensitiveGroups => "Unhealthy for sensitive groups",
+            Self::Unhealthy => "Unhealthy",
+            Self::VeryUnhealthy => "Very unhealthy",
+            Self::Hazardous => "Hazardous",
+        }
+    }
+
+    /// Inline style for a badge in the band's conventional colour.
+    pub fn badge_style(self) -> &'static str {
+        match self {
+            Self::Good => "background-color: #00e400; color: #000",
+            Self::Fair => "background-color: #50ccaa; color: #000",
+            Self::Moderate => "background-color: #ffff00; color: #000",
+            Self::UnhealthyForSensitiveGroups => "background-color: #ff7e00; color: #000",
+            Self::Poor | Self::Unhealthy => "background-color: #ff0000; color: #fff",
+            Self::VeryPoor | Self::VeryUnhealthy => "background-color: #8f3f97; color: #fff",
+            Self::ExtremelyPoor | Self::Hazardous => "background-color: #7e0023; color: #fff",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(12.0, AqiCategory::Good ; "when clean")]
+    #[test_case(20.0, AqiCategory::Good ; "at the first boundary")]
+    #[test_case(20.5, AqiCategory::Fair ; "just above it")]
+    #[test_case(75.0, AqiCategory::Poor ; "when poor")]
+    #[test_case(140.0, AqiCategory::ExtremelyPoor ; "when extremely poor")]
+    fn test_european_category(aqi: f64, expected: AqiCategory) {
+        assert_eq!(AqiCategory::european(aqi), expected);
+    }
+
+    #[test_case(50.0, AqiCategory::Good ; "at the first boundary")]
+    #[test_case(58.0, AqiCategory::Moderate ; "when moderate")]
+    #[test_case(120.0, AqiCategory::UnhealthyForSensitiveGroups ; "for sensitive groups")]
+    #[test_case(180.0, AqiCategory::Unhealthy ; "when unhealthy")]
+    #[test_case(350.0, AqiCategory::Hazardous ; "when hazardous")]
+    fn test_us_category(aqi: f64, expected: AqiCategory) {
+        assert_eq!(AqiCategory::us(aqi), expected);
+    }
+}
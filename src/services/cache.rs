@@ -0,0 +1,1260 @@
+use crate::config::CacheConfig;
+use crate::repositories::{CacheEntry, CacheRepository, CacheTable};
+use crate::services::air_quality::{AirQuality, MAX_AIR_QUALITY_DAYS};
+use crate::services::forecast::{ForecastRequest, HourlyVariable};
+use crate::services::places::PlaceQuery;
+use crate::services::provider::{
+    AirQualityProvider, Geocoder, MarineProvider, ProviderKind, WeatherProvider,
+};
+use crate::services::weather_service::{LatLong, MarineData, Place, ServiceError, WeatherData};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use log::{debug, info, warn};
+use lru::LruCache;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Whether a lookup was answered from a cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    /// Answered with an expired entry.
+    Stale,
+}
+
+impl CacheStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+            Self::Stale => "STALE",
+        }
+    }
+}
+
+/// A cache lookup made while serving a request: which cache, and its outcome.
+pub type Lookup = (&'static str, CacheStatus);
+
+tokio::task_local! {
+    static LOOKUPS: RefCell<Vec<Lookup>>;
+}
+
+/// Runs `future`, collecting the cache lookups made on its task in the order they happened.
+pub async fn track_lookups<F: Future>(future: F) -> (F::Output, Vec<Lookup>) {
+    LOOKUPS
+        .scope(RefCell::new(Vec::new()), async {
+            let output = future.await;
+            (output, LOOKUPS.with(RefCell::take))
+        })
+        .await
+}
+
+/// Notes a lookup for the enclosing [`track_lookups`], if there is one.
+fn record(cache: &'static str, status: CacheStatus) {
+    debug!("{cache} cache {}", status.as_str());
+    // Lookups outside a tracked request, such as in unit tests, go unreported.
+    let _ = LOOKUPS.try_with(|lookups| lookups.borrow_mut().push((cache, status)));
+}
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+/// A value found in a [`TtlCache`]; `expired_for` says how long ago it expired, if it has.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found<V> {
+    pub value: V,
+    pub expired_for: Option<Duration>,
+}
+
+/// A bounded map whose entries expire `ttl` after insertion; when full, the least
+/// recently used entry makes way for a new one.
+///
+/// Expired entries are still returned, marked as such, for a `grace` period.
+pub struct TtlCache<K: Hash + Eq, V> {
+    ttl: Duration,
+    grace: Duration,
+    entries: Mutex<LruCache<K, Entry<V>>>,
+}
+
+impl<K: Hash + Eq, V: Clone> TtlCache<K, V> {
+    pub fn new(capacity: NonZeroUsize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            grace: Duration::ZERO,
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Returns a copy of the entry for `key`, dropping it instead once its grace is over.
+    pub fn get(&self, key: &K) -> Option<Found<V>> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        match entries.get(key) {
+            Some(entry) if now < entry.expires_at + self.grace => Some(Found {
+                value: entry.value.clone(),
+                expired_for: (now >= entry.expires_at).then(|| now - entry.expires_at),
+            }),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        self.insert_expiring(key, value, Instant::now() + self.ttl);
+    }
+
+    /// Inserts an entry that expires at `expires_at` rather than after the cache's TTL.
+    pub fn insert_expiring(&self, key: K, value: V, expires_at: Instant) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(key, Entry { value, expires_at });
+    }
+}
+
+/// How long past expiry a cached value may still be served.
+#[derive(Debug, Clone, Copy, Default)]
+struct StaleWindows {
+    /// Served at once while a fresh value is fetched in the background.
+    revalidate: Duration,
+    /// Served only when fetching a fresh value fails.
+    if_error: Duration,
+}
+
+/// A cached value and when it was fetched.
+#[derive(Clone)]
+struct Stored<V> {
+    value: V,
+    fetched_at: DateTime<Utc>,
+}
+
+/// A value handed out by a [`Tiered`] cache.
+struct Served<V> {
+    value: V,
+    /// When the value was fetched, if it is past its TTL.
+    stale_as_of: Option<DateTime<Utc>>,
+    /// When the value's TTL runs out, or ran out.
+    expires_at: DateTime<Utc>,
+}
+
+/// A cache in memory, optionally backed by a database table that outlives restarts.
+///
+/// Database errors are logged and treated as misses: the cache only ever saves work.
+struct Tiered<V> {
+    name: &'static str,
+    table: CacheTable,
+    stale: StaleWindows,
+    memory: TtlCache<String, Stored<V>>,
+    repository: Option<CacheRepository>,
+    in_flight: SingleFlight<Stored<V>>,
+}
+
+impl<V> Tiered<V>
+where
+    V: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn new(
+        name: &'static str,
+        table: CacheTable,
+        capacity: NonZeroUsize,
+        ttl: Duration,
+        stale: StaleWindows,
+    ) -> Self {
+        Self {
+            name,
+            table,
+            stale,
+            memory: TtlCache::new(capacity, ttl).with_grace(stale.revalidate.max(stale.if_error)),
+            repository: None,
+            in_flight: SingleFlight::new(),
+        }
+    }
+
+    /// Returns the cached value for `key`, or stores and returns what `fetch` produces.
+    ///
+    /// Concurrent misses on one key share a single `fetch`, and its result or error. An
+    /// expired value is returned within the stale windows: at once, while a background
+    /// `fetch` refreshes it, or in place of an error from `fetch`.
+    async fn get_or_fetch<F, Fut>(
+        self: &Arc<Self>,
+        key: String,
+        fetch: F,
+    ) -> Result<Served<V>, ServiceError>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(V, ProviderKind), ServiceError>> + Send + 'static,
+    {
+        let found = self.lookup(&key).await;
+        let stale = match found {
+            Some(Found {
+                value,
+                expired_for: None,
+            }) => {
+                record(self.name, CacheStatus::Hit);
+                return Ok(self.serve(value, false));
+            }
+            Some(Found {
+                value,
+                expired_for: Some(age),
+            }) if age < self.stale.revalidate => {
+                record(self.name, CacheStatus::Stale);
+                self.revalidate(key, fetch);
+                return Ok(self.serve(value, true));
+            }
+            Some(Found {
+                value,
+                expired_for: Some(age),
+            }) if age < self.stale.if_error => Some(value),
+            _ => None,
+        };
+
+        match (self.fetch_shared(key.clone(), fetch).await, stale) {
+            (Ok(value), _) => {
+                record(self.name, CacheStatus::Miss);
+                Ok(self.serve(value, false))
+            }
+            (Err(err), Some(stale)) => {
+                warn!(
+                    "Serving {} cache entry {key} from {} after error: {err}",
+                    self.name, stale.fetched_at
+                );
+                record(self.name, CacheStatus::Stale);
+                Ok(self.serve(stale, true))
+            }
+            (Err(err), None) => {
+                record(self.name, CacheStatus::Miss);
+                Err(err)
+            }
+        }
+    }
+
+    fn serve(&self, stored: Stored<V>, stale: bool) -> Served<V> {
+        Served {
+            value: stored.value,
+            stale_as_of: stale.then_some(stored.fetched_at),
+            expires_at: stored.fetched_at + self.memory.ttl,
+        }
+    }
+
+    /// Runs `fetch` in the background, unless a fetch for `key` is already under way.
+    fn revalidate<F, Fut>(self: &Arc<Self>, key: String, fetch: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(V, ProviderKind), ServiceError>> + Send + 'static,
+    {
+        if self.in_flight.is_running(&key) {
+            return;
+        }
+
+        let this = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(err) = this.fetch_shared(key.clone(), fetch).await {
+                warn!("Failed to refresh {} cache entry {key}: {err}", this.name);
+            }
+        });
+    }
+
+    async fn fetch_shared<F, Fut>(&self, key: String, fetch: F) -> Result<Stored<V>, ServiceError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(V, ProviderKind), ServiceError>>,
+    {
+        self.in_flight
+            .run(&key.clone(), || async {
+                let (value, provider) = fetch().await?;
+                // Stored before the flight lands, so later callers find it in the cache.
+                Ok(self.insert(key, value, provider).await)
+            })
+            .await
+    }
+
+    /// Looks in memory, then in the database.
+    async fn lookup(&self, key: &str) -> Option<Found<Stored<V>>> {
+        match self.memory.get(&key.to_string()) {
+            Some(found) => Some(found),
+            None => self.load(key).await,
+        }
+    }
+
+    /// Reads a database entry that is still within its grace, and keeps it in memory
+    /// for the rest of its life.
+    async fn load(&self, key: &str) -> Option<Found<Stored<V>>> {
+        let repository = self.repository.as_ref()?;
+        let now = Utc::now();
+        let cutoff = before(now, self.memory.grace);
+        let entry = match repository.find(self.table, key, cutoff).await {
+            Ok(entry) => entry?,
+            Err(err) => {
+                warn!("Failed to read {} cache entry {key}: {err}", self.name);
+                return None;
+            }
+        };
+
+        let value = match serde_json::from_str::<V>(&entry.payload) {
+            Ok(value) => value,
+            Err(err) => {
+                warn!("Ignoring unreadable {} cache entry {key}: {err}", self.name);
+                return None;
+            }
+        };
+        let stored = Stored {
+            value,
+            fetched_at: entry.fetched_at,
+        };
+        let expired_for = (now - entry.expires_at).to_std().ok();
+        let expires_at = match expired_for {
+            Some(ago) => Instant::now().checked_sub(ago).unwrap_or_else(Instant::now),
+            None => Instant::now() + (entry.expires_at - now).to_std().unwrap_or_default(),
+        };
+        self.memory
+            .insert_expiring(key.to_string(), stored.clone(), expires_at);
+
+        Some(Found {
+            value: stored,
+            expired_for,
+        })
+    }
+
+    async fn insert(&self, key: String, value: V, provider: ProviderKind) -> Stored<V> {
+        let fetched_at = Utc::now();
+        if let Some(repository) = &self.repository {
+            let result = match serde_json::to_string(&value) {
+                Ok(payload) => {
+                    let entry = CacheEntry {
+                        key: key.clone(),
+                        payload,
+                        provider: provider.as_str().to_string(),
+                        fetched_at,
+                        expires_at: fetched_at + self.memory.ttl,
+                    };
+                    repository
+                        .save(self.table, entry)
+                        .await
+                        .map_err(|err| err.to_string())
+                }
+                Err(err) => Err(err.to_string()),
+            };
+            if let Err(err) = result {
+                warn!("Failed to store {} cache entry {key}: {err}", self.name);
+            }
+        }
+
+        let stored = Stored { value, fetched_at };
+        self.memory.insert(key, stored.clone());
+        stored
+    }
+}
+
+/// `ago` before `now`, saturating at the earliest representable time.
+fn before(now: DateTime<Utc>, ago: Duration) -> DateTime<Utc> {
+    TimeDelta::from_std(ago)
+        .ok()
+        .and_then(|ago| now.checked_sub_signed(ago))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+/// The eventual outcome of one call, shared by everyone waiting on it.
+type Flight<V> = Arc<OnceCell<Result<V, ServiceError>>>;
+
+/// Shares one in-flight call among concurrent callers asking for the same key.
+pub struct SingleFlight<V> {
+    flights: Mutex<HashMap<String, Flight<V>>>,
+}
+
+impl<V: Clone> SingleFlight<V> {
+    pub fn new() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs `call` unless one for `key` is already under way, in which case its
+    /// outcome is awaited instead.
+    ///
+    /// Should the caller running `call` give up on it, one of the waiters starts over.
+    pub async fn run<F, Fut>(&self, key: &str, call: F) -> Result<V, ServiceError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, ServiceError>>,
+    {
+        let flight = self
+            .lock()
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(OnceCell::new()))
+            .clone();
+
+        let result = flight.get_or_init(call).await.clone();
+
+        let mut flights = self.lock();
+        if flights
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &flight))
+        {
+            flights.remove(key);
+        }
+        result
+    }
+
+    pub fn is_running(&self, key: &str) -> bool {
+        self.lock().contains_key(key)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Flight<V>>> {
+        self.flights.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Deletes database cache entries every `sweep_interval_secs` once they are past any
+/// use, until the runtime shuts down.
+pub fn spawn_sweeper(repository: CacheRepository, config: &CacheConfig) -> JoinHandle<()> {
+    let interval = config.sweep_interval();
+    let forecast_retention = config.forecast_retention();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticks.tick().await;
+            let now = Utc::now();
+            for (table, cutoff) in [
+                (CacheTable::Geocode, now),
+                (CacheTable::Forecast, before(now, forecast_retention)),
+            ] {
+                match repository.delete_expired(table, cutoff).await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Swept {count} expired {table:?} cache entries"),
+                    Err(err) => warn!("Failed to sweep expired {table:?} cache entries: {err}"),
+                }
+            }
+        }
+    })
+}
+
+/// Serves repeated place searches from the cache; places rarely move, so entries live for days.
+///
+/// Searches are keyed on the name, ignoring case and spacing, plus the country filter
+/// that is forwarded upstream. Only successful searches are kept, so a geocoder outage
+/// is not remembered.
+pub struct CachedGeocoder {
+    inner: Arc<dyn Geocoder>,
+    cache: Arc<Tiered<Vec<Place>>>,
+}
+
+impl CachedGeocoder {
+    pub fn new(inner: Arc<dyn Geocoder>, config: &CacheConfig) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Tiered::new(
+                "geocode",
+                CacheTable::Geocode,
+                config.geocode_capacity(),
+                config.geocode_ttl(),
+                StaleWindows::default(),
+            )),
+        }
+    }
+
+    /// Also keeps entries in the `geocode_cache` table.
+    pub fn with_repository(mut self, repository: CacheRepository) -> Self {
+        Arc::get_mut(&mut self.cache)
+            .expect("cache not yet shared")
+            .repository = Some(repository);
+        self
+    }
+
+    /// E.g. `new york` or `london|CA`.
+    fn key(query: &PlaceQuery) -> String {
+        let name = query
+            .name
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        match &query.country {
+            Some(country) => format!("{name}|{country}"),
+            None => name,
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for CachedGeocoder {
+    async fn fetch_places(&self, query: &PlaceQuery) -> Result<Vec<Place>, ServiceError> {
+        let (inner, query) = (self.inner.clone(), query.clone());
+        let served = self
+            .cache
+            .get_or_fetch(Self::key(&query), || async move {
+                let places = inner.fetch_places(&query).await?;
+                Ok((places, ProviderKind::OpenMeteo))
+            })
+            .await?;
+        Ok(served.value)
+    }
+}
+
+/// Serves repeated forecasts for the same spot from the cache for a configurable time.
+pub struct CachedWeatherProvider {
+    inner: Arc<dyn WeatherProvider>,
+    cache: Arc<Tiered<WeatherData>>,
+}
+
+impl CachedWeatherProvider {
+    pub fn new(inner: Arc<dyn WeatherProvider>, config: &CacheConfig) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Tiered::new(
+                "forecast",
+                CacheTable::Forecast,
+                config.forecast_capacity(),
+                config.forecast_ttl(),
+                StaleWindows {
+                    revalidate: config.forecast_stale_while_revalidate(),
+                    if_error: config.forecast_stale_if_error(),
+                },
+            )),
+        }
+    }
+
+    /// Also keeps entries in the `forecast_cache` table.
+    pub fn with_repository(mut self, repository: CacheRepository) -> Self {
+        Arc::get_mut(&mut self.cache)
+            .expect("cache not yet shared")
+            .repository = Some(repository);
+        self
+    }
+
+    /// Everything that shapes a forecast, with the coordinates as in [`spot`].
+    ///
+    /// E.g. `51.51,-0.13|temperature_2m,precipitation|hourly|auto|metric|7+0`.
+    fn key(request: &ForecastRequest) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}+{}",
+            spot(&request.coords),
+            HourlyVariable::join(&request.variables),
+            if request.daily { "daily" } else { "hourly" },
+            request.timezone_param(),
+            request.units.as_str(),
+            request.window.days,
+            request.window.past_days,
+        )
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for CachedWeatherProvider {
+    async fn fetch_weather(&self, request: &ForecastRequest) -> Result<WeatherData, ServiceError> {
+        let (inner, request) = (self.inner.clone(), request.clone());
+        let served = self
+            .cache
+            .get_or_fetch(Self::key(&request), || async move {
+                let weather = inner.fetch_weather(&request).await?;
+                let provider = weather.provider;
+                Ok((weather, provider))
+            })
+            .await?;
+        Ok(WeatherData {
+            stale_as_of: served.stale_as_of,
+            expires_at: Some(served.expires_at),
+            ..served.value
+        })
+    }
+}
+
+/// Serves repeated air-quality lookups for the same spot from memory, for as long as, and
+/// with the same stale windows as, forecasts.
+pub struct CachedAirQualityProvider {
+    inner: Arc<dyn AirQualityProvider>,
+    cache: Arc<Tiered<AirQuality>>,
+}
+
+impl CachedAirQualityProvider {
+    pub fn new(inner: Arc<dyn AirQualityProvider>, config: &CacheConfig) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(Tiered::new(
+                "air_quality",
+                CacheTable::Forecast,
+                config.forecast_capacity(),
+                config.forecast_ttl(),
+                StaleWindows {
+                    revalidate: config.forecast_stale_while_revalidate(),
+                    if_error: config.forecast_stale_if_error(),
+                },
+            )),
+        }
+    }
+
+    /// What shapes an air-quality lookup: the forecast key without variables or units,
+    /// and with the days capped at the air-quality horizon.
+    ///
+    /// E.g. `51.51,-0.13|auto|7+0`.
+    fn key(request: &ForecastRequest) -> String {
+        format!(
+            "{}|{}|{}+{}",
+            spot(&request.coords),
+            request.timezone_param(),
+            request.window.days.min(MAX_AIR_QUALITY_DAYS),
+            request.window.past_days,
+        )
+    }
+}
+
+#[async_trait]
+impl AirQualityProvider for CachedAirQualityProvider {
+    async fn fetch_air_quality(
+        &self,
+        request: &ForecastRequest,
+    ) -> Result<AirQuality, ServiceError> {
+        let (inner, request) = (self.inner.clone(), request.clone());
+        let served = self
+            .cache
+            .get_or_fetch(Self::key(&request), || async move {
+                let air_quality = inner.fetch_air_quality(&request).await?;
+                Ok((air_quality, ProviderKind::OpenMeteo))
+            })
+            .await?;
+        Ok(served.value)
+    }
+}
+
+/// Coordinates rounded to two decimals (about a kilometre), well within the resolution of
+/// the forecast models, e.g. `51.51,-0.13`.
+fn spot(coords: &LatLong) -> String {
+    // Adding zero turns a rounded -0.0 into 0.0.
+    let round = |degrees: f32| (f64::from(degrees) * 100.0).round() / 100.0 + 0.0;
+
+    format!(
+        "{:.2},{:.2}",
+        round(coords.latitude),
+        round(coords.longitude)
+    )
+}
+
+/// Serves repeated sea-state lookups from memory for as long as forecasts, and remembers
+/// which places are coastal, so that forecasts for inland places only probe the marine API
+/// once rather than on every request. Coastlines hold still, so that memo lives as long as
+/// geocoding results.
+pub struct CachedMarineProvider {
+    inner: Arc<dyn MarineProvider>,
+    cache: TtlCache<String, MarineData>,
+    coastal: TtlCache<String, bool>,
+}
+
+impl CachedMarineProvider {
+    pub fn new(inner: Arc<dyn MarineProvider>, config: &CacheConfig) -> Self {
+        Self {
+            inner,
+            cache: TtlCache::new(config.forecast_capacity(), config.forecast_ttl()),
+            coastal: TtlCache::new(config.geocode_capacity(), config.geocode_ttl()),
+        }
+    }
+
+    /// What shapes a sea-state lookup: the forecast key without variables or resolution.
+    ///
+    /// E.g. `50.83,-0.14|auto|metric|7+0`.
+    fn key(request: &ForecastRequest) -> String {
+        format!(
+            "{}|{}|{}|{}+{}",
+            spot(&request.coords),
+            request.timezone_param(),
+            request.units.as_str(),
+            request.window.days,
+            request.window.past_days,
+        )
+    }
+}
+
+#[async_trait]
+impl MarineProvider for CachedMarineProvider {
+    async fn fetch_marine(&self, request: &ForecastRequest) -> Result<MarineData, ServiceError> {
+        let key = Self::key(request);
+        if let Some(Found {
+            value,
+            expired_for: None,
+        }) = self.cache.get(&key)
+        {
+            return Ok(value);
+        }
+
+        let marine = self.inner.fetch_marine(request).await?;
+        self.coastal
+            .insert(spot(&request.coords), marine.is_coastal(&request.coords));
+        self.cache.insert(key, marine.clone());
+        Ok(marine)
+    }
+
+    fn known_coastal(&self, coords: &LatLong) -> Option<bool> {
+        self.coastal
+            .get(&spot(coords))
+            .filter(|found| found.expired_for.is_none())
+            .map(|found| found.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::forecast::{ForecastWindow, UnitSystem};
+    use crate::services::weather_service::marine_for;
+    use crate::test_support::{setup_test_db, StaticProvider};
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Counts the calls that get past the cache, and fails them when told to.
+    #[derive(Default)]
+    struct Counting {
+        calls: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Geocoder for Counting {
+        async fn fetch_places(&self, query: &PlaceQuery) -> Result<Vec<Place>, ServiceError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            StaticProvider.fetch_places(query).await
+        }
+    }
+
+    #[async_trait]
+    impl WeatherProvider for Counting {
+        async fn fetch_weather(
+            &self,
+            request: &ForecastRequest,
+        ) -> Result<WeatherData, ServiceError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(ServiceError::WeatherError(
+                    "503 Service Unavailable".to_string(),
+                ));
+            }
+            StaticProvider.fetch_weather(request).await
+        }
+    }
+
+    #[async_trait]
+    impl AirQualityProvider for Counting {
+        async fn fetch_air_quality(
+            &self,
+            request: &ForecastRequest,
+        ) -> Result<AirQuality, ServiceError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            StaticProvider.fetch_air_quality(request).await
+        }
+    }
+
+    #[async_trait]
+    impl MarineProvider for Counting {
+        async fn fetch_marine(
+            &self,
+            request: &ForecastRequest,
+        ) -> Result<MarineData, ServiceError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            StaticProvider.fetch_marine(request).await
+        }
+    }
+
+    fn capacity(entries: usize) -> NonZeroUsize {
+        NonZeroUsize::new(entries).unwrap()
+    }
+
+    fn fresh<V: Clone>(cache: &TtlCache<&'static str, V>, key: &'static str) -> Option<V> {
+        cache
+            .get(&key)
+            .filter(|found| found.expired_for.is_none())
+            .map(|found| found.value)
+    }
+
+    fn forecast(latitude: f32, longitude: f32) -> ForecastRequest {
+        ForecastRequest::new(
+            LatLong {
+                latitude,
+                longitude,
+            },
+            vec![HourlyVariable::Temperature2m],
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_entries_expire_after_ttl() {
+        let cache = TtlCache::new(capacity(4), Duration::from_secs(60));
+        cache.insert("london", 1);
+
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert_eq!(fresh(&cache, "london"), Some(1));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cache.get(&"london"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_entries_are_kept_for_grace() {
+        let cache =
+            TtlCache::new(capacity(4), Duration::from_secs(60)).with_grace(Duration::from_secs(30));
+        cache.insert("london", 1);
+
+        tokio::time::advance(Duration::from_secs(80)).await;
+        assert_eq!(
+            cache.get(&"london"),
+            Some(Found {
+                value: 1,
+                expired_for: Some(Duration::from_secs(20)),
+            })
+        );
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(cache.get(&"london"), None);
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let cache = TtlCache::new(capacity(2), Duration::from_secs(60));
+        cache.insert("london", 1);
+        cache.insert("paris", 2);
+        assert_eq!(fresh(&cache, "london"), Some(1));
+
+        cache.insert("berlin", 3);
+
+        assert_eq!(fresh(&cache, "paris"), None);
+        assert_eq!(fresh(&cache, "london"), Some(1));
+        assert_eq!(fresh(&cache, "berlin"), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_geocoder_key_ignores_case() {
+        let inner = Arc::new(Counting::default());
+        let geocoder = CachedGeocoder::new(inner.clone(), &CacheConfig::default());
+
+        let ((), lookups) = track_lookups(async {
+            for name in ["London", "london", "LONDON"] {
+                let query = PlaceQuery::new(name, None, None).unwrap();
+                geocoder.fetch_places(&query).await.unwrap();
+            }
+            let query = PlaceQuery::new("London", Some("CA"), None).unwrap();
+            geocoder.fetch_places(&query).await.unwrap();
+        })
+        .await;
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            lookups,
+            [
+                ("geocode", CacheStatus::Miss),
+                ("geocode", CacheStatus::Hit),
+                ("geocode", CacheStatus::Hit),
+                ("geocode", CacheStatus::Miss),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_searches_are_not_cached() {
+        let inner = Arc::new(Counting::default());
+        let geocoder = CachedGeocoder::new(inner.clone(), &CacheConfig::default());
+        let query = PlaceQuery::new("Atlantis", None, None).unwrap();
+
+        assert!(geocoder.fetch_places(&query).await.is_err());
+        assert!(geocoder.fetch_places(&query).await.is_err());
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_forecasts_are_keyed_on_rounded_coordinates_and_request() {
+        let inner = Arc::new(Counting::default());
+        let provider = CachedWeatherProvider::new(inner.clone(), &CacheConfig::default());
+
+        provider
+            .fetch_weather(&forecast(51.5074, -0.1278))
+            .await
+            .unwrap();
+        provider
+            .fetch_weather(&forecast(51.5071, -0.1281))
+            .await
+            .unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        provider
+            .fetch_weather(&forecast(51.52, -0.1278))
+            .await
+            .unwrap();
+        let mut imperial = forecast(51.5074, -0.1278).with_units(UnitSystem::Imperial);
+        provider.fetch_weather(&imperial).await.unwrap();
+        imperial.variables.push(HourlyVariable::Precipitation);
+        provider.fetch_weather(&imperial).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_forecasts_expire_after_configured_ttl() {
+        let inner = Arc::new(Counting::default());
+        let config = CacheConfig {
+            forecast_ttl_secs: 300,
+            forecast_stale_while_revalidate_secs: 0,
+            forecast_stale_if_error_secs: 0,
+            ..CacheConfig::default()
+        };
+        let provider = CachedWeatherProvider::new(inner.clone(), &config);
+        let request = forecast(35.6762, 139.6503);
+
+        provider.fetch_weather(&request).await.unwrap();
+        tokio::time::advance(Duration::from_secs(299)).await;
+        provider.fetch_weather(&request).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        provider.fetch_weather(&request).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_keys_are_readable() {
+        let query = PlaceQuery::new(" New   York ", None, None).unwrap();
+        assert_eq!(CachedGeocoder::key(&query), "new york");
+        let query = PlaceQuery::new("London", Some("ca"), None).unwrap();
+        assert_eq!(CachedGeocoder::key(&query), "london|CA");
+
+        let request = ForecastRequest::new(
+            LatLong {
+                latitude: 51.5074,
+                longitude: -0.001,
+            },
+            vec![HourlyVariable::Temperature2m, HourlyVariable::Precipitation],
+        )
+        .with_daily()
+        .with_timezone(Some(chrono_tz::Europe::London))
+        .with_units(UnitSystem::Si)
+        .with_window(ForecastWindow {
+            days: 3,
+            past_days: 1,
+        });
+        assert_eq!(
+            CachedWeatherProvider::key(&request),
+            "51.51,0.00|temperature_2m,precipitation|daily|Europe/London|si|3+1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restarted_instance_serves_stored_entries() {
+        let db = setup_test_db().await;
+        let inner = Arc::new(Counting::default());
+        let request = forecast(48.8566, 2.3522);
+        let query = PlaceQuery::new("Paris", None, None).unwrap();
+
+        let geocoder = CachedGeocoder::new(inner.clone(), &CacheConfig::default())
+            .with_repository(CacheRepository::new(db.clone()));
+        let provider = CachedWeatherProvider::new(inner.clone(), &CacheConfig::default())
+            .with_repository(CacheRepository::new(db.clone()));
+        let places = geocoder.fetch_places(&query).await.unwrap();
+        let weather = provider.fetch_weather(&request).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        let geocoder = CachedGeocoder::new(inner.clone(), &CacheConfig::default())
+            .with_repository(CacheRepository::new(db.clone()));
+        let provider = CachedWeatherProvider::new(inner.clone(), &CacheConfig::default())
+            .with_repository(CacheRepository::new(db.clone()));
+        let ((cached_places, cached_weather), lookups) = track_lookups(async {
+            (
+                geocoder.fetch_places(&query).await.unwrap(),
+                provider.fetch_weather(&request).await.unwrap(),
+            )
+        })
+        .await;
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            lookups,
+            [
+                ("geocode", CacheStatus::Hit),
+                ("forecast", CacheStatus::Hit)
+            ]
+        );
+        assert_eq!(cached_places, places);
+        assert_eq!(cached_weather.timezone, weather.timezone);
+        assert_eq!(cached_weather.provider, weather.provider);
+        assert_eq!(cached_weather.hourly.time, weather.hourly.time);
+        assert_eq!(
+            cached_weather.hourly.temperature_2m,
+            weather.hourly.temperature_2m
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweeper_deletes_expired_entries() {
+        let repository = CacheRepository::new(setup_test_db().await);
+        let fetched_at = Utc::now() - chrono::Duration::minutes(10);
+        for (key, expires_at) in [
+            ("london", fetched_at + chrono::Duration::minutes(5)),
+            ("paris", fetched_at + chrono::Duration::minutes(60)),
+        ] {
+            let entry = CacheEntry {
+                key: key.to_string(),
+                payload: "[]".to_string(),
+                provider: "open_meteo".to_string(),
+                fetched_at,
+                expires_at,
+            };
+            repository.save(CacheTable::Geocode, entry).await.unwrap();
+        }
+
+        // Expired forecasts are kept while they may still be served.
+        for (key, expired_for) in [
+            ("51.51,-0.13", chrono::Duration::minutes(5)),
+            ("48.85,2.35", chrono::Duration::days(2)),
+        ] {
+            let entry = CacheEntry {
+                key: key.to_string(),
+                payload: "{}".to_string(),
+                provider: "open_meteo".to_string(),
+                fetched_at,
+                expires_at: Utc::now() - expired_for,
+            };
+            repository.save(CacheTable::Forecast, entry).await.unwrap();
+        }
+
+        let sweeper = spawn_sweeper(repository.clone(), &CacheConfig::default());
+        // The first sweep runs straight away.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        sweeper.abort();
+
+        assert_eq!(
+            repository
+                .delete_expired(CacheTable::Geocode, fetched_at)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            repository
+                .delete_expired(
+                    CacheTable::Geocode,
+                    fetched_at + chrono::Duration::minutes(60)
+                )
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            repository
+                .delete_expired(CacheTable::Forecast, Utc::now())
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_forecast_is_served_while_revalidating() {
+        let inner = Arc::new(Counting::default());
+        let config = CacheConfig {
+            forecast_ttl_secs: 300,
+            forecast_stale_while_revalidate_secs: 60,
+            ..CacheConfig::default()
+        };
+        let provider = CachedWeatherProvider::new(inner.clone(), &config);
+        let request = forecast(52.52, 13.405);
+        provider.fetch_weather(&request).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(330)).await;
+        let (weather, lookups) = track_lookups(provider.fetch_weather(&request)).await;
+        assert!(weather.unwrap().stale_as_of.is_some());
+        assert_eq!(lookups, [("forecast", CacheStatus::Stale)]);
+
+        // The refresh runs in the background.
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        let (weather, lookups) = track_lookups(provider.fetch_weather(&request)).await;
+        assert_eq!(weather.unwrap().stale_as_of, None);
+        assert_eq!(lookups, [("forecast", CacheStatus::Hit)]);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_forecast_is_served_when_upstream_fails() {
+        let inner = Arc::new(Counting::default());
+        let config = CacheConfig {
+            forecast_ttl_secs: 300,
+            forecast_stale_while_revalidate_secs: 0,
+            forecast_stale_if_error_secs: 3600,
+            ..CacheConfig::default()
+        };
+        let provider = CachedWeatherProvider::new(inner.clone(), &config);
+        let request = forecast(40.4168, -3.7038);
+        provider.fetch_weather(&request).await.unwrap();
+        inner.failing.store(true, Ordering::SeqCst);
+
+        tokio::time::advance(Duration::from_secs(600)).await;
+        let (weather, lookups) = track_lookups(provider.fetch_weather(&request)).await;
+        assert!(weather.unwrap().stale_as_of.is_some());
+        assert_eq!(lookups, [("forecast", CacheStatus::Stale)]);
+
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        let (weather, lookups) = track_lookups(provider.fetch_weather(&request)).await;
+        assert!(matches!(weather, Err(ServiceError::WeatherError(_))));
+        assert_eq!(lookups, [("forecast", CacheStatus::Miss)]);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stored_forecast_is_served_stale_after_restart() {
+        let repository = CacheRepository::new(setup_test_db().await);
+        let request = forecast(41.9028, 12.4964);
+        let weather = StaticProvider.fetch_weather(&request).await.unwrap();
+        let fetched_at = Utc::now() - chrono::Duration::hours(2);
+        let entry = CacheEntry {
+            key: CachedWeatherProvider::key(&request),
+            payload: serde_json::to_string(&weather).unwrap(),
+            provider: "open_meteo".to_string(),
+            fetched_at,
+            expires_at: fetched_at + chrono::Duration::minutes(10),
+        };
+        repository.save(CacheTable::Forecast, entry).await.unwrap();
+
+        let inner = Arc::new(Counting::default());
+        inner.failing.store(true, Ordering::SeqCst);
+        let provider = CachedWeatherProvider::new(inner.clone(), &CacheConfig::default())
+            .with_repository(repository);
+        let stale = provider.fetch_weather(&request).await.unwrap();
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            stale.stale_as_of.map(|time| time.timestamp()),
+            Some(fetched_at.timestamp())
+        );
+        assert_eq!(stale.hourly.temperature_2m, weather.hourly.temperature_2m);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_callers_share_one_call_and_its_error() {
+        let flights = SingleFlight::<u32>::new();
+        let calls = AtomicUsize::new(0);
+        let call = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Err(ServiceError::WeatherError(
+                "503 Service Unavailable".to_string(),
+            ))
+        };
+
+        let results = tokio::join!(
+            flights.run("london", call),
+            flights.run("london", call),
+            flights.run("london", call),
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        for result in [results.0, results.1, results.2] {
+            assert!(
+                matches!(&result, Err(ServiceError::WeatherError(message)) if message == "503 Service Unavailable"),
+                "unexpected result: {result:?}"
+            );
+        }
+
+        // Once landed, a flight is not reused.
+        assert_eq!(flights.run("london", || async { Ok(7) }).await.unwrap(), 7);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waiter_takes_over_when_caller_gives_up() {
+        let flights = SingleFlight::<u32>::new();
+        let calls = AtomicUsize::new(0);
+        let call = || async {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(u32::try_from(call).unwrap())
+        };
+
+        let (abandoned, waiter) = tokio::join!(
+            tokio::time::timeout(Duration::from_millis(500), flights.run("paris", call)),
+            flights.run("paris", call),
+        );
+
+        assert!(abandoned.is_err());
+        assert_eq!(waiter.unwrap(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_inland_places_probe_the_sea_once() {
+        let inner = Arc::new(Counting::default());
+        let marine = CachedMarineProvider::new(inner.clone(), &CacheConfig::default());
+        let berlin = forecast(52.52, 13.41);
+        let brighton = forecast(50.83, -0.14);
+
+        for _ in 0..3 {
+            assert!(marine_for(&marine, &berlin, false).await.unwrap().is_none());
+            assert!(marine_for(&marine, &brighton, false)
+                .await
+                .unwrap()
+                .is_some());
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(marine.known_coastal(&berlin.coords), Some(false));
+
+        // Asking for marine data still returns it.
+        assert!(marine_for(&marine, &berlin, true).await.unwrap().is_some());
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sea_state_is_cached_like_forecasts() {
+        let inner = Arc::new(Counting::default());
+        let config = CacheConfig::default();
+        let marine = CachedMarineProvider::new(inner.clone(), &config);
+        let brighton = forecast(50.83, -0.14);
+
+        marine.fetch_marine(&brighton).await.unwrap();
+        marine.fetch_marine(&brighton).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        let mut imperial = brighton.clone();
+        imperial.units = UnitSystem::Imperial;
+        marine.fetch_marine(&imperial).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        tokio::time::advance(config.forecast_ttl()).await;
+        marine.fetch_marine(&brighton).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_air_quality_is_keyed_on_spot_and_window() {
+        let inner = Arc::new(Counting::default());
+        let air_quality = CachedAirQualityProvider::new(inner.clone(), &CacheConfig::default());
+        let london = forecast(51.5074, -0.1278);
+
+        let first = air_quality.fetch_air_quality(&london).await.unwrap();
+        // Variables and units do not shape air quality, nor do days past its horizon.
+        let mut same = forecast(51.5071, -0.1281);
+        same.variables = vec![HourlyVariable::Precipitation];
+        same.units = UnitSystem::Imperial;
+        same.window.days = 14;
+        assert_eq!(air_quality.fetch_air_quality(&same).await.unwrap(), first);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        let mut longer_past = london.clone();
+        longer_past.window.past_days = 2;
+        air_quality.fetch_air_quality(&longer_past).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::services::forecast::HourlyVariable;
+use crate::services::weather_service::WeatherData;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// One local calendar day of the forecast.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DaySummary {
+    /// Calendar date in the forecast's time zone.
+    pub date: NaiveDate,
+    pub temperature_min: Option<f64>,
+    pub temperature_max: Option<f64>,
+    pub temperature_mean: Option<f64>,
+    pub precipitation_sum: Option<f64>,
+    /// Only known when the provider reports it.
+    pub sunrise: Option<DateTime<FixedOffset>>,
+    pub sunset: Option<DateTime<FixedOffset>>,
+}
+
+/// Per-day summaries, preferring the provider's own aggregates.
+///
+/// Days or values the provider did not aggregate are computed from the hourly series,
+/// grouped by local calendar day in the forecast's time zone.
+pub fn summarize(weather: &WeatherData) -> Vec<DaySummary> {
+    let from_hourly = aggregate_hourly(weather);
+    let Some(daily) = &weather.daily else {
+        return from_hourly;
+    };
+
+    daily
+        .time
+        .iter()
+        .enumerate()
+        .map(|(day, &start)| {
+            let date = weather.local(start).date_naive();
+            let fallback = from_hourly
+                .iter()
+                .find(|summary| summary.date == date)
+                .cloned()
+                .unwrap_or_default();
+            let value = |series: Option<&[Option<f64>]>| series?.get(day).copied().flatten();
+            let time = |series: Option<&[Option<DateTime<Utc>>]>| {
+                let instant = series?.get(day).copied().flatten()?;
+                Some(weather.local(instant).fixed_offset())
+            };
+
+            DaySummary {
+                date,
+                temperature_min: value(daily.temperature_2m_min.as_deref())
+                    .or(fallback.temperature_min),
+                temperature_max: value(daily.temperature_2m_max.as_deref())
+                    .or(fallback.temperature_max),
+                temperature_mean: value(daily.temperature_2m_mean.as_deref())
+                    .or(fallback.temperature_mean),
+                precipitation_sum: value(daily.precipitation_sum.as_deref())
+                    .or(fallback.precipitation_sum),
+                sunrise: time(daily.sunrise.as_deref()),
+                sunset: time(daily.sunset.as_deref()),
+            }
+        })
+        .collect()
+}
+
+/// Running totals for one day of hourly values.
+struct DayAccumulator {
+    date: NaiveDate,
+    min: Option<f64>,
+    max: Option<f64>,
+    temperature_total: f64,
+    temperature_count: f64,
+    precipitation: Option<f64>,
+}
+
+impl DayAccumulator {
+    fn new(date: NaiveDate, has_precipitation: bool) -> Self {
+        Self {
+            date,
+            min: None,
+            max: None,
+            temperature_total: 0.0,
+            temperature_count: 0.0,
+            precipitation: has_precipitation.then_some(0.0),
+        }
+    }
+
+    fn finish(self) -> DaySummary {
+        let mean = (self.temperature_count > 0.0)
+            .then(|| round_tenth(self.temperature_total / self.temperature_count));
+
+        DaySummary {
+            date: self.date,
+            temperature_min: self.min,
+            temperature_max: self.max,
+            temperature_mean: mean,
+            precipitation_sum: self.precipitation.map(round_tenth),
+            sunrise: None,
+            sunset: None,
+        }
+    }
+}
+
+fn aggregate_hourly(weather: &WeatherData) -> Vec<DaySummary> {
+    let hourly = &weather.hourly;
+    let has_precipitation = hourly.series(HourlyVariable::Precipitation).is_some();
+    let mut days: Vec<DayAccumulator> = Vec::new();
+
+    for (hour, &time) in hourly.time.iter().enumerate() {
+        let date = weather.local(time).date_naive();
+        if days.last().is_none_or(|day| day.date != date) {
+            days.push(DayAccumulator::new(date, has_precipitation));
+        }
+        let Some(day) = days.last_mut() else {
+            continue;
+        };
+
+        if let Some(t) = hourly.value(HourlyVariable::Temperature2m, hour) {
+            day.min = Some(day.min.map_or(t, |min| min.min(t)));
+            day.max = Some(day.max.map_or(t, |max| max.max(t)));
+            day.temperature_total += t;
+            day.temperature_count += 1.0;
+        }
+        if let (Some(total), Some(amount)) = (
+            day.precipitation.as_mut(),
+            hourly.value(HourlyVariable::Precipitation, hour),
+        ) {
+            *total += amount;
+        }
+    }
+
+    days.into_iter().map(DayAccumulator::finish).collect()
+}
+
+fn round_tenth(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::weather_service::DailyData;
+    use crate::services::ProviderKind;
+    use chrono_tz::Tz;
+    use pretty_assertions::assert_eq;
+
+    fn weather(timezone: Tz, hourly: serde_json::Value) -> WeatherData {
+        WeatherData {
+            timezone,
+            hourly: serde_json::from_value(hourly).unwrap(),
+            daily: None,
+            provider: ProviderKind::OpenMeteo,
+            updated_at: None,
+            stale_as_of: None,
+            expires_at: None,
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_hourly_is_grouped_by_local_date() {
+        // 2024-10-26T22:00Z onwards: still the 26th in UTC, already the 27th in Tokyo.
+        let hourly = serde_json::json!({
+            "time": [1_729_980_000, 1_729_983_600, 1_729_987_200, 1_729_990_800],
+            "temperature_2m": [10.0, 9.0, null, 6.5],
+            "precipitation": [0.2, 0.15, 1.0, null],
+        });
+
+        let days = summarize(&weather(Tz::UTC, hourly.clone()));
+        assert_eq!(
+            days,
+            [
+                DaySummary {
+                    date: date(2024, 10, 26),
+                    temperature_min: Some(9.0),
+                    temperature_max: Some(10.0),
+                    temperature_mean: Some(9.5),
+                    precipitation_sum: Some(0.4),
+                    ..DaySummary::default()
+                },
+                DaySummary {
+                    date: date(2024, 10, 27),
+                    temperature_min: Some(6.5),
+                    temperature_max: Some(6.5),
+                    temperature_mean: Some(6.5),
+                    precipitation_sum: Some(1.0),
+                    ..DaySummary::default()
+                },
+            ]
+        );
+
+        let days = summarize(&weather(Tz::Asia__Tokyo, hourly));
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].date, date(2024, 10, 27));
+        assert_eq!(days[0].temperature_min, Some(6.5));
+    }
+
+    #[test]
+    fn test_missing_series_stay_empty() {
+        let days = summarize(&weather(
+            Tz::UTC,
+            serde_json::json!({
+                "time": [1_729_900_800],
+                "cloud_cover": [50.0],
+            }),
+        ));
+
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].temperature_mean, None);
+        assert_eq!(days[0].precipitation_sum, None);
+    }
+
+    #[test]
+    fn test_provider_aggregates_take_precedence() {
+        let mut weather = weather(
+            Tz::Europe__London,
+            serde_json::json!({
+                "time": [1_729_897_200, 1_729_900_800],
+                "temperature_2m": [8.0, 10.0],
+            }),
+        );
+        let start = DateTime::from_timestamp(1_729_897_200, 0).unwrap();
+        let sunrise = DateTime::from_timestamp(1_729_925_280, 0).unwrap();
+        weather.daily = Some(DailyData {
+            time: vec![start],
+            temperature_2m_max: Some(vec![Some(14.6)]),
+            temperature_2m_min: Some(vec![None]),
+            sunrise: Some(vec![Some(sunrise)]),
+            ..DailyData::default()
+        });
+
+        let days = summarize(&weather);
+
+        assert_eq!(days[0].date, date(2024, 10, 26));
+        assert_eq!(days[0].temperature_max, Some(14.6));
+        assert_eq!(days[0].temperature_min, Some(8.0));
+        assert_eq!(days[0].temperature_mean, Some(9.0));
+        assert_eq!(
+            days[0].sunrise.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2024-10-26T07:48:00+01:00")
+        );
+        assert_eq!(days[0].sunset, None);
+    }
+}
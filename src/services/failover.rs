@@ -0,0 +1,226 @@
+use crate::services::forecast::ForecastRequest;
+use crate::services::provider::{ProviderKind, WeatherProvider};
+use crate::services::weather_service::{ServiceError, WeatherData};
+use async_trait::async_trait;
+use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Serves forecasts from the first provider, in priority order, that answers in time.
+///
+/// A provider that errors or exceeds `timeout` hands over to the next one; the data
+/// records which provider it came from. Errors in the request itself are returned at
+/// once, since every provider would reject it the same way.
+pub struct FailoverProvider {
+    providers: Vec<(ProviderKind, Arc<dyn WeatherProvider>)>,
+    timeout: Duration,
+}
+
+impl FailoverProvider {
+    pub fn new(
+        providers: Vec<(ProviderKind, Arc<dyn WeatherProvider>)>,
+        timeout: Duration,
+    ) -> Self {
+        Self { providers, timeout }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for FailoverProvider {
+    async fn fetch_weather(&self, request: &ForecastRequest) -> Result<WeatherData, ServiceError> {
+        let mut last_error = None;
+        for (kind, provider) in &self.providers {
+            let err =
+                match tokio::time::timeout(self.timeout, provider.fetch_weather(request)).await {
+                    Ok(Ok(weather)) => return Ok(weather),
+                    Ok(Err(err @ ServiceError::InvalidInput(_))) => return Err(err),
+                    Ok(Err(err)) => err,
+                    Err(_) => ServiceError::UpstreamTimeout(format!(
+                        "{} did not answer within {}s",
+                        kind.label(),
+                        self.timeout.as_secs_f64()
+                    )),
+                };
+            warn!("Weather provider {} failed: {err}", kind.as_str());
+            last_error = Some(err);
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            ServiceError::WeatherError("No weather providers configured".to_string())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UpstreamConfig;
+    use crate::services::forecast::HourlyVariable;
+    use crate::services::weather_service::LatLong;
+    use crate::services::weather_service::WeatherService;
+    use crate::test_support::{MockOpenMeteo, StaticProvider, FORECAST_PATH};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts its calls, then fails or stalls instead of forecasting.
+    struct Broken {
+        calls: AtomicUsize,
+        stall: bool,
+        error: fn() -> ServiceError,
+    }
+
+    impl Broken {
+        fn failing(error: fn() -> ServiceError) -> Arc<Self> {
+            Arc::new(Self {
+                calls: AtomicUsize::new(0),
+                stall: false,
+                error,
+            })
+        }
+
+        fn stalling() -> Arc<Self> {
+            Arc::new(Self {
+                calls: AtomicUsize::new(0),
+                stall: true,
+                error: || ServiceError::WeatherError("unreachable".to_string()),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl WeatherProvider for Broken {
+        async fn fetch_weather(&self, _: &ForecastRequest) -> Result<WeatherData, ServiceError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.stall {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }
+            Err((self.error)())
+        }
+    }
+
+    fn request() -> ForecastRequest {
+        ForecastRequest::new(
+            LatLong {
+                latitude: 51.507_4,
+                longitude: -0.127_8,
+            },
+            vec![HourlyVariable::Temperature2m],
+        )
+    }
+
+    fn unavailable() -> ServiceError {
+        ServiceError::WeatherError("503 Service Unavailable".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_primary_serves_when_healthy() {
+        let secondary = Broken::failing(unavailable);
+        let failover = FailoverProvider::new(
+            vec![
+                (ProviderKind::OpenMeteo, Arc::new(StaticProvider)),
+                (ProviderKind::MetNorway, secondary.clone()),
+            ],
+            Duration::from_secs(1),
+        );
+
+        let weather = failover.fetch_weather(&request()).await.unwrap();
+
+        assert_eq!(weather.provider, ProviderKind::OpenMeteo);
+        assert_eq!(secondary.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_on_error() {
+        let primary = Broken::failing(unavailable);
+        let failover = FailoverProvider::new(
+            vec![
+                (ProviderKind::OpenMeteo, primary.clone()),
+                (ProviderKind::MetNorway, Arc::new(StaticProvider)),
+            ],
+            Duration::from_secs(1),
+        );
+
+        assert!(failover.fetch_weather(&request()).await.is_ok());
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fails_over_on_timeout() {
+        let primary = Broken::stalling();
+        let failover = FailoverProvider::new(
+            vec![
+                (ProviderKind::OpenMeteo, primary.clone()),
+                (ProviderKind::MetNorway, Arc::new(StaticProvider)),
+            ],
+            Duration::from_secs(5),
+        );
+
+        assert!(failover.fetch_weather(&request()).await.is_ok());
+        assert_eq!(primary.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reports_the_last_error_when_all_fail() {
+        let failover = FailoverProvider::new(
+            vec![
+                (ProviderKind::OpenMeteo, Broken::failing(unavailable)),
+                (ProviderKind::MetNorway, Broken::stalling()),
+            ],
+            Duration::from_secs(5),
+        );
+
+        let result = failover.fetch_weather(&request()).await;
+
+        assert!(
+            matches!(&result, Err(ServiceError::UpstreamTimeout(message)) if message == "MET Norway did not answer within 5s"),
+            "unexpected result: {result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input_is_not_retried() {
+        let secondary = Broken::failing(unavailable);
+        let failover = FailoverProvider::new(
+            vec![
+                (
+                    ProviderKind::OpenMeteo,
+                    Broken::failing(|| ServiceError::InvalidInput("bad latitude".to_string())),
+                ),
+                (ProviderKind::MetNorway, secondary.clone()),
+            ],
+            Duration::from_secs(1),
+        );
+
+        assert!(matches!(
+            failover.fetch_weather(&request()).await,
+            Err(ServiceError::InvalidInput(_))
+        ));
+        assert_eq!(secondary.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_retries_a_hung_attempt_within_the_provider_timeout() {
+        let mock = MockOpenMeteo::start().await;
+        mock.delay_next_forecast(Duration::from_secs(5)).await;
+        let upstream = UpstreamConfig {
+            request_timeout_ms: 200,
+            max_retries: 1,
+            retry_base_delay_ms: 1,
+            retry_max_delay_ms: 10,
+            ..UpstreamConfig::default()
+        };
+        let timeout = Duration::from_secs(1);
+        assert!(upstream.longest_call() <= timeout);
+        let failover = FailoverProvider::new(
+            vec![(
+                ProviderKind::OpenMeteo,
+                Arc::new(WeatherService::new(&mock.config(), &upstream)),
+            )],
+            timeout,
+        );
+
+        let weather = failover.fetch_weather(&request()).await.unwrap();
+
+        assert_eq!(weather.provider, ProviderKind::OpenMeteo);
+        assert_eq!(mock.requests_to(FORECAST_PATH).await, 2);
+    }
+}
@@ -0,0 +1,452 @@
+use crate::services::weather_service::{LatLong, ServiceError};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// Hourly series that can be requested from the forecast provider.
+///
+/// The serialized names are Open-Meteo's `hourly=` variable names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HourlyVariable {
+    #[serde(rename = "temperature_2m")]
+    Temperature2m,
+    #[serde(rename = "relative_humidity_2m")]
+    RelativeHumidity2m,
+    Precipitation,
+    PrecipitationProbability,
+    #[serde(rename = "wind_speed_10m")]
+    WindSpeed10m,
+    #[serde(rename = "wind_direction_10m")]
+    WindDirection10m,
+    #[serde(rename = "wind_gusts_10m")]
+    WindGusts10m,
+    CloudCover,
+    WeatherCode,
+}
+
+impl HourlyVariable {
+    pub const ALL: [Self; 9] = [
+        Self::Temperature2m,
+        Self::RelativeHumidity2m,
+        Self::Precipitation,
+        Self::PrecipitationProbability,
+        Self::WindSpeed10m,
+        Self::WindDirection10m,
+        Self::WindGusts10m,
+        Self::CloudCover,
+        Self::WeatherCode,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Temperature2m => "temperature_2m",
+            Self::RelativeHumidity2m => "relative_humidity_2m",
+            Self::Precipitation => "precipitation",
+            Self::PrecipitationProbability => "precipitation_probability",
+            Self::WindSpeed10m => "wind_speed_10m",
+            Self::WindDirection10m => "wind_direction_10m",
+            Self::WindGusts10m => "wind_gusts_10m",
+            Self::CloudCover => "cloud_cover",
+            Self::WeatherCode => "weather_code",
+        }
+    }
+
+    /// Column heading used on the HTML page.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Temperature2m => "Temperature",
+            Self::RelativeHumidity2m => "Humidity",
+            Self::Precipitation => "Precipitation",
+            Self::PrecipitationProbability => "Chance of Rain",
+            Self::WindSpeed10m => "Wind Speed",
+            Self::WindDirection10m => "Wind Direction",
+            Self::WindGusts10m => "Wind Gusts",
+            Self::CloudCover => "Cloud Cover",
+            Self::WeatherCode => "Conditions",
+        }
+    }
+
+    /// Unit of the series in `units`; empty for the WMO weather code.
+    pub fn unit(self, units: UnitSystem) -> &'static str {
+        match self {
+            Self::Temperature2m => units.temperature(),
+            Self::RelativeHumidity2m | Self::PrecipitationProbability | Self::CloudCover => "%",
+            Self::Precipitation => units.precipitation(),
+            Self::WindSpeed10m | Self::WindGusts10m => units.wind_speed(),
+            Self::WindDirection10m => "°",
+            Self::WeatherCode => "",
+        }
+    }
+
+    /// Renders one value of this series for display, e.g. `12.5°C`, `3 km/h` or `Light rain`.
+    pub fn format(self, value: f64, units: UnitSystem) -> String {
+        match self.unit(units) {
+            "" => describe_weather_code(value),
+            unit if unit.starts_with(char::is_alphabetic) => format!("{value} {unit}"),
+            unit => format!("{value}{unit}"),
+        }
+    }
+
+    /// Parses a comma-separated `variables=` list, keeping the given order and dropping repeats.
+    pub fn parse_list(raw: &str) -> Result<Vec<Self>, ServiceError> {
+        let mut variables = Vec::new();
+        for name in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let variable = name.parse()?;
+            if !variables.contains(&variable) {
+                variables.push(variable);
+            }
+        }
+
+        if variables.is_empty() {
+            return Err(ServiceError::InvalidInput(
+                "At least one forecast variable must be given".to_string(),
+            ));
+        }
+        Ok(variables)
+    }
+
+    /// Comma-separated names as Open-Meteo expects them in `hourly=`.
+    pub fn join(variables: &[Self]) -> String {
+        variables
+            .iter()
+            .map(|variable| variable.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl FromStr for HourlyVariable {
+    type Err = ServiceError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|variable| variable.as_str() == name)
+            .ok_or_else(|| {
+                let known: Vec<_> = Self::ALL.iter().map(|v| v.as_str()).collect();
+                ServiceError::InvalidInput(format!(
+                    "Unknown forecast variable '{name}'; expected one of {}",
+                    known.join(", ")
+                ))
+            })
+    }
+}
+
+/// Measurement system for temperatures, wind speeds and precipitation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// °C, km/h and mm: Open-Meteo's defaults.
+    #[default]
+    Metric,
+    /// °F, mph and inches.
+    Imperial,
+    /// K, m/s and mm.
+    Si,
+}
+
+impl UnitSystem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Metric => "metric",
+            Self::Imperial => "imperial",
+            Self::Si => "si",
+        }
+    }
+
+    pub fn temperature(self) -> &'static str {
+        match self {
+            Self::Metric => "°C",
+            Self::Imperial => "°F",
+            Self::Si => "K",
+        }
+    }
+
+    pub fn wind_speed(self) -> &'static str {
+        match self {
+            Self::Metric => "km/h",
+            Self::Imperial => "mph",
+            Self::Si => "m/s",
+        }
+    }
+
+    pub fn precipitation(self) -> &'static str {
+        match self {
+            Self::Metric | Self::Si => "mm",
+            Self::Imperial => "inch",
+        }
+    }
+
+    /// Unit of marine wave heights.
+    pub fn wave_height(self) -> &'static str {
+        match self {
+            Self::Metric | Self::Si => "m",
+            Self::Imperial => "ft",
+        }
+    }
+
+    /// Converts a wave height in metres into this system, to two decimals.
+    pub fn convert_wave_height(self, metres: f64) -> f64 {
+        match self {
+            Self::Metric | Self::Si => metres,
+            Self::Imperial => (metres / 0.3048 * 100.0).round() / 100.0,
+        }
+    }
+
+    /// Open-Meteo's `temperature_unit`, `wind_speed_unit` and `precipitation_unit`.
+    ///
+    /// Open-Meteo has no kelvin, so SI is fetched as metric and converted with
+    /// [`UnitSystem::convert_metric`].
+    pub fn open_meteo_params(self) -> [(&'static str, &'static str); 3] {
+        match self {
+            Self::Metric | Self::Si => [
+                ("temperature_unit", "celsius"),
+                ("wind_speed_unit", "kmh"),
+                ("precipitation_unit", "mm"),
+            ],
+            Self::Imperial => [
+                ("temperature_unit", "fahrenheit"),
+                ("wind_speed_unit", "mph"),
+                ("precipitation_unit", "inch"),
+            ],
+        }
+    }
+
+    /// Whether values fetched with [`UnitSystem::open_meteo_params`] still need converting.
+    pub fn converts_locally(self) -> bool {
+        self == Self::Si
+    }
+
+    /// Converts a metric value of `variable` into this system, to two decimals.
+    pub fn convert_metric(self, variable: HourlyVariable, value: f64) -> f64 {
+        let converted = match (self, variable) {
+            (Self::Imperial, HourlyVariable::Temperature2m) => value * 9.0 / 5.0 + 32.0,
+            (Self::Si, HourlyVariable::Temperature2m) => value + 273.15,
+            (Self::Imperial, HourlyVariable::WindSpeed10m | HourlyVariable::WindGusts10m) => {
+                value / 1.609_344
+            }
+            (Self::Si, HourlyVariable::WindSpeed10m | HourlyVariable::WindGusts10m) => value / 3.6,
+            (Self::Imperial, HourlyVariable::Precipitation) => value / 25.4,
+            _ => return value,
+        };
+        (converted * 100.0).round() / 100.0
+    }
+}
+
+impl FromStr for UnitSystem {
+    type Err = ServiceError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "metric" => Ok(Self::Metric),
+            "imperial" => Ok(Self::Imperial),
+            "si" => Ok(Self::Si),
+            _ => Err(ServiceError::InvalidInput(format!(
+                "Unknown units {name:?}; expected metric, imperial or si"
+            ))),
+        }
+    }
+}
+
+/// Span of days a forecast covers: `past_days` before today plus `days` from today on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub struct ForecastWindow {
+    pub days: u32,
+    pub past_days: u32,
+}
+
+impl ForecastWindow {
+    /// Open-Meteo's own default horizon.
+    pub const DEFAULT_DAYS: u32 = 7;
+    pub const MAX_DAYS: u32 = 16;
+    pub const MAX_PAST_DAYS: u32 = 92;
+
+    /// Number of calendar days in the window, counting today once.
+    pub fn total_days(self) -> usize {
+        (self.past_days + self.days) as usize
+    }
+}
+
+impl Default for ForecastWindow {
+    fn default() -> Self {
+        Self {
+            days: Self::DEFAULT_DAYS,
+            past_days: 0,
+        }
+    }
+}
+
+/// What to fetch from a [`WeatherProvider`](crate::services::WeatherProvider).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForecastRequest {
+    pub coords: LatLong,
+    pub variables: Vec<HourlyVariable>,
+    /// Also ask for per-day aggregates.
+    pub daily: bool,
+    /// Zone whose calendar days the forecast follows; `None` lets the provider pick
+    /// the location's own zone.
+    pub timezone: Option<Tz>,
+    pub units: UnitSystem,
+    pub window: ForecastWindow,
+}
+
+impl ForecastRequest {
+    pub fn new(coords: LatLong, variables: Vec<HourlyVariable>) -> Self {
+        Self {
+            coords,
+            variables,
+            daily: false,
+            timezone: None,
+            units: UnitSystem::default(),
+            window: ForecastWindow::default(),
+        }
+    }
+
+    pub fn with_window(mut self, window: ForecastWindow) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_units(mut self, units: UnitSystem) -> Self {
+        self.units = units;
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: Option<Tz>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Open-Meteo's `timezone=` value.
+    pub fn timezone_param(&self) -> String {
+        self.timezone
+            .map_or_else(|| "auto".to_string(), |tz| tz.name().to_string())
+    }
+
+    pub fn with_daily(mut self) -> Self {
+        self.daily = true;
+        self
+    }
+
+    /// Comma-separated variable names as Open-Meteo expects them in `hourly=`.
+    pub fn hourly_param(&self) -> String {
+        HourlyVariable::join(&self.variables)
+    }
+}
+
+/// Short description of a WMO weather interpretation code.
+// Codes arrive as JSON numbers in the same series type as every other variable.
+#[allow(clippy::cast_possible_truncation)]
+fn describe_weather_code(code: f64) -> String {
+    let description = match code as i64 {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 => "Light rain",
+        63 => "Rain",
+        65 => "Heavy rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 | 77 => "Snow",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => return format!("Code {code}"),
+    };
+    description.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test]
+    fn test_parse_list_keeps_order_and_drops_repeats() {
+        let variables =
+            HourlyVariable::parse_list("precipitation, temperature_2m,precipitation,").unwrap();
+
+        assert_eq!(
+            variables,
+            [HourlyVariable::Precipitation, HourlyVariable::Temperature2m]
+        );
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case(" , " ; "only separators")]
+    #[test_case("temperature_2m,snowfall" ; "unknown variable")]
+    #[test_case("Temperature_2m" ; "wrong case")]
+    fn test_parse_list_rejects(raw: &str) {
+        assert!(matches!(
+            HourlyVariable::parse_list(raw),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_names_match_serde() {
+        for variable in HourlyVariable::ALL {
+            assert_eq!(
+                serde_json::to_value(variable).unwrap(),
+                variable.as_str(),
+                "{variable:?}"
+            );
+        }
+    }
+
+    #[test_case(HourlyVariable::Temperature2m, 12.5, UnitSystem::Metric, "12.5°C")]
+    #[test_case(HourlyVariable::Temperature2m, 54.5, UnitSystem::Imperial, "54.5°F")]
+    #[test_case(HourlyVariable::Temperature2m, 285.65, UnitSystem::Si, "285.65 K")]
+    #[test_case(HourlyVariable::WindSpeed10m, 3.0, UnitSystem::Metric, "3 km/h")]
+    #[test_case(HourlyVariable::WindSpeed10m, 3.0, UnitSystem::Si, "3 m/s")]
+    #[test_case(HourlyVariable::Precipitation, 0.05, UnitSystem::Imperial, "0.05 inch")]
+    #[test_case(HourlyVariable::CloudCover, 40.0, UnitSystem::Imperial, "40%")]
+    #[test_case(HourlyVariable::WeatherCode, 61.0, UnitSystem::Metric, "Light rain")]
+    #[test_case(HourlyVariable::WeatherCode, 42.0, UnitSystem::Metric, "Code 42")]
+    fn test_format(variable: HourlyVariable, value: f64, units: UnitSystem, expected: &str) {
+        assert_eq!(variable.format(value, units), expected);
+    }
+
+    #[test_case(HourlyVariable::Temperature2m, 20.0, UnitSystem::Imperial, 68.0)]
+    #[test_case(HourlyVariable::Temperature2m, -5.5, UnitSystem::Si, 267.65)]
+    #[test_case(HourlyVariable::WindSpeed10m, 36.0, UnitSystem::Si, 10.0)]
+    #[test_case(HourlyVariable::WindGusts10m, 100.0, UnitSystem::Imperial, 62.14)]
+    #[test_case(HourlyVariable::Precipitation, 12.7, UnitSystem::Imperial, 0.5)]
+    #[test_case(HourlyVariable::Precipitation, 1.2, UnitSystem::Si, 1.2)]
+    #[test_case(HourlyVariable::RelativeHumidity2m, 71.0, UnitSystem::Imperial, 71.0)]
+    #[test_case(HourlyVariable::Temperature2m, 7.4, UnitSystem::Metric, 7.4)]
+    fn test_convert_metric(variable: HourlyVariable, value: f64, units: UnitSystem, expected: f64) {
+        assert!((units.convert_metric(variable, value) - expected).abs() < 1e-9);
+    }
+
+    #[test_case(UnitSystem::Metric, 1.5, 1.5 ; "in metres")]
+    #[test_case(UnitSystem::Si, 1.5, 1.5 ; "in si")]
+    #[test_case(UnitSystem::Imperial, 1.5, 4.92 ; "in feet")]
+    fn test_convert_wave_height(units: UnitSystem, metres: f64, expected: f64) {
+        assert!((units.convert_wave_height(metres) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!(
+            "Imperial".parse::<UnitSystem>().unwrap(),
+            UnitSystem::Imperial
+        );
+        assert_eq!(" si".parse::<UnitSystem>().unwrap(), UnitSystem::Si);
+        assert!(matches!(
+            "kelvin".parse::<UnitSystem>(),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,469 @@
+use crate::config::{MetNorwayConfig, UpstreamConfig};
+use crate::services::forecast::{ForecastRequest, HourlyVariable, UnitSystem};
+use crate::services::provider::{ProviderKind, WeatherProvider};
+use crate::services::upstream::{CircuitBreaker, Upstream};
+use crate::services::weather_service::{HourlyData, ServiceError, WeatherData};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use log::{debug, error, info};
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// MET Norway reports wind in m/s; forecasts are assembled in km/h like Open-Meteo's.
+const MS_TO_KMH: f64 = 3.6;
+
+/// Forecasts from MET Norway's locationforecast 2.0 API, in its `compact` format.
+///
+/// The API only forecasts ahead, hourly for the first two to three days and every six
+/// hours after that, and always in UTC; the requested zone is applied locally. Only the
+/// hourly steps are kept, so forecasts end sooner than Open-Meteo's, and requests for
+/// past days fail over to the next provider. It has no daily aggregates, so day
+/// summaries come from the hourly series.
+pub struct MetNorwayService {
+    forecast: Upstream,
+}
+
+/// The parts of a locationforecast response the forecast is built from.
+#[derive(Debug, Deserialize)]
+struct LocationForecast {
+    properties: Properties,
+}
+
+#[derive(Debug, Deserialize)]
+struct Properties {
+    meta: Meta,
+    timeseries: Vec<TimeStep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    /// When the forecast was last updated from a model run.
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeStep {
+    time: DateTime<Utc>,
+    data: StepData,
+}
+
+#[derive(Debug, Deserialize)]
+struct StepData {
+    instant: Instant,
+    next_1_hours: Option<Period>,
+    next_6_hours: Option<Period>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Instant {
+    details: InstantDetails,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct InstantDetails {
+    air_temperature: Option<f64>,
+    relative_humidity: Option<f64>,
+    wind_speed: Option<f64>,
+    wind_from_direction: Option<f64>,
+    wind_speed_of_gust: Option<f64>,
+    cloud_area_fraction: Option<f64>,
+}
+
+/// Summary of the hours following a step.
+#[derive(Debug, Deserialize)]
+struct Period {
+    summary: Option<Summary>,
+    #[serde(default)]
+    details: PeriodDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct Summary {
+    symbol_code: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PeriodDetails {
+    precipitation_amount: Option<f64>,
+    probability_of_precipitation: Option<f64>,
+}
+
+impl TimeStep {
+    /// Metric value of `variable` at this step; precipitation only where the step covers
+    /// a single hour.
+    fn value(&self, variable: HourlyVariable) -> Option<f64> {
+        let instant = &self.data.instant.details;
+        let next_hour = self
+            .data
+            .next_1_hours
+            .as_ref()
+            .map(|period| &period.details);
+        match variable {
+            HourlyVariable::Temperature2m => instant.air_temperature,
+            HourlyVariable::RelativeHumidity2m => instant.relative_humidity,
+            HourlyVariable::Precipitation => next_hour?.precipitation_amount,
+            HourlyVariable::PrecipitationProbability => next_hour?.probability_of_precipitation,
+            HourlyVariable::WindSpeed10m => instant.wind_speed.map(|speed| speed * MS_TO_KMH),
+            HourlyVariable::WindDirection10m => instant.wind_from_direction,
+            HourlyVariable::WindGusts10m => instant.wind_speed_of_gust.map(|gust| gust * MS_TO_KMH),
+            HourlyVariable::CloudCover => instant.cloud_area_fraction,
+            HourlyVariable::WeatherCode => {
+                let period = self
+                    .data
+                    .next_1_hours
+                    .as_ref()
+                    .or(self.data.next_6_hours.as_ref());
+                wmo_code(&period?.summary.as_ref()?.symbol_code)
+            }
+        }
+    }
+}
+
+/// Translates a MET Norway symbol code such as `lightrainshowers_day` into the WMO
+/// weather code Open-Meteo would report.
+///
+/// WMO's mixed rain-and-snow codes are outside Open-Meteo's set, so sleet reads as snow.
+fn wmo_code(symbol: &str) -> Option<f64> {
+    let symbol = symbol.split('_').next().unwrap_or(symbol);
+    if symbol.contains("thunder") {
+        return Some(95.0);
+    }
+    let (intensity, kind) = if let Some(kind) = symbol.strip_prefix("light") {
+        (0, kind)
+    } else if let Some(kind) = symbol.strip_prefix("heavy") {
+        (2, kind)
+    } else {
+        (1, symbol)
+    };
+    let code = match (kind, intensity) {
+        ("clearsky", _) => 0,
+        ("fair", _) => 1,
+        ("partlycloudy", _) => 2,
+        ("cloudy", _) => 3,
+        ("fog", _) => 45,
+        ("rain", 0) => 61,
+        ("rain", 1) => 63,
+        ("rain", _) => 65,
+        ("rainshowers", 0) => 80,
+        ("rainshowers", 1) => 81,
+        ("rainshowers", _) => 82,
+        ("snow" | "sleet", 0) => 71,
+        ("snow" | "sleet", 1) => 73,
+        ("snow" | "sleet", _) => 75,
+        ("snowshowers" | "sleetshowers", 0 | 1) => 85,
+        ("snowshowers" | "sleetshowers", _) => 86,
+        _ => return None,
+    };
+    Some(f64::from(code))
+}
+
+impl MetNorwayService {
+    pub fn new(
+        config: &MetNorwayConfig,
+        upstream: &UpstreamConfig,
+    ) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .user_agent(config.user_agent.clone())
+            .build()?;
+
+        Ok(Self {
+            forecast: Upstream::new(
+                "met_norway_forecast",
+                &config.forecast_url,
+                client,
+                upstream,
+            ),
+        })
+    }
+
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        self.forecast.breaker()
+    }
+}
+
+/// The zone a forecast for `request` follows: the requested one, or else the nautical zone
+/// of its longitude, since MET Norway does not say which zone a location is in.
+fn zone(request: &ForecastRequest) -> Tz {
+    request.timezone.unwrap_or_else(|| {
+        // Etc/GMT names count hours west of Greenwich, so their signs are reversed.
+        let hours_west = (-request.coords.longitude / 15.0).round() + 0.0;
+        format!("Etc/GMT{hours_west:+}")
+            .parse()
+            .unwrap_or(Tz::Etc__GMT)
+    })
+}
+
+impl LocationForecast {
+    /// The hourly steps as rows of the forecast, for `request.window.days` days in the
+    /// request's zone and units.
+    fn into_weather(self, request: &ForecastRequest) -> WeatherData {
+        // The six-hourly tail would read as a gap in hourly tables and sums.
+        let steps: Vec<TimeStep> = self
+            .properties
+            .timeseries
+            .into_iter()
+            .filter(|step| step.data.next_1_hours.is_some())
+            .collect();
+        let series = |variable: HourlyVariable| -> Option<Vec<Option<f64>>> {
+            request
+                .variables
+                .contains(&variable)
+                .then(|| steps.iter().map(|step| step.value(variable)).collect())
+        };
+
+        let hourly = HourlyData {
+            temperature_2m: series(HourlyVariable::Temperature2m),
+            relative_humidity_2m: series(HourlyVariable::RelativeHumidity2m),
+            precipitation: series(HourlyVariable::Precipitation),
+            precipitation_probability: series(HourlyVariable::PrecipitationProbability),
+            wind_speed_10m: series(HourlyVariable::WindSpeed10m),
+            wind_direction_10m: series(HourlyVariable::WindDirection10m),
+            wind_gusts_10m: series(HourlyVariable::WindGusts10m),
+            cloud_cover: series(HourlyVariable::CloudCover),
+            weather_code: series(HourlyVariable::WeatherCode),
+            time: steps.iter().map(|step| step.time).collect(),
+        };
+
+        let mut weather = WeatherData {
+            timezone: zone(request),
+            hourly,
+            daily: None,
+            provider: ProviderKind::MetNorway,
+            updated_at: Some(self.properties.meta.updated_at),
+            stale_as_of: None,
+            expires_at: None,
+        };
+        if request.units != UnitSystem::Metric {
+            weather.convert_metric(request.units);
+        }
+        weather.keep_days(request.window.days as usize);
+        weather
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for MetNorwayService {
+    async fn fetch_weather(&self, forecast: &ForecastRequest) -> Result<WeatherData, ServiceError> {
+        let coords = &forecast.coords;
+        debug!(
+            "Fetching MET Norway forecast for coordinates: lat={}, lon={}",
+            coords.latitude, coords.longitude
+        );
+        if forecast.window.past_days > 0 {
+            return Err(ServiceError::WeatherError(format!(
+                "MET Norway has no data for past days, {} requested",
+                forecast.window.past_days
+            )));
+        }
+
+        // MET Norway asks for at most four decimals so responses can be cached upstream.
+        let params = [
+            ("lat", format!("{:.4}", coords.latitude)),
+            ("lon", format!("{:.4}", coords.longitude)),
+        ];
+        let response = self.forecast.get(&params).await.map_err(|e| {
+            error!("MET Norway request failed: {e}");
+            ServiceError::from_upstream(&e, ServiceError::WeatherError)
+        })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = format!("MET Norway returned {status}: {}", body.trim());
+            error!("{message}");
+            return Err(ServiceError::from_upstream_status(
+                status,
+                message,
+                ServiceError::WeatherError,
+            ));
+        }
+
+        let body: LocationForecast = response.json().await.map_err(|e| {
+            error!("Failed to parse MET Norway response: {e}");
+            ServiceError::WeatherError(e.to_string())
+        })?;
+
+        info!("Successfully fetched MET Norway forecast");
+        Ok(body.into_weather(forecast))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::forecast::ForecastWindow;
+    use crate::services::weather_service::LatLong;
+    use crate::test_support::{fast_upstream, MockMetNorway, MET_NORWAY_PATH};
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    fn request(units: UnitSystem) -> ForecastRequest {
+        ForecastRequest::new(
+            LatLong {
+                latitude: 51.507_4,
+                longitude: -0.127_8,
+            },
+            vec![
+                HourlyVariable::Temperature2m,
+                HourlyVariable::Precipitation,
+                HourlyVariable::WindSpeed10m,
+                HourlyVariable::WeatherCode,
+            ],
+        )
+        .with_timezone(Some(Tz::Europe__London))
+        .with_units(units)
+        .with_window(ForecastWindow {
+            days: 2,
+            past_days: 0,
+        })
+    }
+
+    #[test_case("clearsky_day", Some(0.0) ; "when clear")]
+    #[test_case("partlycloudy_night", Some(2.0) ; "when partly cloudy")]
+    #[test_case("cloudy", Some(3.0) ; "when cloudy")]
+    #[test_case("lightrain", Some(61.0) ; "with light rain")]
+    #[test_case("heavyrainshowers_polartwilight", Some(82.0) ; "with heavy showers")]
+    #[test_case("sleet", Some(73.0) ; "with sleet")]
+    #[test_case("rainandthunder", Some(95.0) ; "with thunder")]
+    #[test_case("volcanicash", None ; "when unknown")]
+    fn test_wmo_code(symbol: &str, expected: Option<f64>) {
+        assert_eq!(wmo_code(symbol), expected);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather() {
+        let mock = MockMetNorway::start().await;
+        let service = MetNorwayService::new(&mock.config(), &fast_upstream()).unwrap();
+
+        let weather = service
+            .fetch_weather(&request(UnitSystem::Metric))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.last_query_param("lat").await.as_deref(),
+            Some("51.5074")
+        );
+        assert_eq!(
+            mock.last_user_agent().await.as_deref(),
+            Some("forecast-rust-tests")
+        );
+        assert_eq!(weather.provider, ProviderKind::MetNorway);
+        assert_eq!(
+            weather.updated_at.map(|time| time.to_rfc3339()).as_deref(),
+            Some("2024-10-25T23:12:08+00:00")
+        );
+        assert_eq!(weather.timezone, Tz::Europe__London);
+        // The six-hourly tail is left out.
+        assert_eq!(weather.hourly.time.len(), 24);
+        assert_eq!(
+            weather.hourly.time[0].to_rfc3339(),
+            "2024-10-26T00:00:00+00:00"
+        );
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::Temperature2m, 0),
+            Some(9.8)
+        );
+        // 3 m/s becomes 10.8 km/h.
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::WindSpeed10m, 0),
+            Some(10.8)
+        );
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::Precipitation, 14),
+            Some(0.6)
+        );
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::WeatherCode, 14),
+            Some(61.0)
+        );
+        assert!(weather.hourly.relative_humidity_2m.is_none());
+        assert!(weather.daily.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_keeps_to_window() {
+        let mock = MockMetNorway::start().await;
+        let service = MetNorwayService::new(&mock.config(), &fast_upstream()).unwrap();
+
+        let one_day = request(UnitSystem::Metric).with_window(ForecastWindow {
+            days: 1,
+            past_days: 0,
+        });
+        let weather = service.fetch_weather(&one_day).await.unwrap();
+        // 2024-10-26 in London ends at 23:00 UTC, still in summer time.
+        assert_eq!(weather.hourly.time.len(), 23);
+        assert_eq!(
+            weather
+                .hourly
+                .time
+                .last()
+                .map(DateTime::to_rfc3339)
+                .as_deref(),
+            Some("2024-10-26T22:00:00+00:00")
+        );
+
+        let with_history = request(UnitSystem::Metric).with_window(ForecastWindow {
+            days: 1,
+            past_days: 2,
+        });
+        let result = service.fetch_weather(&with_history).await;
+        assert!(
+            matches!(&result, Err(ServiceError::WeatherError(message)) if message.contains("past days")),
+            "unexpected result: {result:?}"
+        );
+    }
+
+    #[test_case(-0.13, Tz::Etc__GMTPlus0 ; "near Greenwich")]
+    #[test_case(139.69, Tz::Etc__GMTMinus9 ; "in the east")]
+    #[test_case(-74.0, Tz::Etc__GMTPlus5 ; "in the west")]
+    fn test_zone_without_one_requested(longitude: f32, expected: Tz) {
+        let request = ForecastRequest::new(
+            LatLong {
+                latitude: 0.0,
+                longitude,
+            },
+            vec![HourlyVariable::Temperature2m],
+        );
+
+        assert_eq!(zone(&request), expected);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_imperial() {
+        let mock = MockMetNorway::start().await;
+        let service = MetNorwayService::new(&mock.config(), &fast_upstream()).unwrap();
+
+        let weather = service
+            .fetch_weather(&request(UnitSystem::Imperial))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::Temperature2m, 0),
+            Some(49.64)
+        );
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::WindSpeed10m, 0),
+            Some(6.71)
+        );
+    }
+
+    #[test_case(503 ; "when unavailable")]
+    #[test_case(403 ; "when the user agent is refused")]
+    #[tokio::test]
+    async fn test_fetch_weather_error(status: u16) {
+        let mock = MockMetNorway::start().await;
+        mock.fail_with(MET_NORWAY_PATH, status).await;
+        let service = MetNorwayService::new(&mock.config(), &fast_upstream()).unwrap();
+
+        let result = service.fetch_weather(&request(UnitSystem::Metric)).await;
+
+        assert!(
+            matches!(&result, Err(ServiceError::WeatherError(message)) if message.contains(&status.to_string())),
+            "unexpected result: {result:?}"
+        );
+    }
+}
@@ -1 +1,17 @@
+pub mod air_quality;
+pub mod cache;
+pub mod daily;
+pub mod failover;
+pub mod forecast;
+pub mod met_norway;
+pub mod places;
+pub mod provider;
+pub mod reverse_geocoder;
+pub mod upstream;
+pub mod validation;
 pub mod weather_service;
+
+pub use provider::{
+    AirQualityProvider, Geocoder, HistoryProvider, MarineProvider, ProviderKind, ReverseGeocoder,
+    WeatherProvider,
+};
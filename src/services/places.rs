@@ -0,0 +1,373 @@
+use crate::services::provider::{Geocoder, ReverseGeocoder};
+use crate::services::validation::{
+    normalize_city_name, normalize_country_code, normalize_region_name, validate_coordinates,
+};
+use crate::services::weather_service::{LatLong, Place, ServiceError};
+use log::debug;
+
+/// A same-named place wins outright when it is at least this many times more populous
+/// than the runner-up; otherwise the caller is asked to pick.
+const DOMINANCE_RATIO: u64 = 10;
+
+/// Coordinates are labelled with the nearest known place only when it is this close.
+const LABEL_RADIUS_KM: f64 = 25.0;
+
+/// A validated place search: the name plus optional filters pinning the choice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceQuery {
+    pub name: String,
+    /// ISO 3166-1 alpha-2 code, upper-cased.
+    pub country: Option<String>,
+    pub admin1: Option<String>,
+}
+
+impl PlaceQuery {
+    pub fn new(
+        name: &str,
+        country: Option<&str>,
+        admin1: Option<&str>,
+    ) -> Result<Self, ServiceError> {
+        Ok(Self {
+            name: normalize_city_name(name)?,
+            country: non_empty(country).map(normalize_country_code).transpose()?,
+            admin1: non_empty(admin1).map(normalize_region_name).transpose()?,
+        })
+    }
+
+    fn matches(&self, place: &Place) -> bool {
+        let country_matches = self.country.as_deref().is_none_or(|country| {
+            place
+                .country_code
+                .as_deref()
+                .is_some_and(|code| code.eq_ignore_ascii_case(country))
+        });
+        let admin1_matches = self.admin1.as_deref().is_none_or(|admin1| {
+            place
+                .admin1
+                .as_deref()
+                .is_some_and(|name| name.to_lowercase() == admin1.to_lowercase())
+        });
+
+        country_matches && admin1_matches
+    }
+}
+
+/// What the caller asked for: a named place to geocode, or coordinates to use as-is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocationQuery {
+    Place(PlaceQuery),
+    /// Coordinates, with the name, country and region to show for them when the caller
+    /// already knows them, as after picking one of several candidates.
+    Coordinates(LatLong, Option<PlaceQuery>),
+}
+
+impl LocationQuery {
+    /// Builds a query from request parameters; exactly one of `city` or `lat`+`lon` is allowed.
+    ///
+    /// `name` labels `lat`+`lon`, and `country` and `admin1` then describe it too.
+    pub fn from_params(
+        city: Option<&str>,
+        lat: Option<f32>,
+        lon: Option<f32>,
+        name: Option<&str>,
+        country: Option<&str>,
+        admin1: Option<&str>,
+    ) -> Result<Self, ServiceError> {
+        let name = non_empty(name);
+        match (non_empty(city), lat, lon) {
+            (Some(city), None, None) if name.is_none() => {
+                Ok(Self::Place(PlaceQuery::new(city, country, admin1)?))
+            }
+            (None, Some(lat), Some(lon)) => Ok(Self::Coordinates(
+                validate_coordinates(lat, lon)?,
+                name.map(|name| PlaceQuery::new(name, country, admin1))
+                    .transpose()?,
+            )),
+            (None, None, None) => Err(ServiceError::InvalidInput(
+                "Either city or lat and lon must be given".to_string(),
+            )),
+            (None, _, _) => Err(ServiceError::InvalidInput(
+                "lat and lon must be given together".to_string(),
+            )),
+            (Some(_), None, None) => Err(ServiceError::InvalidInput(
+                "name labels lat and lon; search by city alone".to_string(),
+            )),
+            (Some(_), _, _) => Err(ServiceError::InvalidInput(
+                "Give either city or lat and lon, not both".to_string(),
+            )),
+        }
+    }
+}
+
+/// Outcome of resolving a query to a single place.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    Unique(Place),
+    /// Several plausible places; listed most populous first.
+    Ambiguous(Vec<Place>),
+}
+
+/// Resolves a location query, geocoding only when a place name was given.
+pub async fn resolve_location(
+    geocoder: &dyn Geocoder,
+    reverse_geocoder: &dyn ReverseGeocoder,
+    query: &LocationQuery,
+) -> Result<Resolution, ServiceError> {
+    match query {
+        LocationQuery::Place(place_query) => resolve(geocoder, place_query).await,
+        LocationQuery::Coordinates(coords, None) => Ok(Resolution::Unique(
+            label_coordinates(reverse_geocoder, coords).await,
+        )),
+        LocationQuery::Coordinates(coords, Some(label)) => {
+            let mut place = Place::from_coords(coords);
+            place.name.clone_from(&label.name);
+            place.country_code.clone_from(&label.country);
+            place.admin1.clone_from(&label.admin1);
+            Ok(Resolution::Unique(place))
+        }
+    }
+}
+
+/// Names `coords` after the nearest known place, keeping the exact coordinates.
+///
+/// Falls back to the coordinates themselves when nothing is close enough or the lookup fails.
+async fn label_coordinates(reverse_geocoder: &dyn ReverseGeocoder, coords: &LatLong) -> Place {
+    let mut place = Place::from_coords(coords);
+
+    match reverse_geocoder.reverse_geocode(coords).await {
+        Ok(nearby) if nearby.distance_km <= LABEL_RADIUS_KM => {
+            place.name = nearby.place.name;
+            place.country_code = nearby.place.country_code;
+            place.country = nearby.place.country;
+            place.admin1 = nearby.place.admin1;
+            place.timezone = nearby.place.timezone;
+        }
+        Ok(nearby) => debug!("Nearest place to {coords:?} is too far away: {nearby:?}"),
+        Err(err) => debug!("No display name for {coords:?}: {err}"),
+    }
+
+    place
+}
+
+/// Looks `query` up and decides whether it names one place or needs disambiguation.
+pub async fn resolve(
+    geocoder: &dyn Geocoder,
+    query: &PlaceQuery,
+) -> Result<Resolution, ServiceError> {
+    let candidates: Vec<Place> = geocoder
+        .fetch_places(query)
+        .await?
+        .into_iter()
+        .filter(|place| query.matches(place))
+        .collect();
+
+    if candidates.is_empty() {
+        return Err(ServiceError::CityNotFound(format!(
+            "No places named {} match the given country/region",
+            query.name
+        )));
+    }
+
+    let resolution = choose(&query.name, candidates);
+    debug!("Resolved {query:?} to {resolution:?}");
+    Ok(resolution)
+}
+
+fn choose(name: &str, candidates: Vec<Place>) -> Resolution {
+    let name = name.to_lowercase();
+    let (mut contenders, others): (Vec<Place>, Vec<Place>) = candidates
+        .into_iter()
+        .partition(|place| place.name.to_lowercase() == name);
+
+    // A fuzzy match ("Londn") has no exact-name contenders; trust the geocoder's ranking.
+    if contenders.is_empty() {
+        contenders = others;
+        contenders.truncate(1);
+    }
+
+    if contenders.len() == 1 {
+        return Resolution::Unique(contenders.remove(0));
+    }
+
+    contenders.sort_by_key(|place| std::cmp::Reverse(place.population));
+    let first = contenders[0].population.unwrap_or(0);
+    let second = contenders[1].population.unwrap_or(0);
+
+    if first > 0 && first >= second.saturating_mul(DOMINANCE_RATIO) {
+        Resolution::Unique(contenders.remove(0))
+    } else {
+        Resolution::Ambiguous(contenders)
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.filter(|v| !v.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::StaticProvider;
+    use pretty_assertions::assert_eq;
+
+    fn query(name: &str, country: Option<&str>, admin1: Option<&str>) -> PlaceQuery {
+        PlaceQuery::new(name, country, admin1).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dominant_place_is_unique() {
+        let resolution = resolve(&StaticProvider, &query("Paris", None, None))
+            .await
+            .unwrap();
+
+        match resolution {
+            Resolution::Unique(place) => assert_eq!(place.country_code.as_deref(), Some("FR")),
+            other @ Resolution::Ambiguous(_) => panic!("Expected a unique place, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_comparable_places_are_ambiguous() {
+        let resolution = resolve(&StaticProvider, &query("Springfield", None, None))
+            .await
+            .unwrap();
+
+        match resolution {
+            Resolution::Ambiguous(places) => {
+                let regions: Vec<_> = places.iter().filter_map(|p| p.admin1.as_deref()).collect();
+                assert_eq!(regions, ["Missouri", "Massachusetts", "Illinois"]);
+            }
+            other @ Resolution::Unique(_) => panic!("Expected ambiguous places, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filters_pin_the_choice() {
+        let resolution = resolve(
+            &StaticProvider,
+            &query("Springfield", Some("us"), Some("illinois")),
+        )
+        .await
+        .unwrap();
+        match resolution {
+            Resolution::Unique(place) => assert_eq!(place.admin1.as_deref(), Some("Illinois")),
+            other @ Resolution::Ambiguous(_) => panic!("Expected a unique place, got {other:?}"),
+        }
+
+        let resolution = resolve(&StaticProvider, &query("Paris", Some("US"), None))
+            .await
+            .unwrap();
+        match resolution {
+            Resolution::Unique(place) => assert_eq!(place.admin1.as_deref(), Some("Texas")),
+            other @ Resolution::Ambiguous(_) => panic!("Expected a unique place, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_without_match_is_not_found() {
+        let result = resolve(&StaticProvider, &query("Paris", Some("DE"), None)).await;
+
+        assert!(matches!(result, Err(ServiceError::CityNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_coordinates_skip_geocoding() {
+        let query =
+            LocationQuery::from_params(None, Some(51.5), Some(-0.12), None, None, None).unwrap();
+        let resolution = resolve_location(&StaticProvider, &StaticProvider, &query)
+            .await
+            .unwrap();
+
+        match resolution {
+            Resolution::Unique(place) => {
+                assert_eq!(place.name, "London");
+                assert_eq!(place.country_code.as_deref(), Some("GB"));
+                assert!((place.latitude - 51.5).abs() < f32::EPSILON);
+            }
+            other @ Resolution::Ambiguous(_) => {
+                panic!("Expected a unique place, got {other:?}")
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_coordinates_keep_numeric_label() {
+        let query =
+            LocationQuery::from_params(None, Some(0.0), Some(-30.0), None, None, None).unwrap();
+        let resolution = resolve_location(&StaticProvider, &StaticProvider, &query)
+            .await
+            .unwrap();
+
+        match resolution {
+            Resolution::Unique(place) => {
+                assert_eq!(place.name, "0.0000, -30.0000");
+                assert_eq!(place.country_code, None);
+            }
+            other @ Resolution::Ambiguous(_) => {
+                panic!("Expected a unique place, got {other:?}")
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_named_coordinates_keep_their_label() {
+        let query = LocationQuery::from_params(
+            None,
+            Some(39.801_72),
+            Some(-89.643_71),
+            Some("Springfield"),
+            Some("us"),
+            Some("Illinois"),
+        )
+        .unwrap();
+        let resolution = resolve_location(&StaticProvider, &StaticProvider, &query)
+            .await
+            .unwrap();
+
+        match resolution {
+            Resolution::Unique(place) => {
+                assert_eq!(place.name, "Springfield");
+                assert_eq!(place.country_code.as_deref(), Some("US"));
+                assert_eq!(place.admin1.as_deref(), Some("Illinois"));
+                assert!((place.latitude - 39.801_72).abs() < f32::EPSILON);
+            }
+            other @ Resolution::Ambiguous(_) => {
+                panic!("Expected a unique place, got {other:?}")
+            }
+        }
+    }
+
+    #[test]
+    fn test_location_params_must_be_city_or_coordinates() {
+        let invalid = [
+            (None, None, None, None),
+            (None, Some(51.5), None, None),
+            (Some("London"), Some(51.5), Some(-0.12), None),
+            (None, Some(91.0), Some(0.0), None),
+            (Some("London"), None, None, Some("London")),
+            (None, None, None, Some("London")),
+        ];
+
+        for (city, lat, lon, name) in invalid {
+            assert!(
+                matches!(
+                    LocationQuery::from_params(city, lat, lon, name, None, None),
+                    Err(ServiceError::InvalidInput(_))
+                ),
+                "expected {city:?}/{lat:?}/{lon:?}/{name:?} to be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_filters_are_rejected() {
+        assert!(matches!(
+            PlaceQuery::new("Paris", Some("France"), None),
+            Err(ServiceError::InvalidInput(_))
+        ));
+        assert_eq!(
+            PlaceQuery::new("Paris", Some(""), Some(" ")).unwrap(),
+            query("Paris", None, None)
+        );
+    }
+}
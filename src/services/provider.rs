@@ -0,0 +1,107 @@
+use crate::services::air_quality::AirQuality;
+use crate::services::forecast::{ForecastRequest, HourlyVariable};
+use crate::services::places::PlaceQuery;
+use crate::services::reverse_geocoder::NearbyPlace;
+use crate::services::weather_service::{LatLong, MarineData, Place, ServiceError, WeatherData};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// Upstream forecast services the app can draw on, named as in the configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// Also what every deserialized Open-Meteo response reports.
+    #[default]
+    OpenMeteo,
+    MetNorway,
+}
+
+impl ProviderKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::OpenMeteo => "open_meteo",
+            Self::MetNorway => "met_norway",
+        }
+    }
+
+    /// Attribution shown on the page.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::OpenMeteo => "Open-Meteo",
+            Self::MetNorway => "MET Norway",
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open_meteo" => Ok(Self::OpenMeteo),
+            "met_norway" => Ok(Self::MetNorway),
+            other => Err(format!(
+                "unknown provider {other:?}; expected open_meteo or met_norway"
+            )),
+        }
+    }
+}
+
+/// Resolves a free-form place name to candidate places.
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    /// Returns the places matching `query`, best match first, or `CityNotFound` when there are none.
+    async fn fetch_places(&self, query: &PlaceQuery) -> Result<Vec<Place>, ServiceError>;
+}
+
+/// Fetches the requested hourly series for a pair of coordinates.
+#[async_trait]
+pub trait WeatherProvider: Send + Sync {
+    async fn fetch_weather(&self, request: &ForecastRequest) -> Result<WeatherData, ServiceError>;
+}
+
+/// Fetches pollutant levels and UV index for the place and window of a forecast.
+#[async_trait]
+pub trait AirQualityProvider: Send + Sync {
+    /// Fails with `AirQualityError`, which callers treat as non-fatal.
+    async fn fetch_air_quality(
+        &self,
+        request: &ForecastRequest,
+    ) -> Result<AirQuality, ServiceError>;
+}
+
+/// Fetches sea state near the place of a forecast, over its window and in its units.
+#[async_trait]
+pub trait MarineProvider: Send + Sync {
+    /// Fails with `MarineError`, which callers treat as non-fatal.
+    async fn fetch_marine(&self, request: &ForecastRequest) -> Result<MarineData, ServiceError>;
+
+    /// Whether `coords` is already known to be coastal, without asking upstream; `None`
+    /// until it is.
+    fn known_coastal(&self, _coords: &LatLong) -> Option<bool> {
+        None
+    }
+}
+
+/// Fetches recorded hourly series for a past date range.
+#[async_trait]
+pub trait HistoryProvider: Send + Sync {
+    /// Returns metric values for every hour from `start` to `end` inclusive, in the
+    /// location's own time zone.
+    async fn fetch_historical(
+        &self,
+        coords: &LatLong,
+        start: NaiveDate,
+        end: NaiveDate,
+        variables: &[HourlyVariable],
+    ) -> Result<WeatherData, ServiceError>;
+}
+
+/// Finds the named place nearest to a pair of coordinates.
+#[async_trait]
+pub trait ReverseGeocoder: Send + Sync {
+    async fn reverse_geocode(&self, coords: &LatLong) -> Result<NearbyPlace, ServiceError>;
+}
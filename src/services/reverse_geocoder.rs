@@ -0,0 +1,203 @@
+use crate::repositories::{CityRepository, GazetteerRepository};
+use crate::services::provider::ReverseGeocoder;
+use crate::services::weather_service::{LatLong, Place, ServiceError};
+use async_trait::async_trait;
+use log::debug;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Half-widths, in degrees, of the boxes searched around the target, smallest first.
+const SEARCH_DELTAS: [f32; 3] = [0.25, 1.0, 5.0];
+
+/// Kilometres per degree of latitude.
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// The named place nearest to some coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct NearbyPlace {
+    pub place: Place,
+    pub distance_km: f64,
+}
+
+/// Reverse geocoder that never leaves the process: it searches the imported geonames.org
+/// gazetteer and the coordinates of past searches in the `cities` table.
+pub struct OfflineReverseGeocoder {
+    cities: CityRepository,
+    gazetteer: GazetteerRepository,
+}
+
+impl OfflineReverseGeocoder {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            cities: CityRepository::new(db.clone()),
+            gazetteer: GazetteerRepository::new(db),
+        }
+    }
+
+    async fn candidates(&self, coords: &LatLong, delta: f32) -> Result<Vec<Place>, ServiceError> {
+        let to_service_error =
+            |e| ServiceError::GeocodingError(format!("Gazetteer lookup failed: {e}"));
+
+        let gazetteer = self
+            .gazetteer
+            .find_within(coords, delta)
+            .await
+            .map_err(to_service_error)?
+            .into_iter()
+            .map(|entry| Place {
+                name: entry.name,
+                latitude: entry.lat,
+                longitude: entry.long,
+                country_code: entry.country_code,
+                country: None,
+                admin1: entry.admin1,
+                timezone: entry.timezone,
+                population: entry.population.and_then(|p| u64::try_from(p).ok()),
+                elevation: None,
+            });
+
+        // Searches made by coordinates are labelled with those coordinates, not a name.
+        let history = self
+            .cities
+            .find_within(coords, delta)
+            .await
+            .map_err(to_service_error)?
+            .into_iter()
+            .map(|city| {
+                let mut place = Place::from_coords(&LatLong {
+                    latitude: city.lat,
+                    longitude: city.long,
+                });
+                let is_named = city.name != place.name;
+                place.name = city.name;
+                (is_named, place)
+            })
+            .filter_map(|(is_named, place)| is_named.then_some(place));
+
+        Ok(gazetteer.chain(history).collect())
+    }
+}
+
+#[async_trait]
+impl ReverseGeocoder for OfflineReverseGeocoder {
+    async fn reverse_geocode(&self, coords: &LatLong) -> Result<NearbyPlace, ServiceError> {
+        let mut best: Option<NearbyPlace> = None;
+
+        for delta in SEARCH_DELTAS {
+            best = self
+                .candidates(coords, delta)
+                .await?
+                .into_iter()
+                .map(|place| NearbyPlace {
+                    distance_km: coords.distance_km(&place.coords()),
+                    place,
+                })
+                .min_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+
+            // Anything outside this box is further away than the box's inscribed radius.
+            let radius_km =
+                f64::from(delta) * KM_PER_DEGREE * f64::from(coords.latitude).to_radians().cos();
+            if best.as_ref().is_some_and(|b| b.distance_km <= radius_km) {
+                break;
+            }
+        }
+
+        debug!("Reverse geocoded {coords:?} to {best:?}");
+        best.ok_or_else(|| {
+            ServiceError::CityNotFound(format!(
+                "No known place near {:.4}, {:.4}",
+                coords.latitude, coords.longitude
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::setup_test_db;
+    use pretty_assertions::assert_eq;
+
+    const GAZETTEER: &str = "\
+2643743\tLondon\tLondon\t\t51.50853\t-0.12574\tP\tPPLC\tGB\t\tENG\tGLA\t\t\t8961989\t\t25\tEurope/London\t2024-01-01
+2648110\tGreenwich\tGreenwich\t\t51.47785\t-0.01176\tP\tPPLX\tGB\t\tENG\tGLA\t\t\t0\t\t12\tEurope/London\t2024-01-01
+";
+
+    async fn setup() -> (DatabaseConnection, OfflineReverseGeocoder) {
+        let db = setup_test_db().await;
+        GazetteerRepository::new(db.clone())
+            .import_geonames(GAZETTEER.as_bytes())
+            .await
+            .unwrap();
+        (db.clone(), OfflineReverseGeocoder::new(db))
+    }
+
+    #[tokio::test]
+    async fn test_returns_nearest_gazetteer_entry() {
+        let (_db, geocoder) = setup().await;
+        let coords = LatLong {
+            latitude: 51.48,
+            longitude: -0.02,
+        };
+
+        let nearby = geocoder.reverse_geocode(&coords).await.unwrap();
+
+        assert_eq!(nearby.place.name, "Greenwich");
+        assert_eq!(nearby.place.timezone.as_deref(), Some("Europe/London"));
+        assert!(nearby.distance_km < 1.0, "distance {}", nearby.distance_km);
+    }
+
+    #[tokio::test]
+    async fn test_uses_named_search_history() {
+        let (db, geocoder) = setup().await;
+        let cities = CityRepository::new(db);
+        let oxford = LatLong {
+            latitude: 51.752,
+            longitude: -1.2577,
+        };
+        cities
+            .save_search("Oxford".to_string(), &oxford, None)
+            .await
+            .unwrap();
+        let unnamed = LatLong {
+            latitude: 51.75,
+            longitude: -1.25,
+        };
+        cities
+            .save_search(Place::from_coords(&unnamed).name, &unnamed, None)
+            .await
+            .unwrap();
+
+        let nearby = geocoder.reverse_geocode(&unnamed).await.unwrap();
+
+        assert_eq!(nearby.place.name, "Oxford");
+    }
+
+    #[tokio::test]
+    async fn test_widens_search_until_something_is_found() {
+        let (_db, geocoder) = setup().await;
+        let coords = LatLong {
+            latitude: 53.0,
+            longitude: -1.0,
+        };
+
+        let nearby = geocoder.reverse_geocode(&coords).await.unwrap();
+
+        assert_eq!(nearby.place.name, "London");
+        assert!(nearby.distance_km > 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_nothing_nearby_is_not_found() {
+        let (_db, geocoder) = setup().await;
+        let coords = LatLong {
+            latitude: -33.87,
+            longitude: 151.21,
+        };
+
+        let result = geocoder.reverse_geocode(&coords).await;
+
+        assert!(matches!(result, Err(ServiceError::CityNotFound(_))));
+    }
+}
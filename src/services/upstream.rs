@@ -0,0 +1,485 @@
+use crate::config::UpstreamConfig;
+use log::{debug, warn};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+/// Whether calls to an upstream are let through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Calls go through; failures are being counted.
+    Closed,
+    /// Calls fail fast until the cool-down has passed.
+    Open,
+    /// One probe call is deciding whether to close again.
+    HalfOpen,
+}
+
+/// A breaker's state as reported by the health endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BreakerStatus {
+    pub name: String,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    /// While open, seconds until a probe call is let through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    /// When the breaker last opened, or when the probe of a half-open breaker started.
+    since: Instant,
+}
+
+/// Stops calling an upstream after `threshold` consecutive failed calls, then lets a
+/// single probe through once `cooldown` has passed.
+///
+/// A probe that never reports back, say because its caller gave up on it, is replaced
+/// by another after a further `cooldown`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    name: &'static str,
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &'static str, threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            name,
+            threshold,
+            cooldown,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                since: Instant::now(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Whether a call may go ahead now; a `true` must be followed by a recorded outcome.
+    pub fn try_acquire(&self) -> bool {
+        let mut inner = self.lock();
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::Open | BreakerState::HalfOpen
+                if inner.since.elapsed() >= self.cooldown =>
+            {
+                inner.state = BreakerState::HalfOpen;
+                inner.since = Instant::now();
+                true
+            }
+            BreakerState::Open | BreakerState::HalfOpen => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.lock();
+        if inner.state != BreakerState::Closed {
+            debug!("Circuit breaker {} closed", self.name);
+        }
+        inner.state = BreakerState::Closed;
+        inner.consecutive_failures = 0;
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.lock();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let trips = match inner.state {
+            BreakerState::Closed => inner.consecutive_failures >= self.threshold,
+            BreakerState::HalfOpen => true,
+            BreakerState::Open => false,
+        };
+        if trips {
+            warn!(
+                "Circuit breaker {} opened after {} consecutive failures",
+                self.name, inner.consecutive_failures
+            );
+            inner.state = BreakerState::Open;
+            inner.since = Instant::now();
+        }
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let inner = self.lock();
+        BreakerStatus {
+            name: self.name.to_string(),
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            retry_in_secs: (inner.state == BreakerState::Open).then(|| {
+                self.cooldown
+                    .saturating_sub(inner.since.elapsed())
+                    .as_secs()
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum UpstreamError {
+    #[error("{0} is unavailable and its circuit breaker is open")]
+    CircuitOpen(&'static str),
+
+    #[error(transparent)]
+    Transport(#[from] reqwest::Error),
+}
+
+/// One upstream endpoint, called with a timeout, bounded retries and a circuit breaker.
+///
+/// Connection failures, timeouts and 5xx responses are retried with jittered
+/// exponential backoff; a 429 waits for its `Retry-After` when that fits within the
+/// maximum delay. Other responses, including 4xx, are returned as they are, and only
+/// the final outcome of a call counts towards the breaker.
+pub struct Upstream {
+    url: String,
+    client: Client,
+    timeout: Duration,
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl Upstream {
+    /// `client` may be shared between upstreams; the timeout is applied per request.
+    pub fn new(name: &'static str, url: &str, client: Client, config: &UpstreamConfig) -> Self {
+        Self {
+            url: url.to_string(),
+            client,
+            timeout: config.request_timeout(),
+            max_retries: config.max_retries,
+            base_delay: config.retry_base_delay(),
+            max_delay: config.retry_max_delay(),
+            breaker: Arc::new(CircuitBreaker::new(
+                name,
+                config.breaker_failure_threshold,
+                config.breaker_cooldown(),
+            )),
+        }
+    }
+
+    pub fn breaker(&self) -> Arc<CircuitBreaker> {
+        self.breaker.clone()
+    }
+
+    /// GETs the endpoint with `query`, retrying transient failures.
+    ///
+    /// Returns the last response even when it is an error status, so callers can
+    /// report what the upstream said.
+    pub async fn get<Q: Serialize + ?Sized>(&self, query: &Q) -> Result<Response, UpstreamError> {
+        if !self.breaker.try_acquire() {
+            return Err(UpstreamError::CircuitOpen(self.breaker.name));
+        }
+
+        let mut attempt = 0;
+        loop {
+            let request = self
+                .client
+                .get(&self.url)
+                .query(query)
+                .timeout(self.timeout);
+            debug!("{} request: {request:?}", self.breaker.name);
+            let result = request.send().await;
+
+            let retry_in = match &result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    Some(retry_after(response).unwrap_or_else(|| self.backoff(attempt)))
+                }
+                Ok(response) if response.status().is_server_error() => Some(self.backoff(attempt)),
+                Err(err) if err.is_timeout() || err.is_connect() => Some(self.backoff(attempt)),
+                Ok(_) | Err(_) => None,
+            };
+
+            match retry_in {
+                Some(delay) if attempt < self.max_retries && delay <= self.max_delay => {
+                    warn!(
+                        "{} attempt {} failed ({}); retrying in {}ms",
+                        self.breaker.name,
+                        attempt + 1,
+                        describe(&result),
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Some(_) => {
+                    self.breaker.record_failure();
+                    return Ok(result?);
+                }
+                None => {
+                    match &result {
+                        Ok(_) => self.breaker.record_success(),
+                        Err(_) => self.breaker.record_failure(),
+                    }
+                    return Ok(result?);
+                }
+            }
+        }
+    }
+
+    /// Exponential backoff from `base_delay`, capped at `max_delay`, with the upper half
+    /// randomized so that clients retrying together drift apart.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay);
+        let half = ceiling / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+fn describe(result: &Result<Response, reqwest::Error>) -> String {
+    match result {
+        Ok(response) => response.status().to_string(),
+        Err(err) => err.to_string(),
+    }
+}
+
+/// Delay asked for by a `Retry-After` header, in seconds or as an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fast_upstream;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn server_answering(statuses: &[ResponseTemplate]) -> MockServer {
+        let server = MockServer::start().await;
+        for response in statuses {
+            Mock::given(path("/data"))
+                .respond_with(response.clone())
+                .up_to_n_times(1)
+                .with_priority(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("ok"))
+            .with_priority(2)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn upstream(server: &MockServer, config: &UpstreamConfig) -> Upstream {
+        Upstream::new(
+            "test_api",
+            &format!("{}/data", server.uri()),
+            Client::new(),
+            config,
+        )
+    }
+
+    async fn requests(server: &MockServer) -> usize {
+        server.received_requests().await.map_or(0, |r| r.len())
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors() {
+        let server =
+            server_answering(&[ResponseTemplate::new(503), ResponseTemplate::new(502)]).await;
+        let upstream = upstream(&server, &fast_upstream());
+
+        let response = upstream.get(&[("q", "1")]).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests(&server).await, 3);
+        assert_eq!(upstream.breaker().status().consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let server = server_answering(&[
+            ResponseTemplate::new(500),
+            ResponseTemplate::new(500),
+            ResponseTemplate::new(500),
+        ])
+        .await;
+        let upstream = upstream(&server, &fast_upstream());
+
+        let response = upstream.get(&[("q", "1")]).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(requests(&server).await, 3);
+        assert_eq!(upstream.breaker().status().consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_are_not_retried() {
+        let server = server_answering(&[ResponseTemplate::new(400)]).await;
+        let upstream = upstream(&server, &fast_upstream());
+
+        let response = upstream.get(&[("q", "1")]).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(requests(&server).await, 1);
+        assert_eq!(upstream.breaker().status().state, BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_honours_retry_after() {
+        let server =
+            server_answering(&[ResponseTemplate::new(429).insert_header("Retry-After", "1")]).await;
+        let config = UpstreamConfig {
+            retry_max_delay_ms: 2_000,
+            ..fast_upstream()
+        };
+        let upstream = upstream(&server, &config);
+
+        let started = std::time::Instant::now();
+        let response = upstream.get(&[("q", "1")]).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_beyond_max_delay_is_not_waited_for() {
+        let server =
+            server_answering(&[ResponseTemplate::new(429).insert_header("Retry-After", "120")])
+                .await;
+        let upstream = upstream(&server, &fast_upstream());
+
+        let response = upstream.get(&[("q", "1")]).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(requests(&server).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_times_out_slow_responses() {
+        let server = server_answering(&[
+            ResponseTemplate::new(200).set_delay(Duration::from_secs(5)),
+            ResponseTemplate::new(200).set_delay(Duration::from_secs(5)),
+            ResponseTemplate::new(200).set_delay(Duration::from_secs(5)),
+        ])
+        .await;
+        let config = UpstreamConfig {
+            request_timeout_ms: 100,
+            ..fast_upstream()
+        };
+        let upstream = upstream(&server, &config);
+
+        let result = upstream.get(&[("q", "1")]).await;
+
+        assert!(
+            matches!(&result, Err(UpstreamError::Transport(err)) if err.is_timeout()),
+            "unexpected result: {result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_fails_fast() {
+        let server = MockServer::start().await;
+        Mock::given(path("/data"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let config = UpstreamConfig {
+            max_retries: 0,
+            breaker_failure_threshold: 2,
+            ..fast_upstream()
+        };
+        let upstream = upstream(&server, &config);
+
+        for _ in 0..2 {
+            upstream.get(&[("q", "1")]).await.unwrap();
+        }
+        let result = upstream.get(&[("q", "1")]).await;
+
+        assert!(matches!(
+            result,
+            Err(UpstreamError::CircuitOpen("test_api"))
+        ));
+        assert_eq!(requests(&server).await, 2);
+        let status = upstream.breaker().status();
+        assert_eq!(status.state, BreakerState::Open);
+        assert_eq!(status.consecutive_failures, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_breaker_probes_after_cooldown() {
+        let breaker = CircuitBreaker::new("test_api", 1, Duration::from_secs(30));
+
+        assert!(breaker.try_acquire());
+        breaker.record_failure();
+        assert!(!breaker.try_acquire());
+        assert_eq!(breaker.status().retry_in_secs, Some(30));
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.try_acquire());
+        assert_eq!(breaker.status().state, BreakerState::HalfOpen);
+        // Only the one probe goes through.
+        assert!(!breaker.try_acquire());
+
+        // A failed probe reopens the breaker ...
+        breaker.record_failure();
+        assert_eq!(breaker.status().state, BreakerState::Open);
+
+        // ... and a successful one closes it.
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.try_acquire());
+        breaker.record_success();
+        assert_eq!(breaker.status().state, BreakerState::Closed);
+        assert_eq!(breaker.status().consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_abandoned_probe_is_replaced() {
+        let breaker = CircuitBreaker::new("test_api", 1, Duration::from_secs(30));
+        breaker.record_failure();
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.try_acquire());
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let config = UpstreamConfig {
+            retry_base_delay_ms: 100,
+            retry_max_delay_ms: 1_000,
+            ..UpstreamConfig::default()
+        };
+        let upstream = Upstream::new(
+            "test_api",
+            "http://127.0.0.1:9/data",
+            Client::new(),
+            &config,
+        );
+
+        for (attempt, ceiling) in [(0, 100), (1, 200), (2, 400), (5, 1_000), (40, 1_000)] {
+            let delay = upstream.backoff(attempt);
+            assert!(
+                delay >= Duration::from_millis(ceiling / 2)
+                    && delay <= Duration::from_millis(ceiling),
+                "attempt {attempt}: {delay:?}"
+            );
+        }
+    }
+}
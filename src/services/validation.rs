@@ -0,0 +1,327 @@
+use crate::services::forecast::ForecastWindow;
+use crate::services::weather_service::{LatLong, ServiceError};
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use unicode_normalization::UnicodeNormalization;
+use unicode_properties::{GeneralCategoryGroup, UnicodeGeneralCategory};
+
+/// Longest place name accepted, in characters, after normalization.
+pub const MAX_CITY_NAME_LEN: usize = 100;
+
+/// Longest date range served by one history request, in days.
+pub const MAX_HISTORY_DAYS: i64 = 366;
+
+/// First day covered by the Open-Meteo archive.
+const ARCHIVE_START: NaiveDate = match NaiveDate::from_ymd_opt(1940, 1, 1) {
+    Some(date) => date,
+    None => panic!("invalid archive start"),
+};
+
+/// Punctuation that legitimately appears in place names ("St. John's", "Saint-Étienne").
+const ALLOWED_PUNCTUATION: &[char] = &[' ', '-', '\'', '’', '.', ',', '(', ')'];
+
+/// Trims, NFC-normalizes and checks a user-supplied city name.
+///
+/// Letters and combining marks from any script are accepted, as are digits (for postal
+/// codes) and the punctuation in `ALLOWED_PUNCTUATION`. Anything else is rejected with
+/// `ServiceError::InvalidInput` rather than being forwarded to the geocoder.
+pub fn normalize_city_name(raw: &str) -> Result<String, ServiceError> {
+    normalize_place_name("City name", raw)
+}
+
+/// Applies the city name policy to a region name such as an `admin1` filter.
+pub fn normalize_region_name(raw: &str) -> Result<String, ServiceError> {
+    normalize_place_name("Region name", raw)
+}
+
+/// Checks an ISO 3166-1 alpha-2 country code and returns it upper-cased.
+pub fn normalize_country_code(raw: &str) -> Result<String, ServiceError> {
+    let code = raw.trim();
+
+    if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(ServiceError::InvalidInput(format!(
+            "Country must be a two-letter ISO 3166-1 code, got {code:?}"
+        )));
+    }
+
+    Ok(code.to_ascii_uppercase())
+}
+
+/// Checks that a latitude/longitude pair lies within WGS 84 bounds.
+pub fn validate_coordinates(latitude: f32, longitude: f32) -> Result<LatLong, ServiceError> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(ServiceError::InvalidInput(format!(
+            "Latitude must be between -90 and 90, got {latitude}"
+        )));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(ServiceError::InvalidInput(format!(
+            "Longitude must be between -180 and 180, got {longitude}"
+        )));
+    }
+
+    Ok(LatLong {
+        latitude,
+        longitude,
+    })
+}
+
+/// Checks the `days` and `past_days` parameters, defaulting to Open-Meteo's seven days ahead.
+pub fn validate_forecast_window(
+    days: Option<u32>,
+    past_days: Option<u32>,
+) -> Result<ForecastWindow, ServiceError> {
+    let days = days.unwrap_or(ForecastWindow::DEFAULT_DAYS);
+    let past_days = past_days.unwrap_or(0);
+
+    if !(1..=ForecastWindow::MAX_DAYS).contains(&days) {
+        return Err(ServiceError::InvalidInput(format!(
+            "Days must be between 1 and {}, got {days}",
+            ForecastWindow::MAX_DAYS
+        )));
+    }
+    if past_days > ForecastWindow::MAX_PAST_DAYS {
+        return Err(ServiceError::InvalidInput(format!(
+            "Past days must be between 0 and {}, got {past_days}",
+            ForecastWindow::MAX_PAST_DAYS
+        )));
+    }
+
+    Ok(ForecastWindow { days, past_days })
+}
+
+/// Parses and checks an inclusive `YYYY-MM-DD` date range for a history lookup.
+///
+/// Both dates must lie between 1940-01-01 and yesterday (relative to `today`), `start`
+/// must not come after `end`, and the range may span at most [`MAX_HISTORY_DAYS`].
+pub fn validate_date_range(
+    start: &str,
+    end: &str,
+    today: NaiveDate,
+) -> Result<(NaiveDate, NaiveDate), ServiceError> {
+    let parse = |field: &str, raw: &str| {
+        NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d").map_err(|_| {
+            ServiceError::InvalidInput(format!(
+                "{field} date must be formatted YYYY-MM-DD, got {raw:?}"
+            ))
+        })
+    };
+    let start = parse("Start", start)?;
+    let end = parse("End", end)?;
+
+    if start > end {
+        return Err(ServiceError::InvalidInput(format!(
+            "Start date {start} is after end date {end}"
+        )));
+    }
+    if start < ARCHIVE_START {
+        return Err(ServiceError::InvalidInput(format!(
+            "History starts on {ARCHIVE_START}, got {start}"
+        )));
+    }
+    if end >= today {
+        return Err(ServiceError::InvalidInput(format!(
+            "End date must be before today ({today}), got {end}"
+        )));
+    }
+    if (end - start).num_days() >= MAX_HISTORY_DAYS {
+        return Err(ServiceError::InvalidInput(format!(
+            "Date range must span at most {MAX_HISTORY_DAYS} days"
+        )));
+    }
+
+    Ok((start, end))
+}
+
+/// Parses an IANA time zone name such as `America/New_York`.
+pub fn parse_timezone(raw: &str) -> Result<Tz, ServiceError> {
+    raw.trim().parse().map_err(|_| {
+        ServiceError::InvalidInput(format!(
+            "Unknown time zone {raw:?}; expected an IANA name such as Europe/Berlin"
+        ))
+    })
+}
+
+fn normalize_place_name(field: &str, raw: &str) -> Result<String, ServiceError> {
+    let name: String = raw.trim().nfc().collect();
+
+    if name.is_empty() {
+        return Err(ServiceError::InvalidInput(format!(
+            "{field} cannot be empty"
+        )));
+    }
+
+    if name.chars().count() > MAX_CITY_NAME_LEN {
+        return Err(ServiceError::InvalidInput(format!(
+            "{field} must be at most {MAX_CITY_NAME_LEN} characters"
+        )));
+    }
+
+    if let Some(invalid) = name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || is_mark(*c) || ALLOWED_PUNCTUATION.contains(c)))
+    {
+        return Err(ServiceError::InvalidInput(format!(
+            "{field} contains an invalid character: {invalid:?}"
+        )));
+    }
+
+    Ok(name)
+}
+
+/// Combining marks (Mn, Mc, Me) that survive NFC because no precomposed form exists, as
+/// in Devanagari or Tamil.
+fn is_mark(c: char) -> bool {
+    c.general_category_group() == GeneralCategoryGroup::Mark
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test_case("Kraków"        ; "with a precomposed accent")]
+    #[test_case("Saint-Étienne" ; "with a hyphen")]
+    #[test_case("Port of Spain" ; "with spaces")]
+    #[test_case("St. John's"    ; "with a dot and apostrophe")]
+    #[test_case("東京"           ; "in CJK script")]
+    #[test_case("नई दिल्ली"       ; "with Devanagari vowel signs")]
+    #[test_case("சென்னை"         ; "with a Tamil virama")]
+    #[test_case("10967"         ; "as a postal code")]
+    fn test_accepts_real_place_names(name: &str) {
+        assert_eq!(normalize_city_name(name).unwrap(), name);
+    }
+
+    #[test]
+    fn test_normalizes_to_nfc_and_trims() {
+        let decomposed = "  Krako\u{301}w ";
+        assert_eq!(normalize_city_name(decomposed).unwrap(), "Kraków");
+    }
+
+    #[test_case(""                  ; "when empty")]
+    #[test_case("   "               ; "when blank")]
+    #[test_case("Paris&count=10"    ; "with a query separator")]
+    #[test_case("Paris#fragment"    ; "with a fragment marker")]
+    #[test_case("Paris\nLondon"     ; "with a control character")]
+    #[test_case("<script>"          ; "with markup")]
+    fn test_rejects_invalid_names(name: &str) {
+        assert!(matches!(
+            normalize_city_name(name),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    #[test_case("Asia/Kolkata", Tz::Asia__Kolkata ; "with a region")]
+    #[test_case(" UTC ", Tz::UTC ; "when padded")]
+    fn test_parses_timezone(raw: &str, expected: Tz) {
+        assert_eq!(parse_timezone(raw).unwrap(), expected);
+    }
+
+    #[test_case("Mars/Olympus" ; "when unknown")]
+    #[test_case("+02:00" ; "as an offset")]
+    fn test_rejects_timezone(raw: &str) {
+        assert!(matches!(
+            parse_timezone(raw),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    #[test_case(None, None, 7, 0 ; "by default")]
+    #[test_case(Some(16), Some(0), 16, 0 ; "at the longest horizon")]
+    #[test_case(Some(1), Some(92), 1, 92 ; "at the longest history")]
+    fn test_validates_forecast_window(
+        days: Option<u32>,
+        past_days: Option<u32>,
+        expected_days: u32,
+        expected_past_days: u32,
+    ) {
+        assert_eq!(
+            validate_forecast_window(days, past_days).unwrap(),
+            ForecastWindow {
+                days: expected_days,
+                past_days: expected_past_days,
+            }
+        );
+    }
+
+    #[test_case(Some(0), None ; "without days")]
+    #[test_case(Some(17), None ; "beyond the horizon")]
+    #[test_case(None, Some(93) ; "beyond the history")]
+    fn test_rejects_forecast_window(days: Option<u32>, past_days: Option<u32>) {
+        assert!(matches!(
+            validate_forecast_window(days, past_days),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test_case("2024-03-01", "2024-03-01", day(2024, 3, 1), day(2024, 3, 1) ; "for a single day")]
+    #[test_case(" 2023-10-12", "2024-10-11 ", day(2023, 10, 12), day(2024, 10, 11) ; "for the longest range")]
+    #[test_case("1940-01-01", "1940-01-02", day(1940, 1, 1), day(1940, 1, 2) ; "from the first archived day")]
+    fn test_validates_date_range(start: &str, end: &str, from: NaiveDate, to: NaiveDate) {
+        assert_eq!(
+            validate_date_range(start, end, day(2024, 10, 26)).unwrap(),
+            (from, to)
+        );
+    }
+
+    #[test_case("2024-3-1x", "2024-03-01" ; "with a malformed start")]
+    #[test_case("2024-03-01", "01/03/2024" ; "with a malformed end")]
+    #[test_case("2024-03-02", "2024-03-01" ; "when reversed")]
+    #[test_case("1939-12-31", "1940-01-01" ; "before the archive")]
+    #[test_case("2024-10-20", "2024-10-26" ; "when ending today")]
+    #[test_case("2023-10-11", "2024-10-11" ; "when spanning over a year")]
+    fn test_rejects_date_range(start: &str, end: &str) {
+        assert!(matches!(
+            validate_date_range(start, end, day(2024, 10, 26)),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    #[test_case("us", "US" ; "when lower case")]
+    #[test_case(" FR ", "FR" ; "when padded")]
+    fn test_normalizes_country_code(raw: &str, expected: &str) {
+        assert_eq!(normalize_country_code(raw).unwrap(), expected);
+    }
+
+    #[test_case("USA" ; "when alpha-3")]
+    #[test_case("U1"  ; "with a digit")]
+    #[test_case(""    ; "when empty")]
+    fn test_rejects_invalid_country_code(raw: &str) {
+        assert!(matches!(
+            normalize_country_code(raw),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    #[test_case(90.0, 180.0    ; "at the upper bounds")]
+    #[test_case(-90.0, -180.0  ; "at the lower bounds")]
+    #[test_case(51.5, -0.12    ; "for London")]
+    fn test_accepts_valid_coordinates(latitude: f32, longitude: f32) {
+        assert!(validate_coordinates(latitude, longitude).is_ok());
+    }
+
+    #[test_case(90.1, 0.0      ; "when latitude is too large")]
+    #[test_case(0.0, -180.5    ; "when longitude is too small")]
+    #[test_case(f32::NAN, 0.0  ; "when latitude is not a number")]
+    fn test_rejects_invalid_coordinates(latitude: f32, longitude: f32) {
+        assert!(matches!(
+            validate_coordinates(latitude, longitude),
+            Err(ServiceError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_overlong_names() {
+        let name = "a".repeat(MAX_CITY_NAME_LEN + 1);
+        assert!(matches!(
+            normalize_city_name(&name),
+            Err(ServiceError::InvalidInput(_))
+        ));
+        assert!(normalize_city_name(&"a".repeat(MAX_CITY_NAME_LEN)).is_ok());
+    }
+}
@@ -1,10 +1,41 @@
+use crate::config::{OpenMeteoConfig, UpstreamConfig};
+use crate::services::air_quality::{
+    AirQuality, AirQualityReading, AirQualityValues, AIR_QUALITY_VARIABLES, MAX_AIR_QUALITY_DAYS,
+};
+use crate::services::forecast::{ForecastRequest, HourlyVariable, UnitSystem};
+use crate::services::places::PlaceQuery;
+use crate::services::provider::{
+    AirQualityProvider, Geocoder, HistoryProvider, MarineProvider, ProviderKind, ReverseGeocoder,
+    WeatherProvider,
+};
+use crate::services::reverse_geocoder::NearbyPlace;
+use crate::services::upstream::{CircuitBreaker, Upstream, UpstreamError};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
 use log::{debug, error, info, warn};
-use reqwest::Client;
-use serde::Deserialize;
+use reqwest::{Client, StatusCode};
+use serde::{de, de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
 use thiserror::Error;
+use utoipa::ToSchema;
 
-const GEOCODING_API_URL: &str = "https://geocoding-api.open-meteo.com/v1/search";
-const WEATHER_API_URL: &str = "https://api.open-meteo.com/v1/forecast";
+/// Mean Earth radius used for great-circle distances.
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Number of candidates requested from the geocoding API.
+const GEOCODING_RESULT_COUNT: &str = "10";
+
+/// Hourly series requested from the marine API.
+const MARINE_VARIABLES: &str =
+    "wave_height,wave_period,swell_wave_direction,sea_surface_temperature";
+
+/// A place whose nearest sea grid cell lies within this distance counts as coastal.
+pub const COASTAL_RADIUS_KM: f64 = 20.0;
+
+/// Daily aggregates requested alongside the hourly series when a forecast asks for them.
+const DAILY_VARIABLES: &str =
+    "temperature_2m_max,temperature_2m_min,temperature_2m_mean,precipitation_sum,sunrise,sunset";
 
 #[derive(Debug, Error)]
 pub enum ServiceError {
@@ -17,117 +48,928 @@ pub enum ServiceError {
     #[error("City not found")]
     CityNotFound(String),
 
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
     #[error("Failed to parse response")]
     InvalidResponse(#[from] serde_json::Error),
+
+    /// Air quality is an optional extra; callers report it without failing the forecast.
+    #[error("Failed to fetch air quality: {0}")]
+    AirQualityError(String),
+
+    /// Marine data is an optional extra; callers report it without failing the forecast.
+    #[error("Failed to fetch marine data: {0}")]
+    MarineError(String),
+
+    /// An upstream API, or a forecast provider, did not answer in time.
+    #[error("Upstream timed out: {0}")]
+    UpstreamTimeout(String),
+
+    /// An upstream API is failing and its circuit breaker is open.
+    #[error("Upstream unavailable: {0}")]
+    UpstreamUnavailable(String),
+
+    /// An upstream API turned the request down with `429 Too Many Requests`.
+    #[error("Upstream rate limit reached: {0}")]
+    RateLimited(String),
+}
+
+impl ServiceError {
+    /// Classifies a call to an upstream API that got no response; failures with no more
+    /// specific variant become `failed`.
+    pub fn from_upstream(err: &UpstreamError, failed: fn(String) -> Self) -> Self {
+        match err {
+            UpstreamError::CircuitOpen(_) => Self::UpstreamUnavailable(err.to_string()),
+            UpstreamError::Transport(transport) if transport.is_timeout() => {
+                Self::UpstreamTimeout(err.to_string())
+            }
+            UpstreamError::Transport(_) => failed(err.to_string()),
+        }
+    }
+
+    /// Classifies an error `status` from an upstream API, described by `message`.
+    pub fn from_upstream_status(
+        status: StatusCode,
+        message: String,
+        failed: fn(String) -> Self,
+    ) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            Self::RateLimited(message)
+        } else {
+            failed(message)
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Lets one upstream failure be handed to every caller that was waiting on it.
+impl Clone for ServiceError {
+    fn clone(&self) -> Self {
+        match self {
+            Self::GeocodingError(msg) => Self::GeocodingError(msg.clone()),
+            Self::WeatherError(msg) => Self::WeatherError(msg.clone()),
+            Self::CityNotFound(msg) => Self::CityNotFound(msg.clone()),
+            Self::InvalidInput(msg) => Self::InvalidInput(msg.clone()),
+            // `serde_json::Error` is not `Clone`; its message, position included, survives.
+            Self::InvalidResponse(err) => Self::InvalidResponse(de::Error::custom(err)),
+            Self::AirQualityError(msg) => Self::AirQualityError(msg.clone()),
+            Self::MarineError(msg) => Self::MarineError(msg.clone()),
+            Self::UpstreamTimeout(msg) => Self::UpstreamTimeout(msg.clone()),
+            Self::UpstreamUnavailable(msg) => Self::UpstreamUnavailable(msg.clone()),
+            Self::RateLimited(msg) => Self::RateLimited(msg.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct LatLong {
     pub latitude: f32,
     pub longitude: f32,
 }
 
+impl LatLong {
+    /// Great-circle (haversine) distance to `other` in kilometres.
+    pub fn distance_km(&self, other: &LatLong) -> f64 {
+        let (lat1, lat2) = (
+            f64::from(self.latitude).to_radians(),
+            f64::from(other.latitude).to_radians(),
+        );
+        let d_lat = lat2 - lat1;
+        let d_lon = f64::from(other.longitude - self.longitude).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+/// A geocoding match with enough metadata to tell same-named places apart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Place {
+    pub name: String,
+    pub latitude: f32,
+    pub longitude: f32,
+    #[serde(default)]
+    pub country_code: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub admin1: Option<String>,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub population: Option<u64>,
+    #[serde(default)]
+    pub elevation: Option<f32>,
+}
+
+impl Place {
+    /// An unnamed place labelled with its own coordinates.
+    pub fn from_coords(coords: &LatLong) -> Self {
+        Self {
+            name: format!("{:.4}, {:.4}", coords.latitude, coords.longitude),
+            latitude: coords.latitude,
+            longitude: coords.longitude,
+            country_code: None,
+            country: None,
+            admin1: None,
+            timezone: None,
+            population: None,
+            elevation: None,
+        }
+    }
+
+    pub fn coords(&self) -> LatLong {
+        LatLong {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        }
+    }
+
+    /// The place's IANA zone, when known and recognised.
+    pub fn tz(&self) -> Option<Tz> {
+        self.timezone.as_deref()?.parse().ok()
+    }
+
+    /// Human-readable qualifier such as "Illinois, United States".
+    pub fn region(&self) -> String {
+        [self.admin1.as_deref(), self.country.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty() && *part != self.name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GeoResponse {
-    pub results: Option<Vec<LatLong>>,
+    pub results: Option<Vec<Place>>,
 }
 
+/// Body Open-Meteo sends alongside 4xx statuses.
 #[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    reason: String,
+}
+
+/// A forecast with its times as UTC instants; [`WeatherData::local`] places them in `timezone`.
+///
+/// Serializes back into Open-Meteo's shape, which is how cached forecasts are stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WeatherData {
+    /// Zone the provider used for the forecast, also the one daily aggregates follow.
+    #[serde(
+        default = "default_timezone",
+        serialize_with = "serialize_timezone",
+        deserialize_with = "deserialize_timezone"
+    )]
+    pub timezone: Tz,
     pub hourly: HourlyData,
+    /// Present when daily aggregates were requested and the provider computed them.
+    #[serde(default)]
+    pub daily: Option<DailyData>,
+    /// Service the data came from; absent from Open-Meteo's own responses.
+    #[serde(default)]
+    pub provider: ProviderKind,
+    /// When the provider last updated the forecast. MET Norway reports its model run;
+    /// Open-Meteo does not, so its forecasts leave this empty.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// When the forecast was fetched, if it is being served past its cache lifetime.
+    #[serde(skip)]
+    pub stale_as_of: Option<DateTime<Utc>>,
+    /// When the cached forecast goes, or went, out of date; `None` if it was not cached.
+    #[serde(skip)]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Deserialize)]
+impl WeatherData {
+    pub fn local(&self, time: DateTime<Utc>) -> DateTime<Tz> {
+        time.with_timezone(&self.timezone)
+    }
+
+    /// Converts every metric value, hourly and daily, into `units`.
+    pub fn convert_metric(&mut self, units: UnitSystem) {
+        let convert = |series: &mut Option<Vec<Option<f64>>>, variable: HourlyVariable| {
+            for value in series.iter_mut().flatten().flatten() {
+                *value = units.convert_metric(variable, *value);
+            }
+        };
+
+        for variable in HourlyVariable::ALL {
+            convert(self.hourly.series_mut(variable), variable);
+        }
+        if let Some(daily) = &mut self.daily {
+            convert(&mut daily.temperature_2m_max, HourlyVariable::Temperature2m);
+            convert(&mut daily.temperature_2m_min, HourlyVariable::Temperature2m);
+            convert(
+                &mut daily.temperature_2m_mean,
+                HourlyVariable::Temperature2m,
+            );
+            convert(&mut daily.precipitation_sum, HourlyVariable::Precipitation);
+        }
+    }
+
+    /// Drops everything after the first `days` local calendar days.
+    ///
+    /// Providers may return a longer horizon than requested; this keeps summaries and
+    /// tables to the window the caller asked for.
+    pub fn keep_days(&mut self, days: usize) {
+        let mut dates = self.hourly.time.iter().map(|&t| self.local(t).date_naive());
+        let mut seen = 0;
+        let mut previous = None;
+        let hours = dates
+            .position(|date| {
+                if previous != Some(date) {
+                    previous = Some(date);
+                    seen += 1;
+                }
+                seen > days
+            })
+            .unwrap_or(self.hourly.time.len());
+
+        self.hourly.time.truncate(hours);
+        for variable in HourlyVariable::ALL {
+            if let Some(series) = self.hourly.series_mut(variable) {
+                series.truncate(hours);
+            }
+        }
+        if let Some(daily) = &mut self.daily {
+            daily.time.truncate(days);
+            for series in [
+                &mut daily.temperature_2m_max,
+                &mut daily.temperature_2m_min,
+                &mut daily.temperature_2m_mean,
+                &mut daily.precipitation_sum,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                series.truncate(days);
+            }
+            for series in [&mut daily.sunrise, &mut daily.sunset]
+                .into_iter()
+                .flatten()
+            {
+                series.truncate(days);
+            }
+        }
+    }
+}
+
+/// Hourly series keyed by time; a series is `None` unless it was requested.
+///
+/// Individual values may be `null` where the model has no data for that hour.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct HourlyData {
-    pub time: Vec<String>,
-    pub temperature_2m: Vec<f64>,
+    #[serde(
+        serialize_with = "serialize_unix_times",
+        deserialize_with = "deserialize_unix_times"
+    )]
+    pub time: Vec<DateTime<Utc>>,
+    pub temperature_2m: Option<Vec<Option<f64>>>,
+    pub relative_humidity_2m: Option<Vec<Option<f64>>>,
+    pub precipitation: Option<Vec<Option<f64>>>,
+    pub precipitation_probability: Option<Vec<Option<f64>>>,
+    pub wind_speed_10m: Option<Vec<Option<f64>>>,
+    pub wind_direction_10m: Option<Vec<Option<f64>>>,
+    pub wind_gusts_10m: Option<Vec<Option<f64>>>,
+    pub cloud_cover: Option<Vec<Option<f64>>>,
+    pub weather_code: Option<Vec<Option<f64>>>,
+}
+
+impl HourlyData {
+    pub fn series(&self, variable: HourlyVariable) -> Option<&[Option<f64>]> {
+        let series = match variable {
+            HourlyVariable::Temperature2m => &self.temperature_2m,
+            HourlyVariable::RelativeHumidity2m => &self.relative_humidity_2m,
+            HourlyVariable::Precipitation => &self.precipitation,
+            HourlyVariable::PrecipitationProbability => &self.precipitation_probability,
+            HourlyVariable::WindSpeed10m => &self.wind_speed_10m,
+            HourlyVariable::WindDirection10m => &self.wind_direction_10m,
+            HourlyVariable::WindGusts10m => &self.wind_gusts_10m,
+            HourlyVariable::CloudCover => &self.cloud_cover,
+            HourlyVariable::WeatherCode => &self.weather_code,
+        };
+        series.as_deref()
+    }
+
+    fn series_mut(&mut self, variable: HourlyVariable) -> &mut Option<Vec<Option<f64>>> {
+        match variable {
+            HourlyVariable::Temperature2m => &mut self.temperature_2m,
+            HourlyVariable::RelativeHumidity2m => &mut self.relative_humidity_2m,
+            HourlyVariable::Precipitation => &mut self.precipitation,
+            HourlyVariable::PrecipitationProbability => &mut self.precipitation_probability,
+            HourlyVariable::WindSpeed10m => &mut self.wind_speed_10m,
+            HourlyVariable::WindDirection10m => &mut self.wind_direction_10m,
+            HourlyVariable::WindGusts10m => &mut self.wind_gusts_10m,
+            HourlyVariable::CloudCover => &mut self.cloud_cover,
+            HourlyVariable::WeatherCode => &mut self.weather_code,
+        }
+    }
+
+    /// Value of `variable` at position `hour`, if the series exists and has data there.
+    pub fn value(&self, variable: HourlyVariable, hour: usize) -> Option<f64> {
+        self.series(variable)
+            .and_then(|series| series.get(hour).copied().flatten())
+    }
+
+    /// Lowest and highest temperature, ignoring missing hours.
+    pub fn temperature_range(&self) -> Option<(f64, f64)> {
+        self.series(HourlyVariable::Temperature2m)?
+            .iter()
+            .flatten()
+            .fold(None, |range, &t| match range {
+                None => Some((t, t)),
+                Some((min, max)) => Some((t.min(min), t.max(max))),
+            })
+    }
+
+    /// Checks that every requested series is present and lines up with `time`.
+    fn check_columns(&self, variables: &[HourlyVariable]) -> Result<(), String> {
+        for &variable in variables {
+            match self.series(variable) {
+                None => return Err(format!("missing hourly series {}", variable.as_str())),
+                Some(series) if series.len() != self.time.len() => {
+                    return Err(format!(
+                        "hourly series {} has {} values for {} times",
+                        variable.as_str(),
+                        series.len(),
+                        self.time.len()
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sea state from the marine API for the sea grid cell nearest a place.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MarineData {
+    /// Centre of the grid cell the values belong to.
+    pub latitude: f32,
+    pub longitude: f32,
+    pub hourly: MarineHourly,
+}
+
+/// Hourly sea-state series; values are `null` where the wave model has no data.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MarineHourly {
+    #[serde(deserialize_with = "deserialize_unix_times")]
+    pub time: Vec<DateTime<Utc>>,
+    /// Significant wave height in metres.
+    pub wave_height: Option<Vec<Option<f64>>>,
+    /// Seconds.
+    pub wave_period: Option<Vec<Option<f64>>>,
+    /// Degrees the swell comes from.
+    pub swell_wave_direction: Option<Vec<Option<f64>>>,
+    /// Degrees Celsius.
+    pub sea_surface_temperature: Option<Vec<Option<f64>>>,
+}
+
+impl MarineData {
+    /// Distance from `coords` to the grid cell, in kilometres.
+    pub fn distance_km(&self, coords: &LatLong) -> f64 {
+        coords.distance_km(&LatLong {
+            latitude: self.latitude,
+            longitude: self.longitude,
+        })
+    }
+
+    /// Whether the nearest sea cell is close enough for `coords` to count as coastal.
+    pub fn is_coastal(&self, coords: &LatLong) -> bool {
+        self.distance_km(coords) <= COASTAL_RADIUS_KM
+    }
+
+    /// Converts wave heights and sea temperatures from metric into `units`.
+    pub fn convert_metric(&mut self, units: UnitSystem) {
+        let hourly = &mut self.hourly;
+        for value in hourly.wave_height.iter_mut().flatten().flatten() {
+            *value = units.convert_wave_height(*value);
+        }
+        for value in hourly
+            .sea_surface_temperature
+            .iter_mut()
+            .flatten()
+            .flatten()
+        {
+            *value = units.convert_metric(HourlyVariable::Temperature2m, *value);
+        }
+    }
+}
+
+/// Marine data for a forecast when asked for explicitly or when the place is coastal.
+///
+/// The distance to the nearest sea cell decides whether a place is coastal, so `provider`
+/// is consulted unless it already knows the place is inland; `Ok(None)` means it is and
+/// nobody asked.
+pub async fn marine_for(
+    provider: &dyn MarineProvider,
+    request: &ForecastRequest,
+    requested: bool,
+) -> Result<Option<MarineData>, ServiceError> {
+    if !requested && provider.known_coastal(&request.coords) == Some(false) {
+        return Ok(None);
+    }
+    let marine = provider.fetch_marine(request).await?;
+    Ok((requested || marine.is_coastal(&request.coords)).then_some(marine))
+}
+
+impl MarineHourly {
+    pub fn value(series: Option<&Vec<Option<f64>>>, hour: usize) -> Option<f64> {
+        series?.get(hour).copied().flatten()
+    }
+}
+
+/// Open-Meteo's per-day aggregates, keyed by the instant each local day starts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyData {
+    #[serde(
+        serialize_with = "serialize_unix_times",
+        deserialize_with = "deserialize_unix_times"
+    )]
+    pub time: Vec<DateTime<Utc>>,
+    pub temperature_2m_max: Option<Vec<Option<f64>>>,
+    pub temperature_2m_min: Option<Vec<Option<f64>>>,
+    pub temperature_2m_mean: Option<Vec<Option<f64>>>,
+    pub precipitation_sum: Option<Vec<Option<f64>>>,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_unix_times",
+        deserialize_with = "deserialize_optional_unix_times"
+    )]
+    pub sunrise: Option<Instants>,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_unix_times",
+        deserialize_with = "deserialize_optional_unix_times"
+    )]
+    pub sunset: Option<Instants>,
+}
+
+/// A series of instants in which individual entries may be missing.
+pub type Instants = Vec<Option<DateTime<Utc>>>;
+
+/// Open-Meteo air-quality response: current values plus hourly columns.
+#[derive(Debug, Deserialize)]
+struct AirQualityResponse {
+    current: Option<AirQualityCurrent>,
+    hourly: AirQualityHourly,
+}
+
+#[derive(Debug, Deserialize)]
+struct AirQualityCurrent {
+    #[serde(deserialize_with = "deserialize_unix_time")]
+    time: DateTime<Utc>,
+    #[serde(flatten)]
+    values: AirQualityValues,
+}
+
+#[derive(Debug, Deserialize)]
+struct AirQualityHourly {
+    #[serde(deserialize_with = "deserialize_unix_times")]
+    time: Vec<DateTime<Utc>>,
+    pm2_5: Option<Vec<Option<f64>>>,
+    pm10: Option<Vec<Option<f64>>>,
+    ozone: Option<Vec<Option<f64>>>,
+    european_aqi: Option<Vec<Option<f64>>>,
+    us_aqi: Option<Vec<Option<f64>>>,
+    uv_index: Option<Vec<Option<f64>>>,
+}
+
+impl From<AirQualityResponse> for AirQuality {
+    fn from(response: AirQualityResponse) -> Self {
+        let hourly = &response.hourly;
+        let at = |series: &Option<Vec<Option<f64>>>, hour: usize| {
+            series.as_ref()?.get(hour).copied().flatten()
+        };
+
+        Self {
+            current: response.current.map(|current| AirQualityReading {
+                time: current.time,
+                values: current.values,
+            }),
+            hourly: hourly
+                .time
+                .iter()
+                .enumerate()
+                .map(|(hour, &time)| AirQualityReading {
+                    time,
+                    values: AirQualityValues {
+                        pm2_5: at(&hourly.pm2_5, hour),
+                        pm10: at(&hourly.pm10, hour),
+                        ozone: at(&hourly.ozone, hour),
+                        european_aqi: at(&hourly.european_aqi, hour),
+                        us_aqi: at(&hourly.us_aqi, hour),
+                        uv_index: at(&hourly.uv_index, hour),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+fn deserialize_timezone<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tz, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    name.parse().map_err(de::Error::custom)
+}
+
+// Signatures below are dictated by `#[serde(serialize_with)]`.
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn serialize_timezone<S: Serializer>(timezone: &Tz, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(timezone.name())
+}
+
+fn unix_time<E: de::Error>(seconds: i64) -> Result<DateTime<Utc>, E> {
+    DateTime::from_timestamp(seconds, 0)
+        .ok_or_else(|| E::custom(format!("timestamp out of range: {seconds}")))
+}
+
+fn deserialize_unix_time<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<DateTime<Utc>, D::Error> {
+    unix_time(i64::deserialize(deserializer)?)
+}
+
+/// Reads the epoch seconds Open-Meteo sends for `timeformat=unixtime`.
+fn deserialize_unix_times<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<DateTime<Utc>>, D::Error> {
+    Vec::<i64>::deserialize(deserializer)?
+        .into_iter()
+        .map(unix_time)
+        .collect()
+}
+
+fn serialize_unix_times<S: Serializer>(
+    times: &[DateTime<Utc>],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(times.iter().map(DateTime::timestamp))
+}
+
+#[allow(clippy::ref_option)]
+fn serialize_optional_unix_times<S: Serializer>(
+    times: &Option<Instants>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match times {
+        Some(times) => {
+            serializer.collect_seq(times.iter().map(|time| time.map(|time| time.timestamp())))
+        }
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_unix_times<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Instants>, D::Error> {
+    Option::<Vec<Option<i64>>>::deserialize(deserializer)?
+        .map(|times| {
+            times
+                .into_iter()
+                .map(|time| time.map(unix_time).transpose())
+                .collect()
+        })
+        .transpose()
 }
 
 pub struct WeatherService {
-    client: Client,
+    geocoding: Upstream,
+    forecast: Upstream,
+    archive: Upstream,
+    air_quality: Upstream,
+    marine: Upstream,
+    reverse_geocoder: Option<Arc<dyn ReverseGeocoder>>,
 }
 
 impl WeatherService {
-    pub fn new() -> Self {
+    /// Each Open-Meteo API gets its own circuit breaker, so an outage of one (say the
+    /// archive) leaves the others usable.
+    pub fn new(config: &OpenMeteoConfig, upstream: &UpstreamConfig) -> Self {
+        let client = Client::new();
+        let api = |name, url: &str| Upstream::new(name, url, client.clone(), upstream);
+
         Self {
-            client: Client::new(),
+            geocoding: api("open_meteo_geocoding", &config.geocoding_url),
+            forecast: api("open_meteo_forecast", &config.forecast_url),
+            archive: api("open_meteo_archive", &config.archive_url),
+            air_quality: api("open_meteo_air_quality", &config.air_quality_url),
+            marine: api("open_meteo_marine", &config.marine_url),
+            reverse_geocoder: None,
         }
     }
 
-    pub async fn fetch_coordinates(&self, city: &str) -> Result<LatLong, ServiceError> {
-        debug!("Fetching coordinates for city: {}", city);
+    pub fn breakers(&self) -> Vec<Arc<CircuitBreaker>> {
+        [
+            &self.geocoding,
+            &self.forecast,
+            &self.archive,
+            &self.air_quality,
+            &self.marine,
+        ]
+        .into_iter()
+        .map(Upstream::breaker)
+        .collect()
+    }
 
-        if city.trim().is_empty() {
-            warn!("Empty city name provided");
-            return Err(ServiceError::CityNotFound(
-                "City name cannot be empty".to_string(),
-            ));
-        }
+    /// Open-Meteo has no reverse geocoding API, so lookups are delegated to `backend`.
+    pub fn with_reverse_geocoder(mut self, backend: Arc<dyn ReverseGeocoder>) -> Self {
+        self.reverse_geocoder = Some(backend);
+        self
+    }
 
-        let url = format!("{GEOCODING_API_URL}?name={city}&count=1&language=en&format=json");
-        debug!("Geocoding API request: {}", url);
+    /// Sends a forecast-shaped request to `upstream` and checks every requested series
+    /// came back.
+    async fn get_hourly(
+        api: &str,
+        upstream: &Upstream,
+        params: &[(&str, String)],
+        variables: &[HourlyVariable],
+    ) -> Result<WeatherData, ServiceError> {
+        let weather_data: WeatherData =
+            Self::get_json(api, upstream, params, ServiceError::WeatherError).await?;
+        weather_data
+            .hourly
+            .check_columns(variables)
+            .map_err(|message| {
+                error!("Incomplete {api} response: {message}");
+                ServiceError::WeatherError(message)
+            })?;
 
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("Geocoding API request failed: {}", e);
-            ServiceError::GeocodingError(e.to_string())
-        })?;
+        Ok(weather_data)
+    }
 
-        let geo_data: GeoResponse = response.json().await.map_err(|e| {
-            error!("Failed to parse Geocoding API response: {}", e);
-            ServiceError::GeocodingError(format!("Failed to parse JSON: {e}"))
+    /// GETs `upstream` and parses the JSON body, turning transport errors, error statuses
+    /// and an open circuit breaker into errors, `failed` unless more specific; `api`
+    /// names the upstream in the logs.
+    async fn get_json<T: DeserializeOwned, Q: Serialize + ?Sized>(
+        api: &str,
+        upstream: &Upstream,
+        params: &Q,
+        failed: fn(String) -> ServiceError,
+    ) -> Result<T, ServiceError> {
+        let response = upstream.get(params).await.map_err(|e| {
+            error!("{api} request failed: {e}");
+            ServiceError::from_upstream(&e, failed)
         })?;
 
+        let status = response.status();
+        if !status.is_success() {
+            let message = describe_error_response(response).await;
+            error!("{api} returned an error: {message}");
+            return Err(ServiceError::from_upstream_status(status, message, failed));
+        }
+
+        response.json().await.map_err(|e| {
+            error!("Failed to parse {api} response: {e}");
+            failed(e.to_string())
+        })
+    }
+}
+
+#[async_trait]
+impl Geocoder for WeatherService {
+    async fn fetch_places(&self, query: &PlaceQuery) -> Result<Vec<Place>, ServiceError> {
+        let city = &query.name;
+        debug!("Fetching places for city: {city}");
+
+        let mut params = vec![
+            ("name", city.as_str()),
+            ("count", GEOCODING_RESULT_COUNT),
+            ("language", "en"),
+            ("format", "json"),
+        ];
+        if let Some(country) = &query.country {
+            params.push(("countryCode", country.as_str()));
+        }
+
+        let geo_data: GeoResponse = Self::get_json(
+            "Geocoding API",
+            &self.geocoding,
+            &params,
+            ServiceError::GeocodingError,
+        )
+        .await?;
+
         match geo_data.results {
             Some(results) if !results.is_empty() => {
-                info!("Found coordinates for {}: {:?}", city, results[0]);
-                Ok(results[0].clone())
+                info!("Found {} places for {city}", results.len());
+                Ok(results)
             }
             _ => {
-                warn!("No coordinates found for city: {}", city);
+                warn!("No coordinates found for city: {city}");
                 Err(ServiceError::CityNotFound(format!(
                     "No coordinates found for {city}"
                 )))
             }
         }
     }
+}
 
-    pub async fn fetch_weather(&self, coords: &LatLong) -> Result<WeatherData, ServiceError> {
+#[async_trait]
+impl WeatherProvider for WeatherService {
+    async fn fetch_weather(&self, forecast: &ForecastRequest) -> Result<WeatherData, ServiceError> {
+        let coords = &forecast.coords;
         debug!(
             "Fetching weather for coordinates: lat={}, lon={}",
             coords.latitude, coords.longitude
         );
 
-        let url = format!(
-            "{WEATHER_API_URL}?latitude={}&longitude={}&hourly=temperature_2m",
+        // Epoch seconds are unambiguous across DST changes; `timezone` still decides
+        // where daily aggregates start and end.
+        let mut params = vec![
+            ("latitude", coords.latitude.to_string()),
+            ("longitude", coords.longitude.to_string()),
+            ("hourly", forecast.hourly_param()),
+            ("timezone", forecast.timezone_param()),
+            ("timeformat", "unixtime".to_string()),
+            ("forecast_days", forecast.window.days.to_string()),
+            ("past_days", forecast.window.past_days.to_string()),
+        ];
+        if forecast.daily {
+            params.push(("daily", DAILY_VARIABLES.to_string()));
+        }
+        for (key, value) in forecast.units.open_meteo_params() {
+            params.push((key, value.to_string()));
+        }
+
+        let mut weather_data =
+            Self::get_hourly("Weather API", &self.forecast, &params, &forecast.variables).await?;
+        if forecast.units.converts_locally() {
+            weather_data.convert_metric(forecast.units);
+        }
+
+        info!("Successfully fetched weather data");
+        Ok(weather_data)
+    }
+}
+
+#[async_trait]
+impl AirQualityProvider for WeatherService {
+    async fn fetch_air_quality(
+        &self,
+        forecast: &ForecastRequest,
+    ) -> Result<AirQuality, ServiceError> {
+        let coords = &forecast.coords;
+        debug!(
+            "Fetching air quality for coordinates: lat={}, lon={}",
+            coords.latitude, coords.longitude
+        );
+
+        let params = [
+            ("latitude", coords.latitude.to_string()),
+            ("longitude", coords.longitude.to_string()),
+            ("current", AIR_QUALITY_VARIABLES.to_string()),
+            ("hourly", AIR_QUALITY_VARIABLES.to_string()),
+            ("timezone", forecast.timezone_param()),
+            ("timeformat", "unixtime".to_string()),
+            (
+                "forecast_days",
+                forecast.window.days.min(MAX_AIR_QUALITY_DAYS).to_string(),
+            ),
+            ("past_days", forecast.window.past_days.to_string()),
+        ];
+
+        let response: AirQualityResponse = Self::get_json(
+            "Air quality API",
+            &self.air_quality,
+            &params,
+            ServiceError::AirQualityError,
+        )
+        .await?;
+
+        info!("Successfully fetched air quality data");
+        Ok(response.into())
+    }
+}
+
+#[async_trait]
+impl MarineProvider for WeatherService {
+    async fn fetch_marine(&self, forecast: &ForecastRequest) -> Result<MarineData, ServiceError> {
+        let coords = &forecast.coords;
+        debug!(
+            "Fetching marine data for coordinates: lat={}, lon={}",
             coords.latitude, coords.longitude
         );
-        debug!("Weather API request: {}", url);
 
-        let response = self.client.get(&url).send().await.map_err(|e| {
-            error!("Weather API request failed: {}", e);
-            ServiceError::WeatherError(e.to_string())
-        })?;
+        let params = [
+            ("latitude", coords.latitude.to_string()),
+            ("longitude", coords.longitude.to_string()),
+            ("hourly", MARINE_VARIABLES.to_string()),
+            ("timezone", forecast.timezone_param()),
+            ("timeformat", "unixtime".to_string()),
+            ("forecast_days", forecast.window.days.to_string()),
+            ("past_days", forecast.window.past_days.to_string()),
+            // Use the nearest sea cell even when the place itself is on land.
+            ("cell_selection", "sea".to_string()),
+        ];
 
-        let weather_data = response.json().await.map_err(|e| {
-            error!("Failed to parse Weather API response: {}", e);
-            ServiceError::WeatherError(e.to_string())
-        })?;
+        let mut marine: MarineData = Self::get_json(
+            "Marine API",
+            &self.marine,
+            &params,
+            ServiceError::MarineError,
+        )
+        .await?;
+        marine.convert_metric(forecast.units);
 
-        info!("Successfully fetched weather data");
+        info!("Successfully fetched marine data");
+        Ok(marine)
+    }
+}
+
+#[async_trait]
+impl HistoryProvider for WeatherService {
+    async fn fetch_historical(
+        &self,
+        coords: &LatLong,
+        start: NaiveDate,
+        end: NaiveDate,
+        variables: &[HourlyVariable],
+    ) -> Result<WeatherData, ServiceError> {
+        debug!(
+            "Fetching history for coordinates: lat={}, lon={}, {start} to {end}",
+            coords.latitude, coords.longitude
+        );
+
+        let params = [
+            ("latitude", coords.latitude.to_string()),
+            ("longitude", coords.longitude.to_string()),
+            ("start_date", start.to_string()),
+            ("end_date", end.to_string()),
+            ("hourly", HourlyVariable::join(variables)),
+            ("timezone", "auto".to_string()),
+            ("timeformat", "unixtime".to_string()),
+        ];
+
+        let weather_data =
+            Self::get_hourly("Archive API", &self.archive, &params, variables).await?;
+
+        info!("Successfully fetched historical weather data");
         Ok(weather_data)
     }
 }
 
+#[async_trait]
+impl ReverseGeocoder for WeatherService {
+    async fn reverse_geocode(&self, coords: &LatLong) -> Result<NearbyPlace, ServiceError> {
+        let backend = self.reverse_geocoder.as_ref().ok_or_else(|| {
+            ServiceError::GeocodingError("Reverse geocoding is not configured".to_string())
+        })?;
+
+        backend.reverse_geocode(coords).await
+    }
+}
+
+/// Formats a non-success response using Open-Meteo's `reason` when the body carries one.
+async fn describe_error_response(response: reqwest::Response) -> String {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+
+    match serde_json::from_str::<ApiErrorBody>(&body) {
+        Ok(error) => format!("{status}: {}", error.reason),
+        Err(_) => format!("{status}: {}", body.trim()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::services::forecast::ForecastWindow;
+    use crate::services::places::PlaceQuery;
+    use crate::test_support::{
+        fast_upstream, MockOpenMeteo, StaticProvider, AIR_QUALITY_PATH, ARCHIVE_PATH,
+        FORECAST_PATH, GEOCODING_PATH, MARINE_PATH,
+    };
     use pretty_assertions::assert_eq;
     use test_case::test_case;
 
+    async fn setup() -> (MockOpenMeteo, WeatherService) {
+        let mock = MockOpenMeteo::start().await;
+        let service = WeatherService::new(&mock.config(), &fast_upstream());
+        (mock, service)
+    }
+
+    async fn fetch_coordinates(
+        service: &WeatherService,
+        city: &str,
+    ) -> Result<LatLong, ServiceError> {
+        let query = PlaceQuery::new(city, None, None)?;
+        let places = service.fetch_places(&query).await?;
+        Ok(places[0].coords())
+    }
+
     #[tokio::test]
     async fn test_fetch_coordinates_london() {
-        let service = WeatherService::new();
-        let result = service.fetch_coordinates("London").await;
+        let (_mock, service) = setup().await;
+        let result = fetch_coordinates(&service, "London").await;
 
         assert!(
             result.is_ok(),
@@ -155,8 +997,8 @@ mod tests {
     #[test_case("NewYork" ; "when querying NewYork")]
     #[tokio::test]
     async fn test_fetch_coordinates_major_cities(city: &str) {
-        let service = WeatherService::new();
-        let result = service.fetch_coordinates(city).await;
+        let (_mock, service) = setup().await;
+        let result = fetch_coordinates(&service, city).await;
 
         assert!(
             result.is_ok(),
@@ -170,10 +1012,37 @@ mod tests {
         );
     }
 
+    #[test_case("Kraków", 50.06143       ; "when querying a precomposed accent")]
+    #[test_case("Krako\u{301}w", 50.06143 ; "when querying a decomposed accent")]
+    #[test_case("Saint-Étienne", 45.43389 ; "when querying a hyphenated name")]
+    #[test_case("Port of Spain", 10.66668 ; "when querying a name with spaces")]
+    #[tokio::test]
+    async fn test_fetch_coordinates_encodes_name(city: &str, latitude: f32) {
+        let (_mock, service) = setup().await;
+        let coords = fetch_coordinates(&service, city).await.unwrap();
+
+        assert!((coords.latitude - latitude).abs() < f32::EPSILON);
+    }
+
+    #[test_case("Paris&count=10" ; "with an injected parameter")]
+    #[test_case("Paris#x"        ; "with a fragment")]
+    #[test_case("   "            ; "when blank")]
+    #[tokio::test]
+    async fn test_fetch_coordinates_rejects_invalid_input(city: &str) {
+        let (mock, service) = setup().await;
+        let result = fetch_coordinates(&service, city).await;
+
+        assert!(
+            matches!(result, Err(ServiceError::InvalidInput(_))),
+            "Expected InvalidInput error, got: {result:?}"
+        );
+        assert_eq!(mock.request_count().await, 0);
+    }
+
     #[tokio::test]
     async fn test_fetch_coordinates_invalid_city() {
-        let service = WeatherService::new();
-        let result = service.fetch_coordinates("ThisCityDoesNotExist123").await;
+        let (_mock, service) = setup().await;
+        let result = fetch_coordinates(&service, "ThisCityDoesNotExist123").await;
 
         match result {
             Err(ServiceError::CityNotFound(msg)) => {
@@ -188,21 +1057,506 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_fetch_weather() {
-        let service = WeatherService::new();
-        let coords = LatLong {
+    async fn test_fetch_places_returns_candidates_with_metadata() {
+        let (_mock, service) = setup().await;
+        let query = PlaceQuery::new("Springfield", None, None).unwrap();
+        let places = service.fetch_places(&query).await.unwrap();
+
+        assert_eq!(places.len(), 3);
+        let first = &places[0];
+        assert_eq!(first.country_code.as_deref(), Some("US"));
+        assert_eq!(first.admin1.as_deref(), Some("Illinois"));
+        assert_eq!(first.timezone.as_deref(), Some("America/Chicago"));
+        assert_eq!(first.population, Some(114_394));
+        assert!(first.elevation.is_some());
+        assert_eq!(first.region(), "Illinois, United States");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_places_forwards_country_filter() {
+        let (mock, service) = setup().await;
+        mock.respond_to_country("Paris", "US", "Texas").await;
+
+        let query = PlaceQuery::new("Paris", Some("us"), None).unwrap();
+        let places = service.fetch_places(&query).await.unwrap();
+
+        assert_eq!(places.len(), 1);
+        assert_eq!(places[0].admin1.as_deref(), Some("Texas"));
+    }
+
+    #[test]
+    fn test_distance_km() {
+        let london = LatLong {
             latitude: 51.5074,
             longitude: -0.1278,
         };
-        let result = service.fetch_weather(&coords).await;
+        let paris = LatLong {
+            latitude: 48.8566,
+            longitude: 2.3522,
+        };
+
+        let distance = london.distance_km(&paris);
+        assert!((340.0..345.0).contains(&distance), "distance {distance}");
+        assert!(london.distance_km(&london).abs() < f64::EPSILON);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_geocode_delegates_to_backend() {
+        let (_mock, service) = setup().await;
+        let coords = LatLong {
+            latitude: 48.86,
+            longitude: 2.35,
+        };
+
+        let result = service.reverse_geocode(&coords).await;
+        assert!(matches!(result, Err(ServiceError::GeocodingError(_))));
+
+        let service = service.with_reverse_geocoder(Arc::new(StaticProvider));
+        let nearby = service.reverse_geocode(&coords).await.unwrap();
+        assert_eq!(nearby.place.name, "Paris");
+    }
+
+    fn forecast_request(latitude: f32, longitude: f32) -> ForecastRequest {
+        ForecastRequest::new(
+            LatLong {
+                latitude,
+                longitude,
+            },
+            vec![HourlyVariable::Temperature2m],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather() {
+        let (_mock, service) = setup().await;
+        let result = service
+            .fetch_weather(&forecast_request(51.5074, -0.1278))
+            .await;
         assert!(result.is_ok());
 
         let weather = result.unwrap();
         assert!(!weather.hourly.time.is_empty());
-        assert!(!weather.hourly.temperature_2m.is_empty());
         assert_eq!(
-            weather.hourly.time.len(),
-            weather.hourly.temperature_2m.len()
+            weather
+                .hourly
+                .series(HourlyVariable::Temperature2m)
+                .map(<[_]>::len),
+            Some(weather.hourly.time.len())
+        );
+        assert_eq!(weather.hourly.temperature_range(), Some((7.4, 14.6)));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_requests_variables() {
+        let (mock, service) = setup().await;
+        let request = ForecastRequest::new(
+            LatLong {
+                latitude: 51.5074,
+                longitude: -0.1278,
+            },
+            HourlyVariable::ALL.to_vec(),
+        );
+
+        let weather = service.fetch_weather(&request).await.unwrap();
+
+        for variable in HourlyVariable::ALL {
+            assert!(
+                weather.hourly.series(variable).is_some(),
+                "missing {variable:?}"
+            );
+        }
+        assert_eq!(
+            weather.hourly.value(HourlyVariable::WeatherCode, 11),
+            Some(63.0)
+        );
+
+        assert_eq!(
+            mock.last_query_param("hourly").await,
+            Some(request.hourly_param())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_daily() {
+        let (mock, service) = setup().await;
+
+        service
+            .fetch_weather(&forecast_request(51.5074, -0.1278))
+            .await
+            .unwrap();
+        assert_eq!(mock.last_query_param("daily").await, None);
+        assert_eq!(
+            mock.last_query_param("timezone").await.as_deref(),
+            Some("auto")
+        );
+        assert_eq!(
+            mock.last_query_param("timeformat").await.as_deref(),
+            Some("unixtime")
+        );
+
+        let request = forecast_request(51.5074, -0.1278)
+            .with_daily()
+            .with_timezone(Some(Tz::Europe__London));
+        let weather = service.fetch_weather(&request).await.unwrap();
+        assert_eq!(
+            mock.last_query_param("daily").await.as_deref(),
+            Some(DAILY_VARIABLES)
+        );
+        assert_eq!(
+            mock.last_query_param("timezone").await.as_deref(),
+            Some("Europe/London")
+        );
+        assert_eq!(weather.timezone, Tz::Europe__London);
+
+        let daily = weather.daily.as_ref().unwrap();
+        assert_eq!(
+            weather.local(daily.time[0]).to_rfc3339(),
+            "2024-10-26T00:00:00+01:00"
+        );
+        assert_eq!(daily.precipitation_sum, Some(vec![Some(1.6)]));
+        assert_eq!(daily.temperature_2m_mean, None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_units() {
+        let (mock, service) = setup().await;
+
+        service
+            .fetch_weather(&forecast_request(51.5074, -0.1278).with_units(UnitSystem::Imperial))
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.last_query_param("temperature_unit").await.as_deref(),
+            Some("fahrenheit")
+        );
+        assert_eq!(
+            mock.last_query_param("wind_speed_unit").await.as_deref(),
+            Some("mph")
+        );
+        assert_eq!(
+            mock.last_query_param("precipitation_unit").await.as_deref(),
+            Some("inch")
+        );
+
+        // Open-Meteo has no kelvin: SI is fetched in celsius and converted here.
+        let weather = service
+            .fetch_weather(
+                &forecast_request(51.5074, -0.1278)
+                    .with_daily()
+                    .with_units(UnitSystem::Si),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.last_query_param("temperature_unit").await.as_deref(),
+            Some("celsius")
+        );
+        assert_eq!(weather.hourly.temperature_range(), Some((280.55, 287.75)));
+        let daily = weather.daily.unwrap();
+        assert_eq!(daily.temperature_2m_max, Some(vec![Some(287.75)]));
+        assert_eq!(daily.precipitation_sum, Some(vec![Some(1.6)]));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_window() {
+        let (mock, service) = setup().await;
+
+        service
+            .fetch_weather(
+                &forecast_request(51.5074, -0.1278).with_window(ForecastWindow {
+                    days: 14,
+                    past_days: 1,
+                }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            mock.last_query_param("forecast_days").await.as_deref(),
+            Some("14")
+        );
+        assert_eq!(
+            mock.last_query_param("past_days").await.as_deref(),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn test_keep_days_cuts_at_local_midnight() {
+        // Two hours on each of 26 and 27 October, Tokyo time.
+        let mut weather = WeatherData {
+            timezone: Tz::Asia__Tokyo,
+            hourly: serde_json::from_value(serde_json::json!({
+                "time": [1_729_918_800, 1_729_936_800, 1_729_958_400, 1_729_976_400],
+                "temperature_2m": [12.0, 14.0, 9.0, 7.0],
+            }))
+            .unwrap(),
+            daily: Some(DailyData {
+                time: vec![DateTime::UNIX_EPOCH; 2],
+                temperature_2m_max: Some(vec![Some(14.0), Some(9.0)]),
+                sunrise: Some(vec![None, None]),
+                ..DailyData::default()
+            }),
+            provider: ProviderKind::OpenMeteo,
+            updated_at: None,
+            stale_as_of: None,
+            expires_at: None,
+        };
+
+        weather.keep_days(1);
+
+        assert_eq!(weather.hourly.time.len(), 2);
+        assert_eq!(weather.hourly.temperature_range(), Some((12.0, 14.0)));
+        let daily = weather.daily.unwrap();
+        assert_eq!(daily.temperature_2m_max, Some(vec![Some(14.0)]));
+        assert_eq!(daily.sunrise.map(|s| s.len()), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_missing_series() {
+        let (mock, service) = setup().await;
+        mock.respond_raw(
+            FORECAST_PATH,
+            r#"{"timezone": "GMT", "hourly": {"time": [1729900800], "temperature_2m": [7.4]}}"#,
+        )
+        .await;
+        let mut request = forecast_request(51.5074, -0.1278);
+        request.variables.push(HourlyVariable::CloudCover);
+
+        match service.fetch_weather(&request).await {
+            Err(ServiceError::WeatherError(msg)) => assert!(msg.contains("cloud_cover"), "{msg}"),
+            other => panic!("Expected WeatherError, got: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_times_are_placed_in_the_forecast_zone() {
+        let weather: WeatherData = serde_json::from_str(
+            r#"{"timezone": "America/New_York", "hourly": {"time": [1730610000, 1730613600]}}"#,
+        )
+        .unwrap();
+
+        // New York leaves daylight saving time at 2024-11-03T06:00Z.
+        let local: Vec<_> = weather
+            .hourly
+            .time
+            .iter()
+            .map(|&time| weather.local(time).to_rfc3339())
+            .collect();
+        assert_eq!(
+            local,
+            ["2024-11-03T01:00:00-04:00", "2024-11-03T01:00:00-05:00"]
+        );
+
+        let result = serde_json::from_str::<WeatherData>(
+            r#"{"timezone": "Mars/Olympus", "hourly": {"time": []}}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_hourly_data_tolerates_null_values() {
+        let hourly: HourlyData = serde_json::from_str(
+            r#"{"time": [0, 3600, 7200], "temperature_2m": [3.5, null, -1.0]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(hourly.value(HourlyVariable::Temperature2m, 1), None);
+        assert_eq!(hourly.value(HourlyVariable::Precipitation, 0), None);
+        assert_eq!(hourly.temperature_range(), Some((-1.0, 3.5)));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_reports_upstream_reason() {
+        let (mock, service) = setup().await;
+        mock.fail_with(FORECAST_PATH, 400).await;
+
+        match service.fetch_weather(&forecast_request(100.0, 0.0)).await {
+            Err(ServiceError::WeatherError(msg)) => {
+                assert!(msg.starts_with("400"), "unexpected message: {msg}");
+                assert!(
+                    msg.contains("Latitude must be in range"),
+                    "unexpected message: {msg}"
+                );
+            }
+            other => panic!("Expected WeatherError, got: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_air_quality() {
+        let (mock, service) = setup().await;
+        let request = forecast_request(51.5074, -0.1278).with_window(ForecastWindow {
+            days: 14,
+            past_days: 1,
+        });
+
+        let air_quality = service.fetch_air_quality(&request).await.unwrap();
+
+        assert_eq!(
+            mock.last_query_param("hourly").await.as_deref(),
+            Some(AIR_QUALITY_VARIABLES)
+        );
+        assert_eq!(
+            mock.last_query_param("current").await.as_deref(),
+            Some(AIR_QUALITY_VARIABLES)
+        );
+        // The air-quality API forecasts at most seven days ahead.
+        assert_eq!(
+            mock.last_query_param("forecast_days").await.as_deref(),
+            Some("7")
+        );
+        assert_eq!(
+            mock.last_query_param("past_days").await.as_deref(),
+            Some("1")
+        );
+        let current = air_quality.current.unwrap();
+        assert_eq!(current.time.to_rfc3339(), "2024-10-26T12:00:00+00:00");
+        assert_eq!(current.values.european_aqi, Some(36.0));
+        assert_eq!(current.values.us_aqi, Some(48.0));
+        assert_eq!(current.values.uv_index, Some(1.9));
+        assert_eq!(air_quality.hourly.len(), 24);
+        assert_eq!(air_quality.hourly[12].values.ozone, Some(67.5));
+
+        mock.fail_with(AIR_QUALITY_PATH, 503).await;
+        assert!(matches!(
+            service.fetch_air_quality(&request).await,
+            Err(ServiceError::AirQualityError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_marine() {
+        let (mock, service) = setup().await;
+        let london = LatLong {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        let request =
+            forecast_request(london.latitude, london.longitude).with_units(UnitSystem::Imperial);
+
+        let marine = service.fetch_marine(&request).await.unwrap();
+
+        assert_eq!(
+            mock.last_query_param("hourly").await.as_deref(),
+            Some(MARINE_VARIABLES)
+        );
+        assert_eq!(
+            mock.last_query_param("cell_selection").await.as_deref(),
+            Some("sea")
+        );
+        assert_eq!(marine.hourly.time.len(), 24);
+        // The nearest sea cell is the Thames estuary, too far for London to count as coastal.
+        assert!((marine.distance_km(&london) - 69.5).abs() < 0.5);
+        assert!(!marine.is_coastal(&london));
+        assert!(marine_for(&service, &request, false)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(marine_for(&service, &request, true)
+            .await
+            .unwrap()
+            .is_some());
+        // Swell direction is missing for the second half of the day.
+        assert_eq!(
+            MarineHourly::value(marine.hourly.swell_wave_direction.as_ref(), 18),
+            None
+        );
+
+        mock.fail_with(MARINE_PATH, 503).await;
+        assert!(matches!(
+            service.fetch_marine(&request).await,
+            Err(ServiceError::MarineError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_historical() {
+        let (mock, service) = setup().await;
+        let berlin = LatLong {
+            latitude: 52.52437,
+            longitude: 13.41053,
+        };
+        let day = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+
+        let weather = service
+            .fetch_historical(
+                &berlin,
+                day,
+                day,
+                &[HourlyVariable::Temperature2m, HourlyVariable::Precipitation],
+            )
+            .await
+            .unwrap();
+
+        for (key, value) in [
+            ("start_date", "2024-03-01"),
+            ("end_date", "2024-03-01"),
+            ("hourly", "temperature_2m,precipitation"),
+            ("timezone", "auto"),
+            ("timeformat", "unixtime"),
+        ] {
+            assert_eq!(mock.last_query_param(key).await.as_deref(), Some(value));
+        }
+        assert_eq!(weather.timezone, Tz::Europe__Berlin);
+        assert_eq!(weather.hourly.time.len(), 24);
+        assert_eq!(
+            weather.local(weather.hourly.time[0]).to_rfc3339(),
+            "2024-03-01T00:00:00+01:00"
+        );
+        assert_eq!(weather.hourly.temperature_range(), Some((0.8, 8.6)));
+
+        // The archive has no precipitation probability.
+        let result = service
+            .fetch_historical(
+                &berlin,
+                day,
+                day,
+                &[HourlyVariable::PrecipitationProbability],
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::WeatherError(_))));
+
+        mock.fail_with(ARCHIVE_PATH, 400).await;
+        match service
+            .fetch_historical(&berlin, day, day, &[HourlyVariable::Temperature2m])
+            .await
+        {
+            Err(ServiceError::WeatherError(msg)) => assert!(msg.starts_with("400"), "{msg}"),
+            other => panic!("Expected WeatherError, got: {other:?}"),
+        }
+    }
+
+    #[test_case(500 ; "when the upstream fails")]
+    #[test_case(503 ; "when the upstream is unavailable")]
+    #[tokio::test]
+    async fn test_fetch_coordinates_server_error(status: u16) {
+        let (mock, service) = setup().await;
+        mock.fail_with(GEOCODING_PATH, status).await;
+
+        match fetch_coordinates(&service, "London").await {
+            Err(ServiceError::GeocodingError(msg)) => {
+                assert!(
+                    msg.starts_with(&status.to_string()),
+                    "unexpected message: {msg}"
+                );
+            }
+            other => panic!("Expected GeocodingError, got: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_weather_malformed_body() {
+        let (mock, service) = setup().await;
+        mock.respond_raw(FORECAST_PATH, r#"{"hourly": {"time": []}}"#)
+            .await;
+
+        let result = service
+            .fetch_weather(&forecast_request(51.5074, -0.1278))
+            .await;
+        assert!(
+            matches!(result, Err(ServiceError::WeatherError(_))),
+            "Expected WeatherError, got: {result:?}"
         );
     }
 }
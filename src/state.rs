@@ -0,0 +1,99 @@
+use crate::config::AppConfig;
+use crate::repositories::CacheRepository;
+use crate::services::cache::{
+    CachedAirQualityProvider, CachedGeocoder, CachedMarineProvider, CachedWeatherProvider,
+};
+use crate::services::failover::FailoverProvider;
+use crate::services::met_norway::MetNorwayService;
+use crate::services::reverse_geocoder::OfflineReverseGeocoder;
+use crate::services::upstream::CircuitBreaker;
+use crate::services::weather_service::WeatherService;
+use crate::services::{
+    AirQualityProvider, Geocoder, HistoryProvider, MarineProvider, ProviderKind, ReverseGeocoder,
+    WeatherProvider,
+};
+use axum::extract::FromRef;
+use log::error;
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+
+/// Shared state handed to every handler through axum's `State` extractor.
+#[derive(Clone)]
+pub struct AppState {
+    pub db: DatabaseConnection,
+    pub geocoder: Arc<dyn Geocoder>,
+    pub reverse_geocoder: Arc<dyn ReverseGeocoder>,
+    pub weather: Arc<dyn WeatherProvider>,
+    pub history: Arc<dyn HistoryProvider>,
+    pub air_quality: Arc<dyn AirQualityProvider>,
+    pub marine: Arc<dyn MarineProvider>,
+    /// Circuit breakers of every upstream API, for the health endpoint.
+    pub breakers: Vec<Arc<CircuitBreaker>>,
+}
+
+impl AppState {
+    /// Builds the production state backed by the Open-Meteo APIs (forecast, archive, air
+    /// quality, marine and geocoding) and the local gazetteer.
+    ///
+    /// Forecasts come from the configured providers in priority order, failing over
+    /// between Open-Meteo and MET Norway. Geocoding results and forecasts are cached in
+    /// memory and in the database in front of them, and which places are coastal in
+    /// memory.
+    pub fn new(db: DatabaseConnection, config: &AppConfig) -> Self {
+        let offline = Arc::new(OfflineReverseGeocoder::new(db.clone()));
+        let service = Arc::new(
+            WeatherService::new(&config.open_meteo, &config.upstream)
+                .with_reverse_geocoder(offline),
+        );
+        let mut breakers = service.breakers();
+        let providers = config
+            .weather
+            .providers
+            .iter()
+            .filter_map(|&kind| {
+                let provider: Arc<dyn WeatherProvider> = match kind {
+                    ProviderKind::OpenMeteo => service.clone(),
+                    ProviderKind::MetNorway => {
+                        match MetNorwayService::new(&config.met_norway, &config.upstream) {
+                            Ok(met_norway) => {
+                                breakers.push(met_norway.breaker());
+                                Arc::new(met_norway)
+                            }
+                            Err(err) => {
+                                error!("MET Norway is disabled: {err}");
+                                return None;
+                            }
+                        }
+                    }
+                };
+                Some((kind, provider))
+            })
+            .collect();
+        let weather = FailoverProvider::new(providers, config.weather.provider_timeout());
+        let cache = CacheRepository::new(db.clone());
+
+        Self {
+            geocoder: Arc::new(
+                CachedGeocoder::new(service.clone(), &config.cache).with_repository(cache.clone()),
+            ),
+            reverse_geocoder: service.clone(),
+            weather: Arc::new(
+                CachedWeatherProvider::new(Arc::new(weather), &config.cache).with_repository(cache),
+            ),
+            history: service.clone(),
+            air_quality: Arc::new(CachedAirQualityProvider::new(
+                service.clone(),
+                &config.cache,
+            )),
+            marine: Arc::new(CachedMarineProvider::new(service, &config.cache)),
+            breakers,
+            db,
+        }
+    }
+}
+
+impl FromRef<AppState> for DatabaseConnection {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
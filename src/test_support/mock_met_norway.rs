@@ -0,0 +1,73 @@
+use crate::config::MetNorwayConfig;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const MET_NORWAY_PATH: &str = "/weatherapi/locationforecast/2.0/compact";
+
+const FORECAST_FIXTURE: &str = include_str!("fixtures/met_norway.json");
+
+/// Local stand-in for MET Norway's locationforecast API.
+///
+/// Every request is answered with `fixtures/met_norway.json`: 24 hourly steps over
+/// 2024-10-26 UTC for London, then two six-hourly ones.
+pub struct MockMetNorway {
+    server: MockServer,
+}
+
+impl MockMetNorway {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(MET_NORWAY_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(FORECAST_FIXTURE, "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        Self { server }
+    }
+
+    pub fn config(&self) -> MetNorwayConfig {
+        MetNorwayConfig {
+            forecast_url: format!("{}{MET_NORWAY_PATH}", self.server.uri()),
+            user_agent: "forecast-rust-tests".to_string(),
+        }
+    }
+
+    /// Makes every request to `endpoint_path` fail with `status` and a plain-text body.
+    pub async fn fail_with(&self, endpoint_path: &str, status: u16) {
+        Mock::given(path(endpoint_path))
+            .respond_with(ResponseTemplate::new(status).set_body_string("upstream unavailable"))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Number of requests the stand-in has received so far.
+    pub async fn request_count(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .map_or(0, |requests| requests.len())
+    }
+
+    /// Value of query parameter `key` on the most recent request, if any.
+    pub async fn last_query_param(&self, key: &str) -> Option<String> {
+        let requests = self.server.received_requests().await?;
+        requests
+            .last()?
+            .url
+            .query_pairs()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// `User-Agent` of the most recent request, which MET Norway requires.
+    pub async fn last_user_agent(&self) -> Option<String> {
+        let requests = self.server.received_requests().await?;
+        let value = requests.last()?.headers.get("user-agent")?;
+        value.to_str().ok().map(str::to_string)
+    }
+}
@@ -0,0 +1,225 @@
+use crate::config::{AppConfig, OpenMeteoConfig, WeatherConfig};
+use crate::services::ProviderKind;
+use serde_json::Value;
+use std::time::Duration;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const GEOCODING_PATH: &str = "/v1/search";
+pub const FORECAST_PATH: &str = "/v1/forecast";
+pub const ARCHIVE_PATH: &str = "/v1/archive";
+pub const AIR_QUALITY_PATH: &str = "/v1/air-quality";
+pub const MARINE_PATH: &str = "/v1/marine";
+
+const GEOCODING_FIXTURES: &str = include_str!("fixtures/geocoding.json");
+const FORECAST_FIXTURE: &str = include_str!("fixtures/forecast.json");
+const ARCHIVE_FIXTURE: &str = include_str!("fixtures/archive.json");
+const AIR_QUALITY_FIXTURE: &str = include_str!("fixtures/air_quality.json");
+const MARINE_FIXTURE: &str = include_str!("fixtures/marine.json");
+const ERROR_FIXTURE: &str = include_str!("fixtures/error_invalid_coordinates.json");
+
+/// Local stand-in for the Open-Meteo geocoding, forecast, archive, air-quality and marine
+/// APIs.
+///
+/// Cities listed in `fixtures/geocoding.json` resolve to their canned result; any other
+/// name gets the empty body Open-Meteo returns for unknown places. Every forecast
+/// request is answered with `fixtures/forecast.json`, every archive request with the
+/// single Berlin day in `fixtures/archive.json`, every air-quality request with
+/// `fixtures/air_quality.json`, and every marine request with the Thames estuary cell in
+/// `fixtures/marine.json` (about 70 km from London, so not coastal).
+pub struct MockOpenMeteo {
+    server: MockServer,
+}
+
+impl MockOpenMeteo {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+
+        let fixtures: serde_json::Map<String, Value> =
+            serde_json::from_str(GEOCODING_FIXTURES).expect("Invalid geocoding fixture");
+        for (name, body) in fixtures {
+            Mock::given(method("GET"))
+                .and(path(GEOCODING_PATH))
+                .and(query_param("name", name.as_str()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(body))
+                .mount(&server)
+                .await;
+        }
+
+        Mock::given(method("GET"))
+            .and(path(GEOCODING_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "generationtime_ms": 0.4
+            })))
+            .with_priority(u8::MAX)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(FORECAST_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(FORECAST_FIXTURE, "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(ARCHIVE_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(ARCHIVE_FIXTURE, "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(AIR_QUALITY_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(AIR_QUALITY_FIXTURE, "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path(MARINE_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw(MARINE_FIXTURE, "application/json"),
+            )
+            .mount(&server)
+            .await;
+
+        Self { server }
+    }
+
+    pub fn config(&self) -> OpenMeteoConfig {
+        OpenMeteoConfig {
+            geocoding_url: format!("{}{GEOCODING_PATH}", self.server.uri()),
+            forecast_url: format!("{}{FORECAST_PATH}", self.server.uri()),
+            archive_url: format!("{}{ARCHIVE_PATH}", self.server.uri()),
+            air_quality_url: format!("{}{AIR_QUALITY_PATH}", self.server.uri()),
+            marine_url: format!("{}{MARINE_PATH}", self.server.uri()),
+        }
+    }
+
+    /// An app configuration served by this stand-in alone, so failed forecasts never fall
+    /// back to the real MET Norway.
+    pub fn app_config(&self) -> AppConfig {
+        AppConfig {
+            open_meteo: self.config(),
+            weather: WeatherConfig {
+                providers: vec![ProviderKind::OpenMeteo],
+                ..WeatherConfig::default()
+            },
+            upstream: super::fast_upstream(),
+            ..AppConfig::default()
+        }
+    }
+
+    /// Makes every request to `endpoint_path` fail with `status`.
+    ///
+    /// 4xx statuses carry Open-Meteo's `{"error": true, "reason": ...}` body, 5xx ones a
+    /// plain-text body as returned by the fronting proxy.
+    pub async fn fail_with(&self, endpoint_path: &str, status: u16) {
+        let response = if status < 500 {
+            ResponseTemplate::new(status).set_body_raw(ERROR_FIXTURE, "application/json")
+        } else {
+            ResponseTemplate::new(status).set_body_string("upstream unavailable")
+        };
+
+        Mock::given(path(endpoint_path))
+            .respond_with(response)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers searches for `name` carrying `countryCode=<country>` with only the fixture
+    /// entry in `admin1`, mimicking Open-Meteo's server-side country filter.
+    pub async fn respond_to_country(&self, name: &str, country: &str, admin1: &str) {
+        let fixtures: serde_json::Map<String, Value> =
+            serde_json::from_str(GEOCODING_FIXTURES).expect("Invalid geocoding fixture");
+        let results: Vec<Value> = fixtures[name]["results"]
+            .as_array()
+            .expect("Unknown geocoding fixture")
+            .iter()
+            .filter(|place| place["admin1"] == admin1)
+            .cloned()
+            .collect();
+
+        Mock::given(method("GET"))
+            .and(path(GEOCODING_PATH))
+            .and(query_param("name", name))
+            .and(query_param("countryCode", country))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "results": results
+            })))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers forecast requests with the usual fixture, but only after `delay`.
+    pub async fn delay_forecasts(&self, delay: Duration) {
+        Mock::given(path(FORECAST_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(FORECAST_FIXTURE, "application/json")
+                    .set_delay(delay),
+            )
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers the next forecast request only after `delay`; later ones get the usual
+    /// fixture straight away.
+    pub async fn delay_next_forecast(&self, delay: Duration) {
+        Mock::given(path(FORECAST_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(FORECAST_FIXTURE, "application/json")
+                    .set_delay(delay),
+            )
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Number of requests to `endpoint_path` the stand-in has received so far.
+    pub async fn requests_to(&self, endpoint_path: &str) -> usize {
+        self.server.received_requests().await.map_or(0, |requests| {
+            requests
+                .iter()
+                .filter(|request| request.url.path() == endpoint_path)
+                .count()
+        })
+    }
+
+    /// Number of requests the stand-in has received so far.
+    pub async fn request_count(&self) -> usize {
+        self.server
+            .received_requests()
+            .await
+            .map_or(0, |requests| requests.len())
+    }
+
+    /// Value of query parameter `key` on the most recent request, if any.
+    pub async fn last_query_param(&self, key: &str) -> Option<String> {
+        let requests = self.server.received_requests().await?;
+        requests
+            .last()?
+            .url
+            .query_pairs()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Returns `body` verbatim with a 200 status for every request to `endpoint_path`.
+    pub async fn respond_raw(&self, endpoint_path: &str, body: &str) {
+        Mock::given(path(endpoint_path))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+            .with_priority(1)
+            .mount(&self.server)
+            .await;
+    }
+}
@@ -0,0 +1,451 @@
+//! Helpers shared by the test modules.
+
+use crate::config::UpstreamConfig;
+use crate::services::air_quality::{
+    AirQuality, AirQualityReading, AirQualityValues, MAX_AIR_QUALITY_DAYS,
+};
+use crate::services::forecast::{ForecastRequest, HourlyVariable, UnitSystem};
+use crate::services::places::PlaceQuery;
+use crate::services::reverse_geocoder::NearbyPlace;
+use crate::services::weather_service::{
+    LatLong, MarineData, MarineHourly, Place, ServiceError, WeatherData,
+};
+use crate::services::{
+    AirQualityProvider, Geocoder, HistoryProvider, MarineProvider, ProviderKind, ReverseGeocoder,
+    WeatherProvider,
+};
+use crate::state::AppState;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use sea_orm::{Database, DatabaseConnection};
+use sea_orm_migration::MigratorTrait;
+use std::sync::Arc;
+
+mod mock_met_norway;
+mod mock_open_meteo;
+
+pub use mock_met_norway::{MockMetNorway, MET_NORWAY_PATH};
+pub use mock_open_meteo::{
+    MockOpenMeteo, AIR_QUALITY_PATH, ARCHIVE_PATH, FORECAST_PATH, GEOCODING_PATH, MARINE_PATH,
+};
+
+pub async fn setup_test_db() -> DatabaseConnection {
+    let db = Database::connect("sqlite::memory:")
+        .await
+        .expect("Failed to create test database");
+
+    migration::Migrator::up(&db, None)
+        .await
+        .expect("Failed to run migrations");
+
+    db
+}
+
+/// Builds an `AppState` over a fresh in-memory database and the deterministic `StaticProvider`.
+pub async fn test_state() -> AppState {
+    let provider = Arc::new(StaticProvider);
+
+    AppState {
+        db: setup_test_db().await,
+        geocoder: provider.clone(),
+        reverse_geocoder: provider.clone(),
+        weather: provider.clone(),
+        history: provider.clone(),
+        air_quality: provider.clone(),
+        marine: provider,
+        breakers: Vec::new(),
+    }
+}
+
+/// Upstream settings for tests against the stand-ins: retries and timeouts as in
+/// production, but with millisecond backoff.
+pub fn fast_upstream() -> UpstreamConfig {
+    UpstreamConfig {
+        retry_base_delay_ms: 1,
+        retry_max_delay_ms: 10,
+        ..UpstreamConfig::default()
+    }
+}
+
+/// `(name, latitude, longitude, country code, admin1, population, timezone)`
+type PlaceRow = (
+    &'static str,
+    f32,
+    f32,
+    &'static str,
+    &'static str,
+    u64,
+    &'static str,
+);
+
+/// In-process provider returning fixed places and the same 24 ramped hours for every
+/// day of the requested window or date range.
+pub struct StaticProvider;
+
+impl StaticProvider {
+    /// Hours generated per forecast day.
+    pub const HOURS: u32 = 24;
+
+    const PLACES: &'static [PlaceRow] = &[
+        (
+            "London",
+            51.508_53,
+            -0.125_74,
+            "GB",
+            "England",
+            7_556_900,
+            "Europe/London",
+        ),
+        (
+            "London",
+            42.983_39,
+            -81.233_04,
+            "CA",
+            "Ontario",
+            346_765,
+            "America/Toronto",
+        ),
+        (
+            "Brighton",
+            50.828_38,
+            -0.139_47,
+            "GB",
+            "England",
+            139_001,
+            "Europe/London",
+        ),
+        (
+            "Paris",
+            48.853_41,
+            2.3488,
+            "FR",
+            "Île-de-France",
+            2_138_551,
+            "Europe/Paris",
+        ),
+        (
+            "Paris",
+            33.660_94,
+            -95.555_51,
+            "US",
+            "Texas",
+            24_171,
+            "America/Chicago",
+        ),
+        (
+            "Berlin",
+            52.524_37,
+            13.410_53,
+            "DE",
+            "Berlin",
+            3_426_354,
+            "Europe/Berlin",
+        ),
+        (
+            "Springfield",
+            37.215_33,
+            -93.298_24,
+            "US",
+            "Missouri",
+            169_176,
+            "America/Chicago",
+        ),
+        (
+            "Springfield",
+            42.101_48,
+            -72.589_81,
+            "US",
+            "Massachusetts",
+            155_929,
+            "America/New_York",
+        ),
+        (
+            "Springfield",
+            39.801_72,
+            -89.643_71,
+            "US",
+            "Illinois",
+            114_394,
+            "America/Chicago",
+        ),
+    ];
+
+    /// Sea grid cells for marine lookups: the English Channel off Brighton, and the Atlantic.
+    const SEA_CELLS: &'static [(f32, f32)] = &[(50.8, -0.125), (45.0, -30.0)];
+
+    /// Coordinate lookups within this distance of a known place are labelled with its name.
+    pub const REVERSE_RADIUS_KM: f64 = 25.0;
+
+    /// Temperature climbs 5.0 + 0.5/h; rain falls only in the afternoon.
+    fn hourly_value(variable: HourlyVariable, hour: f64) -> f64 {
+        let afternoon = (12.0..18.0).contains(&hour);
+        match variable {
+            HourlyVariable::Temperature2m => 5.0 + hour * 0.5,
+            HourlyVariable::RelativeHumidity2m => 60.0 + hour,
+            HourlyVariable::Precipitation => {
+                if afternoon {
+                    1.2
+                } else {
+                    0.0
+                }
+            }
+            HourlyVariable::PrecipitationProbability => {
+                if afternoon {
+                    80.0
+                } else {
+                    10.0
+                }
+            }
+            HourlyVariable::WindSpeed10m => 10.0 + hour,
+            HourlyVariable::WindDirection10m => 270.0,
+            HourlyVariable::WindGusts10m => 20.0 + hour,
+            HourlyVariable::CloudCover => {
+                if afternoon {
+                    100.0
+                } else {
+                    25.0
+                }
+            }
+            HourlyVariable::WeatherCode => {
+                if afternoon {
+                    61.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+
+    /// "Today" is 2024-10-26; a forecast starts `past_days` before it.
+    fn first_day(request: &ForecastRequest) -> NaiveDate {
+        let today = NaiveDate::from_ymd_opt(2024, 10, 26).expect("valid date");
+        today - chrono::Duration::days(i64::from(request.window.past_days))
+    }
+
+    /// Epoch seconds of `days` days of hours, each day 24 hours from local midnight.
+    fn times(timezone: Tz, first_day: NaiveDate, days: usize) -> Vec<i64> {
+        first_day
+            .iter_days()
+            .take(days)
+            .map(|day| {
+                day.and_hms_opt(0, 0, 0)
+                    .and_then(|time| timezone.from_local_datetime(&time).earliest())
+                    .expect("every test day has a local midnight")
+                    .timestamp()
+            })
+            .flat_map(|midnight| {
+                (0..Self::HOURS).map(move |hour| midnight + i64::from(hour) * 3600)
+            })
+            .collect()
+    }
+
+    /// Fair European, moderate US air all day; UV peaks at 3 around noon.
+    fn air_quality_values(hour: f64) -> AirQualityValues {
+        AirQualityValues {
+            pm2_5: Some(12.5),
+            pm10: Some(20.0),
+            ozone: Some(60.0),
+            european_aqi: Some(32.0),
+            us_aqi: Some(58.0),
+            uv_index: Some((3.0 - (hour - 12.0).abs() * 0.5).max(0.0)),
+        }
+    }
+
+    /// `days` identical days of ramped values, each 24 hours from local midnight.
+    fn weather(
+        timezone: Tz,
+        first_day: NaiveDate,
+        days: usize,
+        variables: &[HourlyVariable],
+        units: UnitSystem,
+    ) -> Result<WeatherData, ServiceError> {
+        let time = Self::times(timezone, first_day, days);
+        let mut hourly = serde_json::Map::new();
+        hourly.insert("time".to_string(), time.clone().into());
+
+        for &variable in variables {
+            let series: Vec<f64> = (0..time.len())
+                .map(|index| {
+                    let hour = f64::from(u32::try_from(index).unwrap() % Self::HOURS);
+                    let metric = Self::hourly_value(variable, hour);
+                    units.convert_metric(variable, metric)
+                })
+                .collect();
+            hourly.insert(variable.as_str().to_string(), series.into());
+        }
+
+        Ok(WeatherData {
+            timezone,
+            hourly: serde_json::from_value(hourly.into())?,
+            // No daily aggregates, so callers exercise the local fallback.
+            daily: None,
+            provider: ProviderKind::OpenMeteo,
+            // A fixed model run, so responses and their validators are reproducible.
+            updated_at: Utc.with_ymd_and_hms(2024, 10, 25, 23, 0, 0).single(),
+            stale_as_of: None,
+            expires_at: None,
+        })
+    }
+
+    fn places() -> impl Iterator<Item = Place> {
+        Self::PLACES.iter().map(
+            |&(name, latitude, longitude, code, admin1, population, timezone)| Place {
+                name: name.to_string(),
+                latitude,
+                longitude,
+                country_code: Some(code.to_string()),
+                country: None,
+                admin1: Some(admin1.to_string()),
+                timezone: Some(timezone.to_string()),
+                population: Some(population),
+                elevation: None,
+            },
+        )
+    }
+
+    fn lookup(query: &PlaceQuery) -> Vec<Place> {
+        Self::places()
+            .filter(|place| place.name.eq_ignore_ascii_case(&query.name))
+            .filter(|place| query.country.is_none() || place.country_code == query.country)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Geocoder for StaticProvider {
+    async fn fetch_places(&self, query: &PlaceQuery) -> Result<Vec<Place>, ServiceError> {
+        let places = Self::lookup(query);
+
+        if places.is_empty() {
+            return Err(ServiceError::CityNotFound(format!(
+                "No coordinates found for {}",
+                query.name
+            )));
+        }
+
+        Ok(places)
+    }
+}
+
+#[async_trait]
+impl ReverseGeocoder for StaticProvider {
+    async fn reverse_geocode(&self, coords: &LatLong) -> Result<NearbyPlace, ServiceError> {
+        Self::places()
+            .map(|place| NearbyPlace {
+                distance_km: coords.distance_km(&place.coords()),
+                place,
+            })
+            .filter(|nearby| nearby.distance_km <= Self::REVERSE_RADIUS_KM)
+            .min_by(|a, b| a.distance_km.total_cmp(&b.distance_km))
+            .ok_or_else(|| ServiceError::CityNotFound("No known place nearby".to_string()))
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for StaticProvider {
+    async fn fetch_weather(&self, request: &ForecastRequest) -> Result<WeatherData, ServiceError> {
+        Self::weather(
+            request.timezone.unwrap_or(Tz::UTC),
+            Self::first_day(request),
+            request.window.total_days(),
+            &request.variables,
+            request.units,
+        )
+    }
+}
+
+#[async_trait]
+impl AirQualityProvider for StaticProvider {
+    async fn fetch_air_quality(
+        &self,
+        request: &ForecastRequest,
+    ) -> Result<AirQuality, ServiceError> {
+        let timezone = request.timezone.unwrap_or(Tz::UTC);
+        // Like upstream, the air-quality horizon is shorter than the weather one.
+        let window = request.window;
+        let days = window.past_days + window.days.min(MAX_AIR_QUALITY_DAYS);
+        let times = Self::times(timezone, Self::first_day(request), days as usize);
+        let hourly: Vec<AirQualityReading> = times
+            .iter()
+            .enumerate()
+            .map(|(index, &time)| AirQualityReading {
+                time: DateTime::from_timestamp(time, 0).expect("valid timestamp"),
+                values: Self::air_quality_values(f64::from(
+                    u32::try_from(index).unwrap() % Self::HOURS,
+                )),
+            })
+            .collect();
+        // "Now" is noon today.
+        let noon = (window.past_days * Self::HOURS + 12) as usize;
+
+        Ok(AirQuality {
+            current: hourly.get(noon).cloned(),
+            hourly,
+        })
+    }
+}
+
+#[async_trait]
+impl MarineProvider for StaticProvider {
+    async fn fetch_marine(&self, request: &ForecastRequest) -> Result<MarineData, ServiceError> {
+        let coords = &request.coords;
+        let &(latitude, longitude) = Self::SEA_CELLS
+            .iter()
+            .min_by(|a, b| {
+                let distance = |&&(latitude, longitude): &&(f32, f32)| {
+                    coords.distance_km(&LatLong {
+                        latitude,
+                        longitude,
+                    })
+                };
+                distance(a).total_cmp(&distance(b))
+            })
+            .expect("at least one sea cell");
+        let time: Vec<DateTime<Utc>> = Self::times(
+            request.timezone.unwrap_or(Tz::UTC),
+            Self::first_day(request),
+            request.window.total_days(),
+        )
+        .into_iter()
+        .map(|time| DateTime::from_timestamp(time, 0).expect("valid timestamp"))
+        .collect();
+        // Waves build from 1.0 m by 5 cm an hour each day.
+        let hours =
+            (0..time.len()).map(|index| f64::from(u32::try_from(index).unwrap() % Self::HOURS));
+
+        let mut marine = MarineData {
+            latitude,
+            longitude,
+            hourly: MarineHourly {
+                wave_height: Some(hours.clone().map(|hour| Some(1.0 + hour * 0.05)).collect()),
+                wave_period: Some(vec![Some(6.5); time.len()]),
+                swell_wave_direction: Some(vec![Some(225.0); time.len()]),
+                sea_surface_temperature: Some(vec![Some(14.5); time.len()]),
+                time,
+            },
+        };
+        marine.convert_metric(request.units);
+        Ok(marine)
+    }
+}
+
+#[async_trait]
+impl HistoryProvider for StaticProvider {
+    async fn fetch_historical(
+        &self,
+        coords: &LatLong,
+        start: NaiveDate,
+        end: NaiveDate,
+        variables: &[HourlyVariable],
+    ) -> Result<WeatherData, ServiceError> {
+        // Known places report in their own zone, like `timezone=auto` upstream.
+        let timezone = Self::places()
+            .find(|place| place.coords().distance_km(coords) <= Self::REVERSE_RADIUS_KM)
+            .and_then(|place| place.tz())
+            .unwrap_or(Tz::UTC);
+        let days = usize::try_from((end - start).num_days() + 1).unwrap();
+
+        Self::weather(timezone, start, days, variables, UnitSystem::Metric)
+    }
+}